@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::database::{Project, Translation, TranslationStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MatchBand {
+    Exact,
+    Repetition,
+    Fuzzy95To99,
+    Fuzzy85To94,
+    Fuzzy75To84,
+    NoMatch,
+}
+
+impl MatchBand {
+    pub const ALL: [MatchBand; 6] = [
+        MatchBand::Exact,
+        MatchBand::Repetition,
+        MatchBand::Fuzzy95To99,
+        MatchBand::Fuzzy85To94,
+        MatchBand::Fuzzy75To84,
+        MatchBand::NoMatch,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatchBand::Exact => "100%",
+            MatchBand::Repetition => "Repetitions",
+            MatchBand::Fuzzy95To99 => "95-99%",
+            MatchBand::Fuzzy85To94 => "85-94%",
+            MatchBand::Fuzzy75To84 => "75-84%",
+            MatchBand::NoMatch => "No match",
+        }
+    }
+
+    fn from_score(score: u8) -> MatchBand {
+        match score {
+            100 => MatchBand::Exact,
+            95..=99 => MatchBand::Fuzzy95To99,
+            85..=94 => MatchBand::Fuzzy85To94,
+            75..=84 => MatchBand::Fuzzy75To84,
+            _ => MatchBand::NoMatch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnalysisExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Counts {
+    pub segments: u64,
+    pub words: u64,
+    pub characters: u64,
+    pub characters_with_spaces: u64,
+}
+
+impl Counts {
+    fn of(text: &str) -> Counts {
+        Counts {
+            segments: 1,
+            words: count_words(text),
+            characters: text.chars().filter(|c| !c.is_whitespace()).count() as u64,
+            characters_with_spaces: text.chars().count() as u64,
+        }
+    }
+
+    fn add(&mut self, other: &Counts) {
+        self.segments += other.segments;
+        self.words += other.words;
+        self.characters += other.characters;
+        self.characters_with_spaces += other.characters_with_spaces;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusBreakdown {
+    pub status: TranslationStatus,
+    pub counts: Counts,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandBreakdown {
+    pub band: MatchBand,
+    pub counts: Counts,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectAnalysis {
+    pub project_id: String,
+    pub project_name: String,
+    pub source_locale: Option<String>,
+    pub target_locale: Option<String>,
+    pub totals: Counts,
+    pub by_status: Vec<StatusBreakdown>,
+    pub by_band: Vec<BandBreakdown>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocaleAnalysis {
+    pub target_locale: Option<String>,
+    pub project_ids: Vec<String>,
+    pub totals: Counts,
+    pub by_status: Vec<StatusBreakdown>,
+    pub by_band: Vec<BandBreakdown>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisReport {
+    pub projects: Vec<ProjectAnalysis>,
+    pub locales: Vec<LocaleAnalysis>,
+}
+
+/// A confirmed translation that can serve as a leverage source.
+#[derive(Debug, Clone)]
+pub struct MemoryEntry {
+    pub translation_id: String,
+    pub source_text: String,
+}
+
+/// Counts words the way quoting tools do: whitespace-separated tokens for
+/// alphabetic scripts, and one word per character for Han and kana, which
+/// are written without spaces.
+pub fn count_words(text: &str) -> u64 {
+    let mut words = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            words += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        } else if c.is_whitespace() || !matches!(c, '\'' | '’' | '-' | '_' | '.' | ',') {
+            in_word = false;
+        }
+    }
+
+    words
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x31F0..=0x31FF   // Katakana phonetic extensions
+        | 0x3400..=0x4DBF   // CJK extension A
+        | 0x4E00..=0x9FFF   // CJK unified ideographs
+        | 0xF900..=0xFAFF   // CJK compatibility ideographs
+        | 0xFF66..=0xFF9F   // Half-width katakana
+        | 0x20000..=0x2FA1F // CJK extensions B and later
+    )
+}
+
+/// Similarity between two segments as a percentage, based on character edit
+/// distance. Both have their whitespace collapsed by `normalize` already.
+fn char_similarity(a: &[char], b: &[char]) -> u8 {
+    if a == b {
+        return 100;
+    }
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 100;
+    }
+
+    let distance = edit_distance(a, b);
+    let score = ((longest - distance) * 100) / longest;
+    // Only identical text is a 100% match.
+    score.min(99) as u8
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Memory entries with their text normalized once: by text for exact
+/// matches, and by length in characters for fuzzy ones.
+struct MemoryIndex<'a> {
+    exact: HashMap<String, Vec<&'a str>>,
+    by_length: BTreeMap<usize, Vec<(&'a str, Vec<char>)>>,
+}
+
+impl<'a> MemoryIndex<'a> {
+    fn new(memory: &'a [MemoryEntry]) -> Self {
+        let mut exact: HashMap<String, Vec<&str>> = HashMap::new();
+        let mut by_length: BTreeMap<usize, Vec<(&str, Vec<char>)>> = BTreeMap::new();
+        for entry in memory {
+            let text = normalize(&entry.source_text);
+            let chars: Vec<char> = text.chars().collect();
+            by_length
+                .entry(chars.len())
+                .or_default()
+                .push((entry.translation_id.as_str(), chars));
+            exact.entry(text).or_default().push(entry.translation_id.as_str());
+        }
+        MemoryIndex { exact, by_length }
+    }
+}
+
+/// Best leverage score for `source` among the memory, ignoring the entry for
+/// the segment itself.
+fn best_match(id: &str, source: &str, memory: &MemoryIndex) -> u8 {
+    let key = normalize(source);
+    if memory
+        .exact
+        .get(&key)
+        .is_some_and(|ids| ids.iter().any(|other| *other != id))
+    {
+        return 100;
+    }
+
+    // A 75% match cannot exist between segments whose lengths differ by more
+    // than a quarter, and the edit distance is at least the difference, so
+    // lengths are tried from the closest and stop once none can do better.
+    let chars: Vec<char> = key.chars().collect();
+    let length = chars.len();
+    let bound = |other: usize| (length.min(other) * 100 / length.max(other).max(1)).min(99) as u8;
+    let mut lengths: Vec<usize> = memory
+        .by_length
+        .range((length * 3).div_ceil(4)..=length * 4 / 3)
+        .map(|(other, _)| *other)
+        .collect();
+    lengths.sort_by_key(|other| std::cmp::Reverse(bound(*other)));
+
+    let mut best = 0;
+    for other in lengths {
+        if bound(other) <= best {
+            break;
+        }
+        for (entry_id, text) in &memory.by_length[&other] {
+            if *entry_id == id {
+                continue;
+            }
+            best = best.max(char_similarity(&chars, text));
+            if best == 99 {
+                return best;
+            }
+        }
+    }
+    best
+}
+
+pub fn analyze_project(project: &Project, translations: &[Translation], memory: &[MemoryEntry]) -> ProjectAnalysis {
+    let memory = MemoryIndex::new(memory);
+
+    let mut totals = Counts::default();
+    let mut by_status: Vec<StatusBreakdown> = TranslationStatus::ALL
+        .iter()
+        .map(|status| StatusBreakdown { status: *status, counts: Counts::default() })
+        .collect();
+    let mut by_band: Vec<BandBreakdown> = MatchBand::ALL
+        .iter()
+        .map(|band| BandBreakdown { band: *band, counts: Counts::default() })
+        .collect();
+    let mut seen = HashSet::new();
+
+    for translation in translations {
+        let counts = Counts::of(&translation.source_text);
+        totals.add(&counts);
+
+        if let Some(entry) = by_status.iter_mut().find(|entry| entry.status == translation.status) {
+            entry.counts.add(&counts);
+        }
+
+        let score = best_match(&translation.id, &translation.source_text, &memory);
+        let first_occurrence = seen.insert(normalize(&translation.source_text));
+        let band = if score < 100 && !first_occurrence {
+            MatchBand::Repetition
+        } else {
+            MatchBand::from_score(score)
+        };
+        if let Some(entry) = by_band.iter_mut().find(|entry| entry.band == band) {
+            entry.counts.add(&counts);
+        }
+    }
+
+    ProjectAnalysis {
+        project_id: project.id.clone(),
+        project_name: project.name.clone(),
+        source_locale: project.source_locale.clone(),
+        target_locale: project.target_locale.clone(),
+        totals,
+        by_status,
+        by_band,
+    }
+}
+
+/// Rolls project analyses up by target locale.
+pub fn summarize_locales(projects: &[ProjectAnalysis]) -> Vec<LocaleAnalysis> {
+    let mut locales: Vec<LocaleAnalysis> = Vec::new();
+
+    for project in projects {
+        let index = match locales.iter().position(|l| l.target_locale == project.target_locale) {
+            Some(index) => index,
+            None => {
+                locales.push(LocaleAnalysis {
+                    target_locale: project.target_locale.clone(),
+                    project_ids: Vec::new(),
+                    totals: Counts::default(),
+                    by_status: project
+                        .by_status
+                        .iter()
+                        .map(|entry| StatusBreakdown { status: entry.status, counts: Counts::default() })
+                        .collect(),
+                    by_band: project
+                        .by_band
+                        .iter()
+                        .map(|entry| BandBreakdown { band: entry.band, counts: Counts::default() })
+                        .collect(),
+                });
+                locales.len() - 1
+            }
+        };
+
+        let locale = &mut locales[index];
+        locale.project_ids.push(project.project_id.clone());
+        locale.totals.add(&project.totals);
+        for (sum, entry) in locale.by_status.iter_mut().zip(&project.by_status) {
+            sum.counts.add(&entry.counts);
+        }
+        for (sum, entry) in locale.by_band.iter_mut().zip(&project.by_band) {
+            sum.counts.add(&entry.counts);
+        }
+    }
+
+    locales
+}
+
+pub fn export_report(report: &AnalysisReport, format: AnalysisExportFormat) -> Result<String, String> {
+    match format {
+        AnalysisExportFormat::Json => serde_json::to_string_pretty(report).map_err(|e| e.to_string()),
+        AnalysisExportFormat::Csv => Ok(to_csv(report)),
+    }
+}
+
+fn to_csv(report: &AnalysisReport) -> String {
+    let mut out = String::from("scope,name,target_locale,breakdown,category,segments,words,characters,characters_with_spaces\n");
+
+    let mut push = |scope: &str, name: &str, locale: &Option<String>, breakdown: &str, category: &str, counts: &Counts| {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            scope,
+            csv_field(name),
+            csv_field(locale.as_deref().unwrap_or("")),
+            breakdown,
+            csv_field(category),
+            counts.segments,
+            counts.words,
+            counts.characters,
+            counts.characters_with_spaces
+        ));
+    };
+
+    for project in &report.projects {
+        push("project", &project.project_name, &project.target_locale, "total", "", &project.totals);
+        for entry in &project.by_status {
+            push("project", &project.project_name, &project.target_locale, "status", entry.status.as_str(), &entry.counts);
+        }
+        for entry in &project.by_band {
+            push("project", &project.project_name, &project.target_locale, "match", entry.band.label(), &entry.counts);
+        }
+    }
+
+    for locale in &report.locales {
+        let name = locale.target_locale.clone().unwrap_or_default();
+        push("locale", &name, &locale.target_locale, "total", "", &locale.totals);
+        for entry in &locale.by_status {
+            push("locale", &name, &locale.target_locale, "status", entry.status.as_str(), &entry.counts);
+        }
+        for entry in &locale.by_band {
+            push("locale", &name, &locale.target_locale, "match", entry.band.label(), &entry.counts);
+        }
+    }
+
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
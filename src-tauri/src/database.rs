@@ -1,13 +1,30 @@
-use sqlx::{SqlitePool, Row};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, Row, Transaction};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteRow};
+use sqlx::types::Json;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use tokio::sync::Mutex;
+
+use crate::analysis::{self, AnalysisReport, MemoryEntry};
+use crate::formats::inline::{self, InlineElement};
+use crate::formats::{self, placeholders, Placeholder, ResourceFormat, ResourceUnit};
+use crate::segmentation::{self, SegmentationRules};
+use crate::spreadsheet::{ChangeKind, SkippedChange, SpreadsheetApplyResult, SpreadsheetChange};
+use crate::workflow::{Workflow, WorkflowError, WorkflowRole, WorkflowStatus};
+
+const TRANSLATION_COLUMNS: &str = "id, project_id, sequence, resource_key, file_path, section, context, developer_comment, max_length, screenshot, source_text, target_text, notes, previous_source, fuzzy_target, obsolete, start_ms, end_ms, placeholders, status, COALESCE(workflow_status, status) AS workflow_status, locked, lock_reason, locked_by, locked_at, created_at, updated_at";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub id: String,
     pub name: String,
     pub description: Option<String>,
+    pub source_locale: Option<String>,
+    pub target_locale: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -16,21 +33,317 @@ pub struct Project {
 pub struct Translation {
     pub id: String,
     pub project_id: String,
+    /// The row's position in the project, in reading order.
+    #[serde(default)]
+    pub sequence: i64,
+    pub resource_key: Option<String>,
+    /// The path of the source file the row was imported from.
+    pub file_path: Option<String>,
+    /// The part of the file the row belongs to, such as the heading above a
+    /// paragraph.
+    pub section: Option<String>,
+    /// Disambiguates rows that share a key, like gettext's `msgctxt`.
+    pub context: Option<String>,
+    /// The comment written next to the string in the source file.
+    pub developer_comment: Option<String>,
+    /// The longest translation the UI has room for, in characters.
+    pub max_length: Option<i64>,
+    /// A path or URL of a screenshot showing where the string appears.
+    pub screenshot: Option<String>,
     pub source_text: String,
     pub target_text: Option<String>,
     pub notes: Option<String>,
+    /// The source text before a re-import changed it, when the row had a
+    /// translation at the time.
+    pub previous_source: Option<String>,
+    /// The translation of `previous_source`, kept as a fuzzy suggestion.
+    pub fuzzy_target: Option<String>,
+    /// The key is no longer in the source file. Obsolete rows are kept for
+    /// reference but left out of the project's translations.
+    #[serde(default)]
+    pub obsolete: bool,
+    /// Cue timing in milliseconds, for subtitle files.
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    /// Markup protected as numbered tags in the source text, for documents,
+    /// and the variables the source uses.
+    #[serde(default)]
+    pub placeholders: Vec<Placeholder>,
+    /// The source text as text runs, tags and variables, for editors. It is
+    /// built from `source_text` and `placeholders` rather than stored.
+    #[serde(default)]
+    pub source_content: Vec<InlineElement>,
+    #[serde(default)]
+    pub target_content: Option<Vec<InlineElement>>,
     pub status: TranslationStatus,
+    pub workflow_status: String,
+    pub locked: bool,
+    pub lock_reason: Option<String>,
+    pub locked_by: Option<String>,
+    pub locked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// A resource file imported into a project. The original content is kept so
+/// the file can be rebuilt with translations on export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub id: String,
+    pub project_id: String,
+    pub path: String,
+    pub format: ResourceFormat,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub file: SourceFile,
+    pub created: u64,
+    /// Keys left out because the file already has a row with the same key
+    /// and context.
+    pub duplicates: Vec<String>,
+}
+
+/// What a re-import of an updated source file changed, by resource key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReimportSummary {
+    pub file: SourceFile,
+    pub unchanged: u64,
+    /// Keys whose source text changed. Their rows are back in Draft, with the
+    /// old translation kept as a fuzzy suggestion.
+    pub changed: Vec<String>,
+    pub added: Vec<String>,
+    /// Keys that are no longer in the file.
+    pub obsoleted: Vec<String>,
+    /// Keys of locked rows the file changed or no longer has; the rows were
+    /// left as they were.
+    pub locked: Vec<String>,
+}
+
+/// A row for `create_translations_bulk`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewTranslation {
+    pub source_text: String,
+    #[serde(default)]
+    pub target_text: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub resource_key: Option<String>,
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+/// A change for `update_translations_bulk`. Fields left out keep their value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationUpdate {
+    pub id: String,
+    #[serde(default)]
+    pub target_text: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub status: Option<TranslationStatus>,
+    #[serde(default)]
+    pub workflow_status: Option<String>,
+}
+
+/// The outcome of one item of a bulk request, by its position in the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkItemResult {
+    pub index: usize,
+    pub id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// What a bulk request did. Items that fail validation are reported and
+/// skipped; the rest are written together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkResult {
+    pub succeeded: u64,
+    pub failed: u64,
+    pub items: Vec<BulkItemResult>,
+}
+
+impl BulkResult {
+    fn new() -> Self {
+        Self { succeeded: 0, failed: 0, items: Vec::new() }
+    }
+
+    fn push(&mut self, index: usize, id: Option<String>, outcome: Result<(), DbError>) {
+        let error = match outcome {
+            Ok(()) => {
+                self.succeeded += 1;
+                None
+            }
+            Err(error) => {
+                self.failed += 1;
+                Some(error.to_string())
+            }
+        };
+        self.items.push(BulkItemResult { index, id, error });
+    }
+}
+
+/// Picks translations within a project. Every criterion that is set must
+/// match; an empty selector matches the whole project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranslationSelector {
+    pub ids: Option<Vec<String>>,
+    pub status: Option<TranslationStatus>,
+    pub workflow_status: Option<String>,
+    pub source_contains: Option<String>,
+    pub target_contains: Option<String>,
+    /// Rows whose target is empty (true) or filled in (false).
+    #[serde(default)]
+    pub empty_target: Option<bool>,
+    #[serde(default)]
+    pub has_notes: Option<bool>,
+    #[serde(default)]
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Text found, ignoring ASCII case, in the key, source or target.
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranslationSort {
+    #[default]
+    Sequence,
+    Key,
+    Source,
+    Target,
+    Status,
+    UpdatedAt,
+}
+
+impl TranslationSort {
+    fn expression(self) -> &'static str {
+        match self {
+            TranslationSort::Sequence => "sequence",
+            TranslationSort::Key => "COALESCE(resource_key, '')",
+            TranslationSort::Source => "source_text",
+            TranslationSort::Target => "COALESCE(target_text, '')",
+            TranslationSort::Status => "COALESCE(workflow_status, status)",
+            TranslationSort::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// One page of a project's rows, picked and sorted by a
+/// [`TranslationQuery`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranslationQuery {
+    #[serde(flatten)]
+    pub selector: TranslationSelector,
+    /// Rows with (true) or without (false) QA issues.
+    #[serde(default)]
+    pub qa_issues: Option<bool>,
+    /// The ids of the rows QA flags, filled in before a query that filters
+    /// on `qa_issues`.
+    #[serde(skip)]
+    pub flagged: Option<Vec<String>>,
+    #[serde(default)]
+    pub sort: TranslationSort,
+    #[serde(default)]
+    pub descending: bool,
+    /// The `next_cursor` of the previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Rows per page, 100 by default and at most 1000.
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationPage {
+    pub items: Vec<Translation>,
+    /// Rows matching the filters across all pages.
+    pub total: i64,
+    /// Where the next page starts, or `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// The position a page ends at: the sort value and id of its last row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cursor {
+    value: CursorValue,
+    id: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CursorValue {
+    Integer(i64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockResult {
+    pub affected: u64,
+    pub skipped: u64,
+}
+
+/// Translations in a project that share the same source text and context,
+/// which are the rows `propagate_translation` fills in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepetitionGroup {
+    pub source_text: String,
+    pub context: Option<String>,
+    pub translation_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PropagationOptions {
+    /// Also move repetitions to the confirmed row's status, when the workflow
+    /// allows it.
+    #[serde(default)]
+    pub copy_status: bool,
+    /// Overwrite repetitions that are already confirmed with a different
+    /// target.
+    #[serde(default)]
+    pub overwrite_confirmed: bool,
+    #[serde(default)]
+    pub excluded_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PropagationResult {
+    pub updated: u64,
+    pub updated_ids: Vec<String>,
+    pub skipped_locked: u64,
+    pub skipped_confirmed: u64,
+    pub skipped_excluded: u64,
+    pub skipped_workflow: u64,
+    /// Repetitions in another context, where the same source may need a
+    /// different translation.
+    pub skipped_context: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 pub enum TranslationStatus {
     Draft,
     Validated,
     Approved,
 }
 
+impl TranslationStatus {
+    pub const ALL: [TranslationStatus; 3] = [
+        TranslationStatus::Draft,
+        TranslationStatus::Validated,
+        TranslationStatus::Approved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TranslationStatus::Draft => "Draft",
+            TranslationStatus::Validated => "Validated",
+            TranslationStatus::Approved => "Approved",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub id: String,
@@ -40,20 +353,169 @@ pub struct ChatMessage {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 pub enum ChatRole {
     User,
     Assistant,
 }
 
+impl ChatRole {
+    pub const ALL: [ChatRole; 2] = [ChatRole::User, ChatRole::Assistant];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+        }
+    }
+}
+
+/// A row whose stored enum value could not be decoded, along with the value
+/// it was (or would be) reset to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairedRow {
+    pub table: String,
+    pub id: String,
+    pub column: String,
+    pub invalid_value: Option<String>,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub dry_run: bool,
+    pub rows: Vec<RepairedRow>,
+    /// Invalid values on locked rows, which were left as they were.
+    pub locked: Vec<RepairedRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusCount {
+    pub status: TranslationStatus,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStatusCount {
+    pub workflow_status: String,
+    pub label: String,
+    pub category: TranslationStatus,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStatistics {
+    pub project_id: String,
+    pub total: i64,
+    pub by_status: Vec<StatusCount>,
+    pub by_workflow_status: Vec<WorkflowStatusCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupProgress {
+    pub total: i64,
+    /// Rows with a non-empty target.
+    pub translated: i64,
+    pub by_status: Vec<StatusCount>,
+}
+
+impl GroupProgress {
+    fn new() -> Self {
+        Self {
+            total: 0,
+            translated: 0,
+            by_status: TranslationStatus::ALL
+                .iter()
+                .map(|status| StatusCount { status: *status, count: 0 })
+                .collect(),
+        }
+    }
+
+    fn add(&mut self, status: TranslationStatus, count: i64, translated: i64) {
+        self.total += count;
+        self.translated += translated;
+        if let Some(entry) = self.by_status.iter_mut().find(|entry| entry.status == status) {
+            entry.count += count;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionProgress {
+    pub section: Option<String>,
+    pub progress: GroupProgress,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProgress {
+    /// `None` for rows that weren't imported from a file.
+    pub file_path: Option<String>,
+    pub progress: GroupProgress,
+    pub sections: Vec<SectionProgress>,
+}
+
+#[derive(Debug)]
+pub enum DbError {
+    Sqlx(sqlx::Error),
+    NotFound(String),
+    Duplicate(String),
+    Invalid(String),
+    Corrupt(String),
+    Workflow(WorkflowError),
+    Locked { id: String, reason: Option<String> },
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Sqlx(e) => write!(f, "{}", e),
+            DbError::NotFound(what) => write!(f, "{} not found", what),
+            DbError::Duplicate(what) => write!(f, "{} already exists", what),
+            DbError::Invalid(reason) => write!(f, "{}", reason),
+            DbError::Corrupt(reason) => write!(f, "Corrupt data: {}", reason),
+            DbError::Workflow(e) => write!(f, "{}", e),
+            DbError::Locked { id, reason: Some(reason) } => {
+                write!(f, "Translation {} is locked: {}", id, reason)
+            }
+            DbError::Locked { id, reason: None } => write!(f, "Translation {} is locked", id),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<sqlx::Error> for DbError {
+    fn from(e: sqlx::Error) -> Self {
+        DbError::Sqlx(e)
+    }
+}
+
+impl From<WorkflowError> for DbError {
+    fn from(e: WorkflowError) -> Self {
+        DbError::Workflow(e)
+    }
+}
+
 pub struct Database {
     pool: SqlitePool,
+    /// Held by every method that writes. SQLite takes one writer at a time,
+    /// and a transaction that reads before writing fails instead of waiting
+    /// when another write lands in between, so writers queue here while reads
+    /// go straight to the pool.
+    writer: Mutex<()>,
 }
 
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = SqlitePool::connect(database_url).await?;
-        let db = Database { pool };
+        let mut options = SqliteConnectOptions::from_str(database_url)?;
+        // In WAL mode reads carry on while a write is in progress. An
+        // in-memory database has no journal file to switch, so it keeps
+        // SQLite's default.
+        if !is_in_memory(database_url) {
+            options = options.journal_mode(SqliteJournalMode::Wal);
+        }
+        let pool = SqlitePool::connect_with(options).await?;
+        let db = Database { pool, writer: Mutex::new(()) };
         db.init().await?;
         Ok(db)
     }
@@ -109,20 +571,130 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Create project_workflows table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_workflows (
+                project_id TEXT PRIMARY KEY,
+                definition TEXT NOT NULL,
+                updated_at DATETIME NOT NULL,
+                FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create source_files table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS source_files (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                format TEXT NOT NULL,
+                content BLOB NOT NULL,
+                created_at DATETIME NOT NULL,
+                FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create project_segmentation table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_segmentation (
+                project_id TEXT PRIMARY KEY,
+                srx TEXT NOT NULL,
+                updated_at DATETIME NOT NULL,
+                FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.ensure_column("projects", "source_locale", "TEXT").await?;
+        self.ensure_column("projects", "target_locale", "TEXT").await?;
+        self.ensure_column("translations", "workflow_status", "TEXT").await?;
+        self.ensure_column("translations", "resource_key", "TEXT").await?;
+        self.ensure_column("translations", "locked", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_column("translations", "lock_reason", "TEXT").await?;
+        self.ensure_column("translations", "locked_by", "TEXT").await?;
+        self.ensure_column("translations", "locked_at", "DATETIME").await?;
+        self.ensure_column("translations", "start_ms", "INTEGER").await?;
+        self.ensure_column("translations", "end_ms", "INTEGER").await?;
+        self.ensure_column("translations", "placeholders", "TEXT").await?;
+        self.ensure_column("translations", "file_path", "TEXT").await?;
+        self.ensure_column("translations", "context", "TEXT").await?;
+        self.ensure_column("translations", "developer_comment", "TEXT").await?;
+        self.ensure_column("translations", "max_length", "INTEGER").await?;
+        self.ensure_column("translations", "screenshot", "TEXT").await?;
+        self.ensure_column("translations", "previous_source", "TEXT").await?;
+        self.ensure_column("translations", "fuzzy_target", "TEXT").await?;
+        self.ensure_column("translations", "obsolete", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_column("translations", "sequence", "INTEGER").await?;
+        self.ensure_column("translations", "section", "TEXT").await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS translations_sequence ON translations (project_id, sequence)")
+            .execute(&self.pool)
+            .await?;
+
+        // A key identifies one row per file and context, rows without a file
+        // counting as one more file. Rows without a key are left out.
+        sqlx::query(
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS translations_file_key
+            ON translations (project_id, COALESCE(file_path, ''), resource_key, COALESCE(context, ''))
+            WHERE resource_key IS NOT NULL
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds `column` to `table` when it is missing, so databases created by
+    /// older builds pick up new columns.
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await?;
+
+        if columns.iter().any(|row| row.get::<String, _>("name") == column) {
+            return Ok(());
+        }
+
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&self.pool)
+            .await?;
+
         Ok(())
     }
 
     // Project operations
-    pub async fn create_project(&self, name: String, description: Option<String>) -> Result<Project, sqlx::Error> {
+    pub async fn create_project(
+        &self,
+        name: String,
+        description: Option<String>,
+        source_locale: Option<String>,
+        target_locale: Option<String>,
+    ) -> Result<Project, sqlx::Error> {
+        let _write = self.writer.lock().await;
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
         
         sqlx::query(
-            "INSERT INTO projects (id, name, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?)"
+            "INSERT INTO projects (id, name, description, source_locale, target_locale, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&id)
         .bind(&name)
         .bind(&description)
+        .bind(&source_locale)
+        .bind(&target_locale)
         .bind(&now)
         .bind(&now)
         .execute(&self.pool)
@@ -132,13 +704,33 @@ impl Database {
             id,
             name,
             description,
+            source_locale,
+            target_locale,
             created_at: now,
             updated_at: now,
         })
     }
 
+    pub async fn set_project_locales(
+        &self,
+        id: &str,
+        source_locale: Option<String>,
+        target_locale: Option<String>,
+    ) -> Result<Option<Project>, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        sqlx::query("UPDATE projects SET source_locale = ?, target_locale = ?, updated_at = ? WHERE id = ?")
+            .bind(&source_locale)
+            .bind(&target_locale)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        self.get_project(id).await
+    }
+
     pub async fn get_projects(&self) -> Result<Vec<Project>, sqlx::Error> {
-        let rows = sqlx::query("SELECT id, name, description, created_at, updated_at FROM projects ORDER BY updated_at DESC")
+        let rows = sqlx::query("SELECT id, name, description, source_locale, target_locale, created_at, updated_at FROM projects ORDER BY updated_at DESC")
             .fetch_all(&self.pool)
             .await?;
 
@@ -146,6 +738,8 @@ impl Database {
             id: row.get("id"),
             name: row.get("name"),
             description: row.get("description"),
+            source_locale: row.get("source_locale"),
+            target_locale: row.get("target_locale"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         }).collect();
@@ -154,7 +748,7 @@ impl Database {
     }
 
     pub async fn get_project(&self, id: &str) -> Result<Option<Project>, sqlx::Error> {
-        let row = sqlx::query("SELECT id, name, description, created_at, updated_at FROM projects WHERE id = ?")
+        let row = sqlx::query("SELECT id, name, description, source_locale, target_locale, created_at, updated_at FROM projects WHERE id = ?")
             .bind(id)
             .fetch_optional(&self.pool)
             .await?;
@@ -164,6 +758,8 @@ impl Database {
                 id: row.get("id"),
                 name: row.get("name"),
                 description: row.get("description"),
+                source_locale: row.get("source_locale"),
+                target_locale: row.get("target_locale"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
             }))
@@ -172,120 +768,1701 @@ impl Database {
         }
     }
 
-    // Translation operations
-    pub async fn create_translation(&self, project_id: String, source_text: String) -> Result<Translation, sqlx::Error> {
-        let id = Uuid::new_v4().to_string();
+    // Workflow operations
+    pub async fn get_project_workflow(&self, project_id: &str) -> Result<Workflow, DbError> {
+        let row = sqlx::query("SELECT definition FROM project_workflows WHERE project_id = ?")
+            .bind(project_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => {
+                let definition: String = row.try_get("definition")?;
+                serde_json::from_str(&definition)
+                    .map_err(|e| DbError::Corrupt(format!("workflow for project {}: {}", project_id, e)))
+            }
+            None => Ok(Workflow::default()),
+        }
+    }
+
+    /// Stores a project's workflow. Translations whose status no longer exists
+    /// are moved to the first status of the same category, or to the initial
+    /// status when the category is gone too. Nothing is changed when a locked
+    /// translation would have to move.
+    pub async fn set_project_workflow(&self, project_id: &str, workflow: Workflow) -> Result<Workflow, DbError> {
+        let _write = self.writer.lock().await;
+        workflow.validate()?;
+        if self.get_project(project_id).await?.is_none() {
+            return Err(DbError::NotFound(format!("Project {}", project_id)));
+        }
+
+        let definition = serde_json::to_string(&workflow).map_err(|e| DbError::Corrupt(e.to_string()))?;
         let now = Utc::now();
-        
+
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query(
-            "INSERT INTO translations (id, project_id, source_text, created_at, updated_at) VALUES (?, ?, ?, ?, ?)"
+            "INSERT INTO project_workflows (project_id, definition, updated_at) VALUES (?, ?, ?) \
+             ON CONFLICT(project_id) DO UPDATE SET definition = excluded.definition, updated_at = excluded.updated_at"
         )
-        .bind(&id)
-        .bind(&project_id)
-        .bind(&source_text)
-        .bind(&now)
-        .bind(&now)
-        .execute(&self.pool)
+        .bind(project_id)
+        .bind(&definition)
+        .bind(now)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(Translation {
-            id,
-            project_id,
-            source_text,
-            target_text: None,
-            notes: None,
-            status: TranslationStatus::Draft,
-            created_at: now,
-            updated_at: now,
-        })
-    }
-
-    pub async fn get_translations(&self, project_id: &str) -> Result<Vec<Translation>, sqlx::Error> {
         let rows = sqlx::query(
-            "SELECT id, project_id, source_text, target_text, notes, status, created_at, updated_at FROM translations WHERE project_id = ? ORDER BY created_at ASC"
+            "SELECT id, status, COALESCE(workflow_status, status) AS workflow_status, locked, lock_reason \
+             FROM translations WHERE project_id = ?"
         )
         .bind(project_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *tx)
         .await?;
 
-        let translations = rows.into_iter().map(|row| {
-            let status_str: String = row.get("status");
-            let status = match status_str.as_str() {
-                "Draft" => TranslationStatus::Draft,
-                "Validated" => TranslationStatus::Validated,
-                "Approved" => TranslationStatus::Approved,
-                _ => TranslationStatus::Draft,
-            };
-
-            Translation {
-                id: row.get("id"),
-                project_id: row.get("project_id"),
-                source_text: row.get("source_text"),
-                target_text: row.get("target_text"),
-                notes: row.get("notes"),
-                status,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
+        for row in rows {
+            let current: String = row.try_get("workflow_status")?;
+            if workflow.status(&current).is_some() {
+                continue;
+            }
+            // Moving a locked row to another status would change it behind
+            // the lock, so the workflow has to keep the statuses they are in.
+            if row.try_get::<bool, _>("locked")? {
+                return Err(DbError::Locked {
+                    id: row.try_get("id")?,
+                    reason: row.try_get("lock_reason")?,
+                });
             }
-        }).collect();
 
-        Ok(translations)
-    }
+            let category: TranslationStatus = row.try_get("status")?;
+            let replacement = workflow
+                .status_for_category(category)
+                .or_else(|| workflow.status(&workflow.initial_status))
+                .ok_or_else(|| WorkflowError::UnknownStatus(workflow.initial_status.clone()))?;
 
-    pub async fn update_translation(&self, id: &str, target_text: Option<String>, notes: Option<String>, status: Option<TranslationStatus>) -> Result<(), sqlx::Error> {
-        let now = Utc::now();
-        
-        if let Some(target_text) = target_text {
-            sqlx::query("UPDATE translations SET target_text = ?, updated_at = ? WHERE id = ?")
-                .bind(&target_text)
-                .bind(&now)
-                .bind(id)
-                .execute(&self.pool)
+            sqlx::query("UPDATE translations SET status = ?, workflow_status = ? WHERE id = ?")
+                .bind(replacement.category)
+                .bind(&replacement.id)
+                .bind(row.try_get::<String, _>("id")?)
+                .execute(&mut *tx)
                 .await?;
         }
 
-        if let Some(notes) = notes {
-            sqlx::query("UPDATE translations SET notes = ?, updated_at = ? WHERE id = ?")
-                .bind(&notes)
-                .bind(&now)
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
-        }
+        tx.commit().await?;
 
-        if let Some(status) = status {
-            let status_str = match status {
-                TranslationStatus::Draft => "Draft",
-                TranslationStatus::Validated => "Validated",
-                TranslationStatus::Approved => "Approved",
-            };
-            sqlx::query("UPDATE translations SET status = ?, updated_at = ? WHERE id = ?")
-                .bind(status_str)
-                .bind(&now)
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
-        }
+        Ok(workflow)
+    }
 
-        Ok(())
+    // Segmentation operations
+    /// The SRX rules a project set for itself, if any.
+    pub async fn get_project_segmentation(&self, project_id: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT srx FROM project_segmentation WHERE project_id = ?")
+            .bind(project_id)
+            .fetch_optional(&self.pool)
+            .await
     }
 
-    // Chat operations
-    pub async fn add_chat_message(&self, project_id: String, role: ChatRole, content: String) -> Result<ChatMessage, sqlx::Error> {
+    /// The rules the project's documents are split into sentences with.
+    pub async fn get_segmentation_rules(&self, project_id: &str) -> Result<SegmentationRules, DbError> {
+        match self.get_project_segmentation(project_id).await? {
+            Some(srx) => SegmentationRules::from_srx(&srx)
+                .map_err(|e| DbError::Corrupt(format!("segmentation rules for project {}: {}", project_id, e))),
+            None => Ok(SegmentationRules::default()),
+        }
+    }
+
+    /// Stores SRX rules for a project, or goes back to the shipped rules when
+    /// `srx` is `None`. Documents already imported keep their segments.
+    pub async fn set_project_segmentation(&self, project_id: &str, srx: Option<String>) -> Result<(), DbError> {
+        let _write = self.writer.lock().await;
+        if self.get_project(project_id).await?.is_none() {
+            return Err(DbError::NotFound(format!("Project {}", project_id)));
+        }
+
+        match srx {
+            Some(srx) => {
+                SegmentationRules::from_srx(&srx).map_err(DbError::Invalid)?;
+                sqlx::query(
+                    "INSERT INTO project_segmentation (project_id, srx, updated_at) VALUES (?, ?, ?) \
+                     ON CONFLICT(project_id) DO UPDATE SET srx = excluded.srx, updated_at = excluded.updated_at"
+                )
+                .bind(project_id)
+                .bind(&srx)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM project_segmentation WHERE project_id = ?")
+                    .bind(project_id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_project_statistics(&self, project_id: &str) -> Result<ProjectStatistics, DbError> {
+        let workflow = self.get_project_workflow(project_id).await?;
+
+        let rows = sqlx::query(
+            "SELECT status, COALESCE(workflow_status, status) AS workflow_status, COUNT(*) AS count \
+             FROM translations WHERE project_id = ? AND obsolete = 0 GROUP BY status, COALESCE(workflow_status, status)"
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut by_status: Vec<StatusCount> = TranslationStatus::ALL
+            .iter()
+            .map(|status| StatusCount { status: *status, count: 0 })
+            .collect();
+        let mut by_workflow_status: Vec<WorkflowStatusCount> = workflow
+            .statuses
+            .iter()
+            .map(|status| WorkflowStatusCount {
+                workflow_status: status.id.clone(),
+                label: status.label.clone(),
+                category: status.category,
+                count: 0,
+            })
+            .collect();
+        let mut total = 0;
+
+        for row in rows {
+            let status: TranslationStatus = row.try_get("status")?;
+            let workflow_status: String = row.try_get("workflow_status")?;
+            let count: i64 = row.try_get("count")?;
+
+            total += count;
+            if let Some(entry) = by_status.iter_mut().find(|entry| entry.status == status) {
+                entry.count += count;
+            }
+            match by_workflow_status.iter_mut().find(|entry| entry.workflow_status == workflow_status) {
+                Some(entry) => entry.count += count,
+                None => by_workflow_status.push(WorkflowStatusCount {
+                    label: workflow_status.clone(),
+                    workflow_status,
+                    category: status,
+                    count,
+                }),
+            }
+        }
+
+        Ok(ProjectStatistics {
+            project_id: project_id.to_string(),
+            total,
+            by_status,
+            by_workflow_status,
+        })
+    }
+
+    /// Progress of each file and of each section within it, in the order
+    /// they first appear in the project.
+    pub async fn get_group_progress(&self, project_id: &str) -> Result<Vec<FileProgress>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT file_path, section, status, COUNT(*) AS count, \
+             SUM(CASE WHEN target_text IS NOT NULL AND target_text != '' THEN 1 ELSE 0 END) AS translated, \
+             MIN(sequence) AS first \
+             FROM translations WHERE project_id = ? AND obsolete = 0 \
+             GROUP BY file_path, section, status ORDER BY first"
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut files: Vec<FileProgress> = Vec::new();
+        for row in rows {
+            let file_path: Option<String> = row.try_get("file_path")?;
+            let section: Option<String> = row.try_get("section")?;
+            let status: TranslationStatus = row.try_get("status")?;
+            let count: i64 = row.try_get("count")?;
+            let translated: i64 = row.try_get("translated")?;
+
+            let file = match files.iter().position(|file| file.file_path == file_path) {
+                Some(index) => &mut files[index],
+                None => {
+                    files.push(FileProgress {
+                        file_path,
+                        progress: GroupProgress::new(),
+                        sections: Vec::new(),
+                    });
+                    files.last_mut().expect("just pushed")
+                }
+            };
+            file.progress.add(status, count, translated);
+            let group = match file.sections.iter().position(|group| group.section == section) {
+                Some(index) => &mut file.sections[index],
+                None => {
+                    file.sections.push(SectionProgress {
+                        section,
+                        progress: GroupProgress::new(),
+                    });
+                    file.sections.last_mut().expect("just pushed")
+                }
+            };
+            group.progress.add(status, count, translated);
+        }
+
+        Ok(files)
+    }
+
+    // Analysis operations
+    /// Confirmed translations between the given locales, used as the leverage
+    /// source when analysing projects.
+    pub async fn get_memory_entries(
+        &self,
+        source_locale: Option<&str>,
+        target_locale: Option<&str>,
+    ) -> Result<Vec<MemoryEntry>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT t.id, t.source_text FROM translations t JOIN projects p ON p.id = t.project_id \
+             WHERE p.source_locale IS ? AND p.target_locale IS ? AND t.status IN ('Validated', 'Approved') \
+             AND t.target_text IS NOT NULL AND t.target_text != '' AND t.obsolete = 0"
+        )
+        .bind(source_locale)
+        .bind(target_locale)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(MemoryEntry {
+                    translation_id: row.try_get("id")?,
+                    source_text: row.try_get("source_text")?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn analyze_projects(&self, project_ids: &[String]) -> Result<AnalysisReport, DbError> {
+        let mut projects = Vec::new();
+
+        for project_id in project_ids {
+            let project = self
+                .get_project(project_id)
+                .await?
+                .ok_or_else(|| DbError::NotFound(format!("Project {}", project_id)))?;
+            let translations = self.get_translations(project_id).await?;
+            let memory = self
+                .get_memory_entries(project.source_locale.as_deref(), project.target_locale.as_deref())
+                .await?;
+
+            projects.push(analysis::analyze_project(&project, &translations, &memory));
+        }
+
+        let locales = analysis::summarize_locales(&projects);
+        Ok(AnalysisReport { projects, locales })
+    }
+
+    // Translation operations
+    pub async fn create_translation(&self, project_id: String, source_text: String) -> Result<Translation, DbError> {
+        let _write = self.writer.lock().await;
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
-        
-        let role_str = match role {
-            ChatRole::User => "User",
-            ChatRole::Assistant => "Assistant",
+        let workflow = self.get_project_workflow(&project_id).await?;
+        let initial = workflow.resolve(None, None)?;
+
+        let mut tx = self.pool.begin().await?;
+        let sequence = next_sequence(&mut tx, &project_id).await?;
+        sqlx::query(
+            "INSERT INTO translations (id, project_id, sequence, source_text, status, workflow_status, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&id)
+        .bind(&project_id)
+        .bind(sequence)
+        .bind(&source_text)
+        .bind(initial.category)
+        .bind(&initial.id)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let source_content = inline::content(&source_text, &[]);
+        Ok(Translation {
+            id,
+            project_id,
+            sequence,
+            resource_key: None,
+            file_path: None,
+            section: None,
+            context: None,
+            developer_comment: None,
+            max_length: None,
+            screenshot: None,
+            source_text,
+            target_text: None,
+            notes: None,
+            previous_source: None,
+            fuzzy_target: None,
+            obsolete: false,
+            start_ms: None,
+            end_ms: None,
+            placeholders: Vec::new(),
+            source_content,
+            target_content: None,
+            status: initial.category,
+            workflow_status: initial.id.clone(),
+            locked: false,
+            lock_reason: None,
+            locked_by: None,
+            locked_at: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Creates many rows at the end of the project in one transaction. Rows
+    /// without source text are rejected, and so are rows whose key and
+    /// context another row without a file, or an earlier item, already has.
+    pub async fn create_translations_bulk(&self, project_id: &str, items: Vec<NewTranslation>) -> Result<BulkResult, DbError> {
+        let _write = self.writer.lock().await;
+        self.get_project(project_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Project {}", project_id)))?;
+        let workflow = self.get_project_workflow(project_id).await?;
+        let initial = workflow.resolve(None, None)?;
+        let now = Utc::now();
+        let mut result = BulkResult::new();
+
+        let mut tx = self.pool.begin().await?;
+        let mut sequence = next_sequence(&mut tx, project_id).await?;
+        for (index, item) in items.into_iter().enumerate() {
+            if item.source_text.trim().is_empty() {
+                result.push(index, None, Err(DbError::Invalid("Source text is empty".to_string())));
+                continue;
+            }
+
+            let id = Uuid::new_v4().to_string();
+            let key = item.resource_key.filter(|key| !key.is_empty());
+            let inserted = sqlx::query(
+                "INSERT OR IGNORE INTO translations (id, project_id, sequence, resource_key, context, source_text, target_text, notes, \
+                 status, workflow_status, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&id)
+            .bind(project_id)
+            .bind(sequence)
+            .bind(&key)
+            .bind(&item.context)
+            .bind(&item.source_text)
+            .bind(&item.target_text)
+            .bind(&item.notes)
+            .bind(initial.category)
+            .bind(&initial.id)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                let duplicate = DbError::Duplicate(format!("Key {}", key.unwrap_or_default()));
+                result.push(index, None, Err(duplicate));
+                continue;
+            }
+            sequence += 1;
+            result.push(index, Some(id), Ok(()));
+        }
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    // Import operations
+    /// Stores an imported file and creates one translation per unit, all in one
+    /// transaction.
+    pub async fn import_resource(
+        &self,
+        project_id: &str,
+        path: &str,
+        format: ResourceFormat,
+        content: &[u8],
+        units: Vec<ResourceUnit>,
+    ) -> Result<ImportSummary, DbError> {
+        let _write = self.writer.lock().await;
+        if self.get_project(project_id).await?.is_none() {
+            return Err(DbError::NotFound(format!("Project {}", project_id)));
+        }
+        let existing = sqlx::query("SELECT id FROM source_files WHERE project_id = ? AND path = ?")
+            .bind(project_id)
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await?;
+        if existing.is_some() {
+            return Err(DbError::Duplicate(format!("Source file {}", path)));
+        }
+        let workflow = self.get_project_workflow(project_id).await?;
+        let now = Utc::now();
+
+        let file = SourceFile {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            path: path.to_string(),
+            format,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("INSERT INTO source_files (id, project_id, path, format, content, created_at) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(&file.id)
+            .bind(project_id)
+            .bind(path)
+            .bind(format)
+            .bind(content)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+        let keys: Vec<String> = units.iter().map(|unit| unit.key.clone()).collect();
+        let skipped = insert_units(&mut tx, project_id, Some(path), &workflow, units, now).await?;
+
+        tx.commit().await?;
+
+        let created = (keys.len() - skipped.len()) as u64;
+        let duplicates = skipped.into_iter().map(|index| keys[index].clone()).collect();
+        Ok(ImportSummary { file, created, duplicates })
+    }
+
+    /// Merges an updated version of an imported file into the project. Rows
+    /// are matched by key and context: unchanged rows are kept, rows whose
+    /// source changed go back to Draft with their translation moved to a
+    /// fuzzy suggestion, new keys get new rows and missing keys are marked
+    /// obsolete. Document segments are keyed by position, so they are matched
+    /// by text instead, wherever they were, and then an edited segment to the
+    /// row between the same neighbours; a row takes the key of the segment it
+    /// is matched to. Locked rows keep their key and content and are listed
+    /// in `locked` when the file changed or dropped them. The stored file is
+    /// replaced so exports follow the new layout.
+    pub async fn reimport_resource(
+        &self,
+        file_id: &str,
+        content: &[u8],
+        units: Vec<ResourceUnit>,
+    ) -> Result<ReimportSummary, DbError> {
+        let _write = self.writer.lock().await;
+        let (file, _) = self
+            .get_source_file(file_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Source file {}", file_id)))?;
+        let workflow = self.get_project_workflow(&file.project_id).await?;
+        let draft = draft_status(&workflow)?;
+        let now = Utc::now();
+
+        let document = file.format.is_document();
+
+        // Document rows that lost their key to a moved segment are still
+        // matched by their text.
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM translations WHERE project_id = ? AND file_path = ? AND (resource_key IS NOT NULL OR ?) \
+             ORDER BY sequence",
+            TRANSLATION_COLUMNS
+        ))
+        .bind(&file.project_id)
+        .bind(&file.path)
+        .bind(document)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut existing: HashMap<String, Translation> = HashMap::new();
+        let mut ids_by_key: HashMap<(String, Option<String>), String> = HashMap::new();
+        let mut ids_by_source: HashMap<String, Vec<String>> = HashMap::new();
+        // Row ids in the file's order, for document segments edited in place.
+        let mut row_ids = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let translation = translation_from_row(row)?;
+            row_ids.push(translation.id.clone());
+            if let Some(key) = translation.resource_key.clone() {
+                ids_by_key.insert((key, translation.context.clone()), translation.id.clone());
+            }
+            if document {
+                ids_by_source.entry(translation.source_text.clone()).or_default().push(translation.id.clone());
+            }
+            existing.insert(translation.id.clone(), translation);
+        }
+
+        let mut summary = ReimportSummary {
+            file,
+            unchanged: 0,
+            changed: Vec::new(),
+            added: Vec::new(),
+            obsoleted: Vec::new(),
+            locked: Vec::new(),
+        };
+        let mut new_units = Vec::new();
+        // The file's keys in their new order.
+        let mut order = Vec::new();
+
+        let mut seen = HashSet::new();
+        let mut incoming = Vec::new();
+        for unit in units {
+            // Like a first import, a key that repeats keeps its first unit.
+            let identity = (unit.key.clone(), unit.context.clone());
+            if unit.key.is_empty() || !seen.insert(identity.clone()) {
+                continue;
+            }
+            order.push(identity);
+            incoming.push(unit);
+        }
+
+        // Pair units with rows: by key where the text is the same, then
+        // document segments by text and by place, and other units by key.
+        let mut matches: Vec<Option<Translation>> = Vec::with_capacity(incoming.len());
+        for unit in &incoming {
+            let id = ids_by_key
+                .get(&(unit.key.clone(), unit.context.clone()))
+                .filter(|id| existing.get(*id).is_some_and(|t| t.source_text == unit.source_text));
+            matches.push(id.and_then(|id| existing.remove(id)));
+        }
+        // Locked rows keep their key and content: a unit with a locked row's
+        // key goes to that row, and no other row may take the key.
+        let locked_ids: Vec<String> = existing.values().filter(|t| t.locked).map(|t| t.id.clone()).collect();
+        let mut locked_rows: HashMap<String, Translation> =
+            locked_ids.iter().filter_map(|id| existing.remove_entry(id)).collect();
+        for (unit, current) in incoming.iter().zip(matches.iter_mut()) {
+            if current.is_none() {
+                *current = ids_by_key
+                    .get(&(unit.key.clone(), unit.context.clone()))
+                    .and_then(|id| locked_rows.remove(id));
+            }
+        }
+        for (unit, current) in incoming.iter().zip(matches.iter_mut()) {
+            if current.is_some() {
+                continue;
+            }
+            if let Some(ids) = ids_by_source.get(&unit.source_text) {
+                *current = ids.iter().find_map(|id| existing.remove(id));
+            }
+        }
+        if document {
+            match_in_place(&mut matches, &row_ids, &mut existing);
+        } else {
+            for (unit, current) in incoming.iter().zip(matches.iter_mut()) {
+                if current.is_none() {
+                    *current = ids_by_key
+                        .get(&(unit.key.clone(), unit.context.clone()))
+                        .and_then(|id| existing.remove(id));
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Rows that take another key let go of theirs first, since the key
+        // they take may still be held by a row that moves too, or by a row
+        // that is gone, which is left without one.
+        let mut moved = Vec::new();
+        for (unit, current) in incoming.iter().zip(&matches) {
+            if let Some(current) = current.as_ref().filter(|t| t.resource_key.as_ref() != Some(&unit.key)) {
+                sqlx::query("UPDATE translations SET resource_key = NULL WHERE id = ?")
+                    .bind(&current.id)
+                    .execute(&mut *tx)
+                    .await?;
+                moved.push((current.id.clone(), unit.key.clone()));
+            }
+        }
+        let taken: HashSet<&String> = moved.iter().map(|(_, key)| key).collect();
+        let unkeyed: Vec<String> = existing
+            .values()
+            .filter(|t| t.resource_key.as_ref().is_some_and(|key| taken.contains(key)))
+            .map(|t| t.id.clone())
+            .collect();
+        for id in unkeyed {
+            let Some(translation) = existing.remove(&id) else {
+                continue;
+            };
+            sqlx::query("UPDATE translations SET resource_key = NULL, obsolete = 1, updated_at = ? WHERE id = ?")
+                .bind(now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            if !translation.obsolete {
+                summary.obsoleted.extend(translation.resource_key);
+            }
+        }
+        for (id, key) in &moved {
+            sqlx::query("UPDATE translations SET resource_key = ? WHERE id = ?")
+                .bind(key)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for (unit, current) in incoming.into_iter().zip(matches) {
+            let Some(current) = current else {
+                summary.added.push(unit.key.clone());
+                new_units.push(unit);
+                continue;
+            };
+
+            if current.source_text == unit.source_text {
+                if current.locked {
+                    summary.unchanged += 1;
+                    continue;
+                }
+                sqlx::query(
+                    "UPDATE translations SET section = ?, developer_comment = ?, placeholders = ?, start_ms = ?, end_ms = ?, obsolete = 0 WHERE id = ?"
+                )
+                .bind(&unit.section)
+                .bind(&unit.developer_comment)
+                .bind(Some(Json(&unit.placeholders)).filter(|p| !p.is_empty()))
+                .bind(unit.start_ms)
+                .bind(unit.end_ms)
+                .bind(&current.id)
+                .execute(&mut *tx)
+                .await?;
+                summary.unchanged += 1;
+                continue;
+            }
+
+            if current.locked {
+                summary.locked.push(unit.key);
+                continue;
+            }
+
+            // A row without a translation has nothing to keep, so an earlier
+            // suggestion stays.
+            let (previous_source, fuzzy_target) = match current.target_text.filter(|t| !t.is_empty()) {
+                Some(target) => (Some(current.source_text), Some(target)),
+                None => (current.previous_source, current.fuzzy_target),
+            };
+            sqlx::query(
+                "UPDATE translations SET source_text = ?, target_text = NULL, previous_source = ?, fuzzy_target = ?, \
+                 section = ?, developer_comment = ?, placeholders = ?, start_ms = ?, end_ms = ?, status = ?, workflow_status = ?, \
+                 obsolete = 0, updated_at = ? WHERE id = ?"
+            )
+            .bind(&unit.source_text)
+            .bind(&previous_source)
+            .bind(&fuzzy_target)
+            .bind(&unit.section)
+            .bind(&unit.developer_comment)
+            .bind(Some(Json(&unit.placeholders)).filter(|p| !p.is_empty()))
+            .bind(unit.start_ms)
+            .bind(unit.end_ms)
+            .bind(draft.category)
+            .bind(&draft.id)
+            .bind(now)
+            .bind(&current.id)
+            .execute(&mut *tx)
+            .await?;
+            summary.changed.push(unit.key);
+        }
+
+        for translation in existing.into_values() {
+            if translation.obsolete {
+                continue;
+            }
+            sqlx::query("UPDATE translations SET obsolete = 1, updated_at = ? WHERE id = ?")
+                .bind(now)
+                .bind(&translation.id)
+                .execute(&mut *tx)
+                .await?;
+            summary.obsoleted.extend(translation.resource_key);
+        }
+        summary.obsoleted.sort();
+        // Locked rows the file no longer has stay as they are.
+        let mut gone: Vec<String> = locked_rows
+            .into_values()
+            .filter(|t| !t.obsolete)
+            .filter_map(|t| t.resource_key)
+            .collect();
+        gone.sort();
+        summary.locked.extend(gone);
+
+        insert_units(&mut tx, &summary.file.project_id, Some(&summary.file.path), &workflow, new_units, now).await?;
+
+        // New keys were added at the end of the project; put the file's rows
+        // in the file's order, with obsolete rows after the rest.
+        let rows = sqlx::query(
+            "SELECT id, resource_key, context FROM translations WHERE project_id = ? AND file_path = ? ORDER BY sequence"
+        )
+        .bind(&summary.file.project_id)
+        .bind(&summary.file.path)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut ids = Vec::with_capacity(rows.len());
+        let mut ids_by_identity = HashMap::new();
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            if let Some(key) = row.try_get::<Option<String>, _>("resource_key")? {
+                ids_by_identity.insert((key, row.try_get::<Option<String>, _>("context")?), id.clone());
+            }
+            ids.push(id);
+        }
+        let mut ordered: Vec<String> = order.iter().filter_map(|identity| ids_by_identity.remove(identity)).collect();
+        let placed: HashSet<String> = ordered.iter().cloned().collect();
+        ordered.extend(ids.into_iter().filter(|id| !placed.contains(id)));
+        reorder(&mut tx, &ordered).await?;
+
+        sqlx::query("UPDATE source_files SET content = ? WHERE id = ?")
+            .bind(content)
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+
+    /// Applies the changes a user accepted from a spreadsheet re-import.
+    /// Updates go through the same lock and workflow checks as manual edits;
+    /// the ones that fail are reported rather than aborting the rest.
+    pub async fn apply_spreadsheet_changes(
+        &self,
+        project_id: &str,
+        changes: &[SpreadsheetChange],
+        role: Option<WorkflowRole>,
+    ) -> Result<SpreadsheetApplyResult, DbError> {
+        let workflow = self.get_project_workflow(project_id).await?;
+        let mut result = SpreadsheetApplyResult::default();
+        let mut units = Vec::new();
+        // The spreadsheet row of each unit.
+        let mut unit_rows = Vec::new();
+
+        for change in changes {
+            let skip = |reason: String| SkippedChange { row: change.row, reason };
+
+            match (change.kind, &change.translation_id) {
+                (ChangeKind::Added, _) => {
+                    let status = match change.new_status.as_deref() {
+                        Some(id) => match workflow.status(id) {
+                            Some(status) => Some(status),
+                            None => {
+                                result.skipped.push(skip(WorkflowError::UnknownStatus(id.to_string()).to_string()));
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+                    units.push(ResourceUnit {
+                        key: change.key.clone().unwrap_or_default(),
+                        context: change.context.clone(),
+                        source_text: change.source_text.clone(),
+                        target_text: change.new_target.clone(),
+                        notes: change.new_notes.clone(),
+                        status: status.map(|s| s.category),
+                        workflow_status: status.map(|s| s.id.clone()),
+                        ..Default::default()
+                    });
+                    unit_rows.push(change.row);
+                }
+                (ChangeKind::Modified, Some(id)) => {
+                    match self.get_translation(id).await? {
+                        Some(current) if current.project_id == project_id => {}
+                        _ => {
+                            result.skipped.push(skip(format!("Translation {} not found in project", id)));
+                            continue;
+                        }
+                    }
+                    match self
+                        .update_translation(
+                            id,
+                            change.new_target.clone(),
+                            change.new_notes.clone(),
+                            None,
+                            change.new_status.clone(),
+                            role,
+                        )
+                        .await
+                    {
+                        Ok(()) => result.updated += 1,
+                        Err(DbError::Sqlx(e)) => return Err(DbError::Sqlx(e)),
+                        Err(e) => result.skipped.push(skip(e.to_string())),
+                    }
+                }
+                (ChangeKind::Modified, None) => result.skipped.push(skip("No translation to update".to_string())),
+            }
+        }
+
+        if !units.is_empty() {
+            let count = units.len();
+            let skipped = self.import_translations(project_id, units).await?;
+            result.added = (count - skipped.len()) as u64;
+            for index in skipped {
+                result.skipped.push(SkippedChange {
+                    row: unit_rows[index],
+                    reason: "A row with the same key and context already exists".to_string(),
+                });
+            }
+            result.skipped.sort_by_key(|skipped| skipped.row);
+        }
+
+        Ok(result)
+    }
+
+    /// Creates one translation per unit without a backing source file, for
+    /// imports such as spreadsheets that aren't exported back as files.
+    /// Returns the positions of the units left out because their key and
+    /// context were taken.
+    pub async fn import_translations(&self, project_id: &str, units: Vec<ResourceUnit>) -> Result<Vec<usize>, DbError> {
+        let _write = self.writer.lock().await;
+        if self.get_project(project_id).await?.is_none() {
+            return Err(DbError::NotFound(format!("Project {}", project_id)));
+        }
+        let workflow = self.get_project_workflow(project_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let skipped = insert_units(&mut tx, project_id, None, &workflow, units, Utc::now()).await?;
+        tx.commit().await?;
+
+        Ok(skipped)
+    }
+
+    pub async fn get_source_files(&self, project_id: &str) -> Result<Vec<SourceFile>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, project_id, path, format, created_at FROM source_files WHERE project_id = ? ORDER BY created_at ASC"
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(source_file_from_row).collect()
+    }
+
+    /// A source file together with its original content.
+    pub async fn get_source_file(&self, id: &str) -> Result<Option<(SourceFile, Vec<u8>)>, sqlx::Error> {
+        let row = sqlx::query("SELECT id, project_id, path, format, content, created_at FROM source_files WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some((source_file_from_row(&row)?, row.try_get("content")?))),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_translation(&self, id: &str) -> Result<Option<Translation>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM translations WHERE id = ?", TRANSLATION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(translation_from_row).transpose()
+    }
+
+    pub async fn get_translations(&self, project_id: &str) -> Result<Vec<Translation>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM translations WHERE project_id = ? AND obsolete = 0 ORDER BY sequence ASC, created_at ASC",
+            TRANSLATION_COLUMNS
+        ))
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(translation_from_row).collect()
+    }
+
+    /// The project's current rows that `selector` picks, in order.
+    pub async fn select_translations(&self, project_id: &str, selector: &TranslationSelector) -> Result<Vec<Translation>, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM translations", TRANSLATION_COLUMNS));
+        push_selector_conditions(&mut query, project_id, selector);
+        query.push(" AND obsolete = 0 ORDER BY sequence ASC, created_at ASC");
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        rows.iter().map(translation_from_row).collect()
+    }
+
+    /// A page of the project's rows matching `query`, with the number of rows
+    /// that match overall.
+    pub async fn query_translations(&self, project_id: &str, query: &TranslationQuery) -> Result<TranslationPage, DbError> {
+        let limit = query.limit.unwrap_or(100).clamp(1, 1000) as usize;
+        let cursor = match &query.cursor {
+            Some(cursor) => Some(
+                serde_json::from_str::<Cursor>(cursor)
+                    .map_err(|_| DbError::Invalid(format!("Invalid cursor {}", cursor)))?,
+            ),
+            None => None,
+        };
+        let flagged = query
+            .qa_issues
+            .map(|_| serde_json::to_string(query.flagged.as_deref().unwrap_or_default()).expect("ids serialize"));
+        let push_filters = |builder: &mut QueryBuilder<'_, Sqlite>| {
+            push_selector_conditions(builder, project_id, &query.selector);
+            builder.push(" AND obsolete = 0");
+            if let (Some(qa_issues), Some(flagged)) = (query.qa_issues, &flagged) {
+                builder.push(if qa_issues { " AND id IN " } else { " AND id NOT IN " });
+                builder.push("(SELECT value FROM json_each(");
+                builder.push_bind(flagged.clone());
+                builder.push("))");
+            }
+        };
+
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM translations");
+        push_filters(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let expression = query.sort.expression();
+        let direction = if query.descending { "DESC" } else { "ASC" };
+        let mut select = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {}, {} AS sort_value FROM translations",
+            TRANSLATION_COLUMNS, expression
+        ));
+        push_filters(&mut select);
+        if let Some(cursor) = cursor {
+            select.push(format!(" AND ({}, id) {} (", expression, if query.descending { "<" } else { ">" }));
+            match cursor.value {
+                CursorValue::Integer(value) => select.push_bind(value),
+                CursorValue::Text(value) => select.push_bind(value),
+            };
+            select.push(", ");
+            select.push_bind(cursor.id);
+            select.push(")");
+        }
+        select.push(format!(" ORDER BY {} {}, id {} LIMIT ", expression, direction, direction));
+        select.push_bind(limit as i64 + 1);
+        let rows = select.build().fetch_all(&self.pool).await?;
+
+        let next_cursor = match rows.get(limit) {
+            Some(_) => {
+                let last = &rows[limit - 1];
+                let value = match query.sort {
+                    TranslationSort::Sequence => CursorValue::Integer(last.try_get("sort_value")?),
+                    _ => CursorValue::Text(last.try_get("sort_value")?),
+                };
+                let cursor = Cursor { value, id: last.try_get("id")? };
+                Some(serde_json::to_string(&cursor).expect("cursors serialize"))
+            }
+            None => None,
+        };
+        let items = rows.iter().take(limit).map(translation_from_row).collect::<Result<_, _>>()?;
+
+        Ok(TranslationPage { items, total, next_cursor })
+    }
+
+    /// Updates a translation. Locked translations are rejected outright, and a
+    /// status change is checked against the project's workflow before anything
+    /// is written; `role` is the role of whoever is performing the change.
+    pub async fn update_translation(
+        &self,
+        id: &str,
+        target_text: Option<String>,
+        notes: Option<String>,
+        status: Option<TranslationStatus>,
+        workflow_status: Option<String>,
+        role: Option<WorkflowRole>,
+    ) -> Result<(), DbError> {
+        let _write = self.writer.lock().await;
+        let now = Utc::now();
+
+        let current = self
+            .get_translation(id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Translation {}", id)))?;
+        ensure_unlocked(&current)?;
+
+        let transition = if status.is_some() || workflow_status.is_some() {
+            let workflow = self.get_project_workflow(&current.project_id).await?;
+            let target = workflow.resolve(status, workflow_status.as_deref())?;
+            workflow.check_transition(&current.workflow_status, &target.id, role)?;
+            Some((target.category, target.id.clone()))
+        } else {
+            None
+        };
+
+        if let Some(target_text) = target_text {
+            sqlx::query("UPDATE translations SET target_text = ?, updated_at = ? WHERE id = ? AND locked = 0")
+                .bind(&target_text)
+                .bind(&now)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some(notes) = notes {
+            sqlx::query("UPDATE translations SET notes = ?, updated_at = ? WHERE id = ? AND locked = 0")
+                .bind(&notes)
+                .bind(&now)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        if let Some((status, workflow_status)) = transition {
+            sqlx::query("UPDATE translations SET status = ?, workflow_status = ?, updated_at = ? WHERE id = ? AND locked = 0")
+                .bind(status)
+                .bind(&workflow_status)
+                .bind(&now)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies many updates in one transaction. Each is checked the way
+    /// `update_translation` checks one, against the rows as they were before
+    /// the request; an id may only appear once.
+    pub async fn update_translations_bulk(
+        &self,
+        items: Vec<TranslationUpdate>,
+        role: Option<WorkflowRole>,
+    ) -> Result<BulkResult, DbError> {
+        let _write = self.writer.lock().await;
+        let now = Utc::now();
+        let mut workflows: HashMap<String, Workflow> = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut result = BulkResult::new();
+
+        let mut tx = self.pool.begin().await?;
+        for (index, item) in items.into_iter().enumerate() {
+            if !seen.insert(item.id.clone()) {
+                let error = DbError::Invalid(format!("Translation {} is updated more than once", item.id));
+                result.push(index, Some(item.id), Err(error));
+                continue;
+            }
+            let row = sqlx::query(&format!("SELECT {} FROM translations WHERE id = ?", TRANSLATION_COLUMNS))
+                .bind(&item.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let current = match row {
+                Some(row) => translation_from_row(&row)?,
+                None => {
+                    let error = DbError::NotFound(format!("Translation {}", item.id));
+                    result.push(index, Some(item.id), Err(error));
+                    continue;
+                }
+            };
+            if let Err(error) = ensure_unlocked(&current) {
+                result.push(index, Some(item.id), Err(error));
+                continue;
+            }
+
+            let transition = if item.status.is_some() || item.workflow_status.is_some() {
+                if !workflows.contains_key(&current.project_id) {
+                    let workflow = self.get_project_workflow(&current.project_id).await?;
+                    workflows.insert(current.project_id.clone(), workflow);
+                }
+                let workflow = &workflows[&current.project_id];
+                let target = workflow
+                    .resolve(item.status, item.workflow_status.as_deref())
+                    .and_then(|target| {
+                        workflow.check_transition(&current.workflow_status, &target.id, role)?;
+                        Ok(target)
+                    });
+                match target {
+                    Ok(target) => Some((target.category, target.id.clone())),
+                    Err(error) => {
+                        result.push(index, Some(item.id), Err(error.into()));
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let (status, workflow_status) = transition.unzip();
+
+            sqlx::query(
+                "UPDATE translations SET target_text = COALESCE(?, target_text), notes = COALESCE(?, notes), \
+                 status = COALESCE(?, status), workflow_status = COALESCE(?, workflow_status), updated_at = ? WHERE id = ?"
+            )
+            .bind(&item.target_text)
+            .bind(&item.notes)
+            .bind(status)
+            .bind(&workflow_status)
+            .bind(now)
+            .bind(&item.id)
+            .execute(&mut *tx)
+            .await?;
+            result.push(index, Some(item.id), Ok(()));
+        }
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    /// Replaces the reference information kept with a string. It describes the
+    /// source rather than the translation, so locked rows can be updated too.
+    pub async fn set_translation_details(
+        &self,
+        id: &str,
+        developer_comment: Option<String>,
+        max_length: Option<i64>,
+        screenshot: Option<String>,
+    ) -> Result<(), DbError> {
+        let _write = self.writer.lock().await;
+        let result = sqlx::query(
+            "UPDATE translations SET developer_comment = ?, max_length = ?, screenshot = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(&developer_comment)
+        .bind(max_length)
+        .bind(&screenshot)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("Translation {}", id)));
+        }
+        Ok(())
+    }
+
+    // Segment operations
+    /// Splits a translation into consecutive rows at character offsets of its
+    /// source text. The first part keeps the row and the rest are inserted
+    /// right after it. The translation no longer matches any one part, so it
+    /// is kept as a fuzzy suggestion and every part starts over in Draft.
+    /// Keyed rows become `<key>#1`, `<key>#2` and so on, which document
+    /// exports join again.
+    pub async fn split_translation(&self, id: &str, positions: &[usize]) -> Result<Vec<Translation>, DbError> {
+        let _write = self.writer.lock().await;
+        let current = self
+            .get_translation(id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Translation {}", id)))?;
+        ensure_unlocked(&current)?;
+        self.ensure_document_row(&current).await?;
+        if positions.is_empty() {
+            return Err(DbError::Invalid("Nothing to split: no position given".to_string()));
+        }
+
+        let offsets = positions
+            .iter()
+            .map(|&position| {
+                current
+                    .source_text
+                    .char_indices()
+                    .nth(position)
+                    .map(|(offset, _)| offset)
+                    .ok_or_else(|| DbError::Invalid(format!("Cannot split at character {}", position)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let parts = placeholders::split(&current.source_text, &current.placeholders, &offsets).map_err(DbError::Invalid)?;
+
+        let workflow = self.get_project_workflow(&current.project_id).await?;
+        let draft = draft_status(&workflow)?;
+        let (previous_source, fuzzy_target) = match current.target_text.clone().filter(|t| !t.is_empty()) {
+            Some(target) => (Some(current.source_text.clone()), Some(target)),
+            None => (current.previous_source.clone(), current.fuzzy_target.clone()),
+        };
+        let now = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE translations SET sequence = sequence + ? WHERE project_id = ? AND sequence > ?")
+            .bind(parts.len() as i64 - 1)
+            .bind(&current.project_id)
+            .bind(current.sequence)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut ids = Vec::with_capacity(parts.len());
+        for (index, (source_text, used)) in parts.into_iter().enumerate() {
+            if index == 0 {
+                sqlx::query(
+                    "UPDATE translations SET source_text = ?, target_text = NULL, previous_source = ?, fuzzy_target = ?, \
+                     placeholders = ?, status = ?, workflow_status = ?, updated_at = ? WHERE id = ?"
+                )
+                .bind(&source_text)
+                .bind(&previous_source)
+                .bind(&fuzzy_target)
+                .bind(Some(Json(&used)).filter(|p| !p.is_empty()))
+                .bind(draft.category)
+                .bind(&draft.id)
+                .bind(now)
+                .bind(&current.id)
+                .execute(&mut *tx)
+                .await?;
+                ids.push(current.id.clone());
+                continue;
+            }
+
+            // Parts get their final keys once they are all in place.
+            let part_id = Uuid::new_v4().to_string();
+            sqlx::query(
+                "INSERT INTO translations (id, project_id, sequence, resource_key, file_path, section, context, developer_comment, max_length, \
+                 screenshot, source_text, placeholders, status, workflow_status, created_at, updated_at) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&part_id)
+            .bind(&current.project_id)
+            .bind(current.sequence + index as i64)
+            .bind(current.resource_key.as_ref().map(|_| &part_id))
+            .bind(&current.file_path)
+            .bind(&current.section)
+            .bind(&current.context)
+            .bind(&current.developer_comment)
+            .bind(current.max_length)
+            .bind(&current.screenshot)
+            .bind(&source_text)
+            .bind(Some(Json(&used)).filter(|p| !p.is_empty()))
+            .bind(draft.category)
+            .bind(&draft.id)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            ids.push(part_id);
+        }
+
+        if let Some(key) = &current.resource_key {
+            renumber_parts(&mut tx, &current.project_id, current.file_path.as_deref(), base_key(key)).await?;
+        }
+
+        tx.commit().await?;
+
+        let mut translations = Vec::with_capacity(ids.len());
+        for id in &ids {
+            if let Some(translation) = self.get_translation(id).await? {
+                translations.push(translation);
+            }
+        }
+        Ok(translations)
+    }
+
+    /// Merges consecutive rows back into one, the first of them. Sources are
+    /// joined in order; the translations are too when every part has one,
+    /// otherwise the merged row has none. Either way it goes back to Draft.
+    pub async fn merge_translations(&self, ids: &[String]) -> Result<Translation, DbError> {
+        let _write = self.writer.lock().await;
+        let mut rows = Vec::with_capacity(ids.len());
+        for id in ids {
+            let translation = self
+                .get_translation(id)
+                .await?
+                .ok_or_else(|| DbError::NotFound(format!("Translation {}", id)))?;
+            ensure_unlocked(&translation)?;
+            rows.push(translation);
+        }
+        rows.sort_by_key(|t| t.sequence);
+        rows.dedup_by(|a, b| a.id == b.id);
+        if rows.len() < 2 {
+            return Err(DbError::Invalid("Nothing to merge: select at least two segments".to_string()));
+        }
+
+        let first = &rows[0];
+        let base = first.resource_key.as_deref().map(base_key);
+        let same_segment = rows.iter().all(|t| {
+            t.project_id == first.project_id
+                && t.file_path == first.file_path
+                && t.context == first.context
+                && t.resource_key.as_deref().map(base_key) == base
+        });
+        if !same_segment {
+            return Err(DbError::Invalid("Only parts of the same segment can be merged".to_string()));
+        }
+        self.ensure_document_row(first).await?;
+
+        let between: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM translations WHERE project_id = ? AND obsolete = 0 AND sequence BETWEEN ? AND ?"
+        )
+        .bind(&first.project_id)
+        .bind(first.sequence)
+        .bind(rows[rows.len() - 1].sequence)
+        .fetch_one(&self.pool)
+        .await?;
+        if between != rows.len() as i64 {
+            return Err(DbError::Invalid("Only consecutive segments can be merged".to_string()));
+        }
+
+        let join = |texts: Vec<&str>| {
+            texts.into_iter().fold(String::new(), |mut joined, text| {
+                if !joined.is_empty() {
+                    joined.push_str(segmentation::joiner(&joined, text));
+                }
+                joined.push_str(text);
+                joined
+            })
+        };
+        let source_text = join(rows.iter().map(|t| t.source_text.as_str()).collect());
+        let target_text = rows
+            .iter()
+            .map(|t| t.target_text.as_deref().filter(|text| !text.is_empty()))
+            .collect::<Option<Vec<_>>>()
+            .map(join);
+        let notes = rows
+            .iter()
+            .filter_map(|t| t.notes.as_deref().filter(|notes| !notes.is_empty()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut merged_placeholders: Vec<Placeholder> = Vec::new();
+        for placeholder in rows.iter().flat_map(|t| &t.placeholders) {
+            if !merged_placeholders.iter().any(|p| p.id == placeholder.id) {
+                merged_placeholders.push(placeholder.clone());
+            }
+        }
+
+        let workflow = self.get_project_workflow(&first.project_id).await?;
+        let draft = draft_status(&workflow)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        for translation in &rows[1..] {
+            sqlx::query("DELETE FROM translations WHERE id = ?")
+                .bind(&translation.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "UPDATE translations SET source_text = ?, target_text = ?, notes = ?, placeholders = ?, status = ?, \
+             workflow_status = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(&source_text)
+        .bind(&target_text)
+        .bind(Some(notes).filter(|notes| !notes.is_empty()))
+        .bind(Some(Json(&merged_placeholders)).filter(|p| !p.is_empty()))
+        .bind(draft.category)
+        .bind(&draft.id)
+        .bind(Utc::now())
+        .bind(&first.id)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(base) = base {
+            renumber_parts(&mut tx, &first.project_id, first.file_path.as_deref(), base).await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_translation(&first.id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Translation {}", first.id)))
+    }
+
+    /// Keyed rows can only be split or merged when they come from a document,
+    /// whose export joins the parts again; other formats write every key as
+    /// it is, and a key without a file may be exported with any of them.
+    /// Rows without a key are never exported, so they can always be.
+    async fn ensure_document_row(&self, translation: &Translation) -> Result<(), DbError> {
+        if translation.resource_key.is_none() {
+            return Ok(());
+        }
+        let format: Option<ResourceFormat> = match &translation.file_path {
+            Some(path) => {
+                sqlx::query_scalar("SELECT format FROM source_files WHERE project_id = ? AND path = ?")
+                    .bind(&translation.project_id)
+                    .bind(path)
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+            None => None,
+        };
+        match format {
+            Some(format) if format.is_document() => Ok(()),
+            Some(format) => Err(DbError::Invalid(format!(
+                "Strings from {:?} files cannot be split or merged, only document segments",
+                format
+            ))),
+            None => Err(DbError::Invalid(
+                "Keyed strings without a document source file cannot be split or merged".to_string(),
+            )),
+        }
+    }
+
+    // Ordering operations
+    /// Puts rows in a section, or takes them out of one with `None`.
+    pub async fn set_translation_section(&self, ids: &[String], section: Option<String>) -> Result<u64, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE translations SET section = ");
+        query.push_bind(section);
+        query.push(", updated_at = ");
+        query.push_bind(Utc::now());
+        query.push(" WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        query.push(")");
+
+        Ok(query.build().execute(&self.pool).await?.rows_affected())
+    }
+
+    /// Moves rows, keeping their order between them, to right after `after`,
+    /// or to the start of the project when it is `None`.
+    pub async fn move_translations(&self, project_id: &str, ids: &[String], after: Option<&str>) -> Result<(), DbError> {
+        let _write = self.writer.lock().await;
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query("SELECT id, sequence FROM translations WHERE project_id = ? ORDER BY sequence, created_at")
+            .bind(project_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut order = Vec::with_capacity(rows.len());
+        let mut current = HashMap::with_capacity(rows.len());
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            current.insert(id.clone(), row.try_get::<i64, _>("sequence")?);
+            order.push(id);
+        }
+
+        let moving: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        if let Some(missing) = ids.iter().find(|id| !current.contains_key(*id)) {
+            return Err(DbError::NotFound(format!("Translation {}", missing)));
+        }
+        if let Some(after) = after {
+            if moving.contains(after) {
+                return Err(DbError::Invalid("Segments cannot be moved after one of themselves".to_string()));
+            }
+            if !current.contains_key(after) {
+                return Err(DbError::NotFound(format!("Translation {}", after)));
+            }
+        }
+
+        let (moved, mut order): (Vec<String>, Vec<String>) =
+            order.into_iter().partition(|id| moving.contains(id.as_str()));
+        let index = match after {
+            Some(after) => order.iter().position(|id| *id == after).map_or(0, |index| index + 1),
+            None => 0,
+        };
+        order.splice(index..index, moved);
+
+        for (position, id) in order.iter().enumerate() {
+            let sequence = position as i64 + 1;
+            if current.get(id) != Some(&sequence) {
+                sqlx::query("UPDATE translations SET sequence = ? WHERE id = ?")
+                    .bind(sequence)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Puts rows in the given order within the places they already take, so
+    /// the rows around them stay where they are.
+    pub async fn reorder_translations(&self, project_id: &str, ids: &[String]) -> Result<(), DbError> {
+        let _write = self.writer.lock().await;
+        let unique: HashSet<&String> = ids.iter().collect();
+        if unique.len() != ids.len() {
+            return Err(DbError::Invalid("A segment can only appear once in the new order".to_string()));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for id in ids {
+            let found: Option<String> = sqlx::query_scalar("SELECT id FROM translations WHERE id = ? AND project_id = ?")
+                .bind(id)
+                .bind(project_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if found.is_none() {
+                return Err(DbError::NotFound(format!("Translation {}", id)));
+            }
+        }
+        reorder(&mut tx, ids).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Lock operations
+    pub async fn lock_translations(
+        &self,
+        project_id: &str,
+        selector: &TranslationSelector,
+        reason: Option<String>,
+        locked_by: String,
+    ) -> Result<LockResult, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        let now = Utc::now();
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE translations SET locked = 1, lock_reason = ");
+        query.push_bind(reason);
+        query.push(", locked_by = ");
+        query.push_bind(locked_by);
+        query.push(", locked_at = ");
+        query.push_bind(now);
+        query.push(", updated_at = ");
+        query.push_bind(now);
+        push_selector_conditions(&mut query, project_id, selector);
+        query.push(" AND locked = 0");
+
+        let affected = query.build().execute(&self.pool).await?.rows_affected();
+        let matched = self.count_selected(project_id, selector).await?;
+
+        Ok(LockResult {
+            affected,
+            skipped: matched.saturating_sub(affected),
+        })
+    }
+
+    /// Unlocks the selected translations. Locks owned by someone other than
+    /// `user` are left in place unless a project manager is unlocking.
+    pub async fn unlock_translations(
+        &self,
+        project_id: &str,
+        selector: &TranslationSelector,
+        user: &str,
+        role: Option<WorkflowRole>,
+    ) -> Result<LockResult, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        let now = Utc::now();
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "UPDATE translations SET locked = 0, lock_reason = NULL, locked_by = NULL, locked_at = NULL, updated_at = ",
+        );
+        query.push_bind(now);
+        push_selector_conditions(&mut query, project_id, selector);
+        query.push(" AND locked = 1");
+        if role != Some(WorkflowRole::ProjectManager) {
+            query.push(" AND (locked_by IS NULL OR locked_by = ");
+            query.push_bind(user.to_string());
+            query.push(")");
+        }
+
+        let affected = query.build().execute(&self.pool).await?.rows_affected();
+
+        let mut locked = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM translations");
+        push_selector_conditions(&mut locked, project_id, selector);
+        locked.push(" AND locked = 1");
+        let skipped: i64 = locked.build_query_scalar().fetch_one(&self.pool).await?;
+
+        Ok(LockResult {
+            affected,
+            skipped: skipped as u64,
+        })
+    }
+
+    async fn count_selected(&self, project_id: &str, selector: &TranslationSelector) -> Result<u64, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM translations");
+        push_selector_conditions(&mut query, project_id, selector);
+        let count: i64 = query.build_query_scalar().fetch_one(&self.pool).await?;
+        Ok(count as u64)
+    }
+
+    // Repetition operations
+    pub async fn get_repetition_groups(&self, project_id: &str) -> Result<Vec<RepetitionGroup>, sqlx::Error> {
+        let translations = self.get_translations(project_id).await?;
+
+        let mut groups: Vec<RepetitionGroup> = Vec::new();
+        let mut index: HashMap<(String, Option<String>), usize> = HashMap::new();
+        for translation in translations {
+            let identity = (translation.source_text.clone(), translation.context.clone());
+            match index.get(&identity) {
+                Some(&i) => groups[i].translation_ids.push(translation.id),
+                None => {
+                    index.insert(identity, groups.len());
+                    groups.push(RepetitionGroup {
+                        source_text: translation.source_text,
+                        context: translation.context,
+                        translation_ids: vec![translation.id],
+                    });
+                }
+            }
+        }
+
+        groups.retain(|group| group.translation_ids.len() > 1);
+        Ok(groups)
+    }
+
+    /// Copies the target text of translation `id`, which has to be confirmed,
+    /// to every other translation in the project with the same source text
+    /// and context. Locked rows, excluded rows and (unless asked to) rows
+    /// already confirmed with a different target are left alone.
+    pub async fn propagate_translation(
+        &self,
+        id: &str,
+        options: &PropagationOptions,
+        role: Option<WorkflowRole>,
+    ) -> Result<PropagationResult, DbError> {
+        let _write = self.writer.lock().await;
+        let origin = self
+            .get_translation(id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("Translation {}", id)))?;
+        let mut result = PropagationResult::default();
+
+        let target_text = match origin.target_text.as_deref() {
+            Some(text) if !text.is_empty() => text.to_string(),
+            _ => return Ok(result),
         };
+        if origin.status == TranslationStatus::Draft {
+            return Err(DbError::Invalid(format!(
+                "Translation {} is a draft; confirm it before propagating it",
+                id
+            )));
+        }
+        let workflow = self.get_project_workflow(&origin.project_id).await?;
+        let now = Utc::now();
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM translations WHERE project_id = ? AND source_text = ? AND id != ? AND obsolete = 0",
+            TRANSLATION_COLUMNS
+        ))
+        .bind(&origin.project_id)
+        .bind(&origin.source_text)
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+
+        for row in &rows {
+            let repetition = translation_from_row(row)?;
+
+            if repetition.context != origin.context {
+                result.skipped_context += 1;
+                continue;
+            }
+            if options.excluded_ids.contains(&repetition.id) {
+                result.skipped_excluded += 1;
+                continue;
+            }
+            if repetition.locked {
+                result.skipped_locked += 1;
+                continue;
+            }
+            if repetition.status != TranslationStatus::Draft
+                && repetition.target_text.as_deref() != Some(target_text.as_str())
+                && !options.overwrite_confirmed
+            {
+                result.skipped_confirmed += 1;
+                continue;
+            }
+
+            let (status, workflow_status) = if options.copy_status {
+                if workflow
+                    .check_transition(&repetition.workflow_status, &origin.workflow_status, role)
+                    .is_err()
+                {
+                    result.skipped_workflow += 1;
+                    continue;
+                }
+                (origin.status, origin.workflow_status.clone())
+            } else {
+                (repetition.status, repetition.workflow_status.clone())
+            };
+
+            sqlx::query(
+                "UPDATE translations SET target_text = ?, status = ?, workflow_status = ?, updated_at = ? WHERE id = ? AND locked = 0"
+            )
+            .bind(&target_text)
+            .bind(status)
+            .bind(&workflow_status)
+            .bind(now)
+            .bind(&repetition.id)
+            .execute(&mut *tx)
+            .await?;
+
+            result.updated += 1;
+            result.updated_ids.push(repetition.id);
+        }
+
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    // Chat operations
+    pub async fn add_chat_message(&self, project_id: String, role: ChatRole, content: String) -> Result<ChatMessage, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
 
         sqlx::query(
             "INSERT INTO chat_messages (id, project_id, role, content, created_at) VALUES (?, ?, ?, ?, ?)"
         )
         .bind(&id)
         .bind(&project_id)
-        .bind(role_str)
+        .bind(role)
         .bind(&content)
         .bind(&now)
         .execute(&self.pool)
@@ -308,23 +2485,762 @@ impl Database {
         .fetch_all(&self.pool)
         .await?;
 
-        let messages = rows.into_iter().map(|row| {
-            let role_str: String = row.get("role");
-            let role = match role_str.as_str() {
-                "User" => ChatRole::User,
-                "Assistant" => ChatRole::Assistant,
-                _ => ChatRole::User,
-            };
+        rows.iter().map(chat_message_from_row).collect()
+    }
 
-            ChatMessage {
+    // Maintenance operations
+    pub async fn repair_invalid_enum_values(&self, dry_run: bool) -> Result<RepairReport, sqlx::Error> {
+        let _write = self.writer.lock().await;
+        let statuses: Vec<&str> = TranslationStatus::ALL.iter().map(|s| s.as_str()).collect();
+        let roles: Vec<&str> = ChatRole::ALL.iter().map(|r| r.as_str()).collect();
+
+        let mut rows = self
+            .find_invalid_values("translations", "status", &statuses, TranslationStatus::Draft.as_str())
+            .await?;
+        rows.extend(
+            self.find_invalid_values("chat_messages", "role", &roles, ChatRole::User.as_str())
+                .await?,
+        );
+
+        // Locked rows are reported but not changed.
+        let locked_ids: HashSet<String> = sqlx::query_scalar("SELECT id FROM translations WHERE locked = 1")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+        let (locked, rows): (Vec<RepairedRow>, Vec<RepairedRow>) = rows
+            .into_iter()
+            .partition(|row| row.table == "translations" && locked_ids.contains(&row.id));
+
+        if !dry_run {
+            for row in &rows {
+                let sql = format!("UPDATE {} SET {} = ? WHERE id = ?", row.table, row.column);
+                sqlx::query(&sql)
+                    .bind(&row.replacement)
+                    .bind(&row.id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(RepairReport { dry_run, rows, locked })
+    }
+
+    async fn find_invalid_values(
+        &self,
+        table: &str,
+        column: &str,
+        allowed: &[&str],
+        replacement: &str,
+    ) -> Result<Vec<RepairedRow>, sqlx::Error> {
+        let placeholders = vec!["?"; allowed.len()].join(", ");
+        let sql = format!(
+            "SELECT id, CAST({column} AS TEXT) AS value FROM {table} WHERE {column} IS NULL OR typeof({column}) != 'text' OR {column} NOT IN ({placeholders})"
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in allowed {
+            query = query.bind(*value);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| RepairedRow {
+                table: table.to_string(),
                 id: row.get("id"),
-                project_id: row.get("project_id"),
-                role,
-                content: row.get("content"),
-                created_at: row.get("created_at"),
+                column: column.to_string(),
+                invalid_value: row.get("value"),
+                replacement: replacement.to_string(),
+            })
+            .collect())
+    }
+}
+
+pub(crate) fn ensure_unlocked(translation: &Translation) -> Result<(), DbError> {
+    if translation.locked {
+        Err(DbError::Locked {
+            id: translation.id.clone(),
+            reason: translation.lock_reason.clone(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Appends a `WHERE` clause restricting the query to `project_id` and the
+/// criteria in `selector`.
+fn push_selector_conditions(query: &mut QueryBuilder<'_, Sqlite>, project_id: &str, selector: &TranslationSelector) {
+    query.push(" WHERE project_id = ");
+    query.push_bind(project_id.to_string());
+
+    if let Some(ids) = &selector.ids {
+        if ids.is_empty() {
+            query.push(" AND 0");
+        } else {
+            query.push(" AND id IN (");
+            let mut separated = query.separated(", ");
+            for id in ids {
+                separated.push_bind(id.clone());
             }
-        }).collect();
+            separated.push_unseparated(")");
+        }
+    }
+    if let Some(status) = selector.status {
+        query.push(" AND status = ");
+        query.push_bind(status);
+    }
+    if let Some(workflow_status) = &selector.workflow_status {
+        query.push(" AND COALESCE(workflow_status, status) = ");
+        query.push_bind(workflow_status.clone());
+    }
+    if let Some(text) = &selector.source_contains {
+        query.push(" AND instr(source_text, ");
+        query.push_bind(text.clone());
+        query.push(") > 0");
+    }
+    if let Some(text) = &selector.target_contains {
+        query.push(" AND instr(COALESCE(target_text, ''), ");
+        query.push_bind(text.clone());
+        query.push(") > 0");
+    }
+    if let Some(empty) = selector.empty_target {
+        query.push(if empty { " AND COALESCE(target_text, '') = ''" } else { " AND COALESCE(target_text, '') != ''" });
+    }
+    if let Some(has_notes) = selector.has_notes {
+        query.push(if has_notes { " AND COALESCE(notes, '') != ''" } else { " AND COALESCE(notes, '') = ''" });
+    }
+    if let Some(since) = selector.modified_since {
+        query.push(" AND updated_at >= ");
+        query.push_bind(since);
+    }
+    if let Some(text) = &selector.text {
+        let text = text.to_lowercase();
+        query.push(" AND (instr(lower(COALESCE(resource_key, '')), ");
+        query.push_bind(text.clone());
+        query.push(") > 0 OR instr(lower(source_text), ");
+        query.push_bind(text.clone());
+        query.push(") > 0 OR instr(lower(COALESCE(target_text, '')), ");
+        query.push_bind(text);
+        query.push(") > 0)");
+    }
+}
+
+/// Pairs each unmatched document segment with the first live row left
+/// between the rows of the matched segments around it, so a paragraph
+/// edited in place keeps its row. `row_ids` are the file's rows in order.
+fn match_in_place(
+    matches: &mut [Option<Translation>],
+    row_ids: &[String],
+    existing: &mut HashMap<String, Translation>,
+) {
+    let position: HashMap<&str, usize> = row_ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+    let place = |current: &Option<Translation>| {
+        current.as_ref().filter(|t| !t.obsolete).map(|t| position[t.id.as_str()])
+    };
+    // The place of the next matched row after each segment.
+    let mut next = vec![row_ids.len(); matches.len()];
+    let mut after = row_ids.len();
+    for (i, current) in matches.iter().enumerate().rev() {
+        next[i] = after;
+        after = place(current).unwrap_or(after);
+    }
+
+    let mut start = 0;
+    for (i, current) in matches.iter_mut().enumerate() {
+        if current.is_none() {
+            let id = row_ids
+                .get(start..next[i])
+                .unwrap_or_default()
+                .iter()
+                .find(|id| existing.get(*id).is_some_and(|t| !t.obsolete))
+                .cloned();
+            *current = id.and_then(|id| existing.remove(&id));
+        }
+        if let Some(place) = place(current) {
+            start = place + 1;
+        }
+    }
+}
+
+/// Creates one translation per unit, after the project's last row. A key
+/// that repeats within a file keeps its first row; the positions of the
+/// units left out are returned.
+async fn insert_units(
+    tx: &mut Transaction<'_, Sqlite>,
+    project_id: &str,
+    file_path: Option<&str>,
+    workflow: &Workflow,
+    units: Vec<ResourceUnit>,
+    now: DateTime<Utc>,
+) -> Result<Vec<usize>, DbError> {
+    let mut skipped = Vec::new();
+    let mut sequence = next_sequence(tx, project_id).await?;
+    for (index, unit) in units.into_iter().enumerate() {
+        let status = workflow.resolve(unit.status, unit.workflow_status.as_deref())?;
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO translations (id, project_id, sequence, resource_key, file_path, section, context, developer_comment, max_length, source_text, target_text, notes, start_ms, end_ms, placeholders, status, workflow_status, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(project_id)
+        .bind(sequence)
+        .bind(Some(&unit.key).filter(|key| !key.is_empty()))
+        .bind(file_path)
+        .bind(&unit.section)
+        .bind(&unit.context)
+        .bind(&unit.developer_comment)
+        .bind(unit.max_length)
+        .bind(&unit.source_text)
+        .bind(&unit.target_text)
+        .bind(&unit.notes)
+        .bind(unit.start_ms)
+        .bind(unit.end_ms)
+        .bind(Some(Json(&unit.placeholders)).filter(|p| !p.is_empty()))
+        .bind(status.category)
+        .bind(&status.id)
+        .bind(now)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            skipped.push(index);
+        } else {
+            sequence += 1;
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Whether `database_url` names an in-memory database, like `sqlite::memory:`
+/// or a `mode=memory` URI.
+fn is_in_memory(database_url: &str) -> bool {
+    database_url.contains(":memory:") || database_url.contains("mode=memory")
+}
+
+/// The status rows go back to when their source changes.
+fn draft_status(workflow: &Workflow) -> Result<&WorkflowStatus, WorkflowError> {
+    match workflow.status_for_category(TranslationStatus::Draft) {
+        Some(status) => Ok(status),
+        None => workflow.resolve(None, None),
+    }
+}
+
+/// The segment key behind a sentence key written `<key>#<n>`.
+fn base_key(key: &str) -> &str {
+    formats::split_key(key).map_or(key, |(base, _)| base)
+}
+
+/// Numbers the rows of a split segment `<base>#1`, `<base>#2`... in order,
+/// or gives the key back to the segment when one row is left. Keys are set
+/// to the row ids first so the renumbering can't collide with itself.
+async fn renumber_parts(
+    tx: &mut Transaction<'_, Sqlite>,
+    project_id: &str,
+    file_path: Option<&str>,
+    base: &str,
+) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, resource_key FROM translations WHERE project_id = ? AND file_path IS ? AND resource_key IS NOT NULL AND obsolete = 0 ORDER BY sequence"
+    )
+    .bind(project_id)
+    .bind(file_path)
+    .fetch_all(&mut **tx)
+    .await?;
+    // Parts just inserted by a split still have their id as the key.
+    let mut ids = Vec::new();
+    for row in &rows {
+        let id: String = row.try_get("id")?;
+        let key: String = row.try_get("resource_key")?;
+        if base_key(&key) == base || key == id {
+            ids.push(id);
+        }
+    }
+
+    for id in &ids {
+        sqlx::query("UPDATE translations SET resource_key = id WHERE id = ?")
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    for (index, id) in ids.iter().enumerate() {
+        let key = match ids.len() {
+            1 => base.to_string(),
+            _ => format!("{}#{}", base, index + 1),
+        };
+        sqlx::query("UPDATE translations SET resource_key = ? WHERE id = ?")
+            .bind(key)
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The position after the last row of `project_id`.
+async fn next_sequence(tx: &mut Transaction<'_, Sqlite>, project_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(sequence), 0) + 1 FROM translations WHERE project_id = ?")
+        .bind(project_id)
+        .fetch_one(&mut **tx)
+        .await
+}
+
+/// Gives the rows `ids` the positions they already hold between them, in
+/// the given order.
+async fn reorder(tx: &mut Transaction<'_, Sqlite>, ids: &[String]) -> Result<(), sqlx::Error> {
+    let mut current = Vec::with_capacity(ids.len());
+    for id in ids {
+        let sequence: i64 = sqlx::query_scalar("SELECT sequence FROM translations WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await?;
+        current.push(sequence);
+    }
+    let mut positions = current.clone();
+    positions.sort_unstable();
+
+    for ((id, old), new) in ids.iter().zip(current).zip(positions) {
+        if old != new {
+            sqlx::query("UPDATE translations SET sequence = ? WHERE id = ?")
+                .bind(new)
+                .bind(id)
+                .execute(&mut **tx)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn translation_from_row(row: &SqliteRow) -> Result<Translation, sqlx::Error> {
+    let source_text: String = row.try_get("source_text")?;
+    let target_text: Option<String> = row.try_get("target_text")?;
+    let placeholders = row
+        .try_get::<Option<Json<Vec<Placeholder>>>, _>("placeholders")?
+        .map(|placeholders| placeholders.0)
+        .unwrap_or_default();
+
+    Ok(Translation {
+        id: row.try_get("id")?,
+        project_id: row.try_get("project_id")?,
+        sequence: row.try_get("sequence")?,
+        resource_key: row.try_get("resource_key")?,
+        file_path: row.try_get("file_path")?,
+        section: row.try_get("section")?,
+        context: row.try_get("context")?,
+        developer_comment: row.try_get("developer_comment")?,
+        max_length: row.try_get("max_length")?,
+        screenshot: row.try_get("screenshot")?,
+        source_content: inline::content(&source_text, &placeholders),
+        target_content: target_text.as_deref().map(|text| inline::content(text, &placeholders)),
+        source_text,
+        target_text,
+        notes: row.try_get("notes")?,
+        previous_source: row.try_get("previous_source")?,
+        fuzzy_target: row.try_get("fuzzy_target")?,
+        obsolete: row.try_get("obsolete")?,
+        start_ms: row.try_get("start_ms")?,
+        end_ms: row.try_get("end_ms")?,
+        placeholders,
+        status: row.try_get("status")?,
+        workflow_status: row.try_get("workflow_status")?,
+        locked: row.try_get("locked")?,
+        lock_reason: row.try_get("lock_reason")?,
+        locked_by: row.try_get("locked_by")?,
+        locked_at: row.try_get("locked_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn source_file_from_row(row: &SqliteRow) -> Result<SourceFile, sqlx::Error> {
+    Ok(SourceFile {
+        id: row.try_get("id")?,
+        project_id: row.try_get("project_id")?,
+        path: row.try_get("path")?,
+        format: row.try_get("format")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn chat_message_from_row(row: &SqliteRow) -> Result<ChatMessage, sqlx::Error> {
+    Ok(ChatMessage {
+        id: row.try_get("id")?,
+        project_id: row.try_get("project_id")?,
+        role: row.try_get("role")?,
+        content: row.try_get("content")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::WorkflowTransition;
+
+    async fn database() -> Database {
+        Database::new("sqlite::memory:").await.unwrap()
+    }
+
+    async fn project(db: &Database) -> Project {
+        db.create_project("Test".to_string(), None, Some("en".to_string()), Some("de".to_string()))
+            .await
+            .unwrap()
+    }
+
+    fn status(id: &str, category: TranslationStatus) -> WorkflowStatus {
+        WorkflowStatus { id: id.to_string(), label: id.to_string(), category }
+    }
+
+    fn transition(from: &str, to: &str, roles: Vec<WorkflowRole>) -> WorkflowTransition {
+        WorkflowTransition { from: from.to_string(), to: to.to_string(), roles }
+    }
+
+    #[tokio::test]
+    async fn workflow_transitions_are_checked() {
+        let db = database().await;
+        let project = project(&db).await;
+        let workflow = Workflow {
+            initial_status: "New".to_string(),
+            statuses: vec![
+                status("New", TranslationStatus::Draft),
+                status("Review", TranslationStatus::Validated),
+                status("Done", TranslationStatus::Approved),
+            ],
+            transitions: vec![
+                transition("New", "Review", Vec::new()),
+                transition("Review", "Done", vec![WorkflowRole::Reviewer]),
+            ],
+        };
+        db.set_project_workflow(&project.id, workflow).await.unwrap();
+
+        let row = db.create_translation(project.id.clone(), "Hello".to_string()).await.unwrap();
+        assert_eq!(row.workflow_status, "New");
+
+        let skip = db
+            .update_translation(&row.id, None, None, None, Some("Done".to_string()), Some(WorkflowRole::ProjectManager))
+            .await;
+        assert!(matches!(skip, Err(DbError::Workflow(WorkflowError::TransitionNotAllowed { .. }))));
+
+        db.update_translation(&row.id, None, None, None, Some("Review".to_string()), None).await.unwrap();
+        let wrong_role = db
+            .update_translation(&row.id, None, None, Some(TranslationStatus::Approved), None, Some(WorkflowRole::Translator))
+            .await;
+        assert!(matches!(wrong_role, Err(DbError::Workflow(WorkflowError::RoleNotAllowed { .. }))));
+
+        db.update_translation(&row.id, None, None, Some(TranslationStatus::Approved), None, Some(WorkflowRole::Reviewer))
+            .await
+            .unwrap();
+        let row = db.get_translation(&row.id).await.unwrap().unwrap();
+        assert_eq!((row.status, row.workflow_status.as_str()), (TranslationStatus::Approved, "Done"));
+    }
+
+    fn unit(key: &str, source: &str) -> ResourceUnit {
+        ResourceUnit { key: key.to_string(), source_text: source.to_string(), ..Default::default() }
+    }
+
+    fn select(ids: &[&str]) -> TranslationSelector {
+        TranslationSelector { ids: Some(ids.iter().map(|id| id.to_string()).collect()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn locked_rows_are_left_alone() {
+        let db = database().await;
+        let project = project(&db).await;
+        let units = vec![unit("hello", "Hello"), unit("bye", "Bye"), unit("open", "Open")];
+        let file = db
+            .import_resource(&project.id, "en.json", ResourceFormat::I18nextJson, b"{}", units)
+            .await
+            .unwrap()
+            .file;
+        let rows = db.get_translations(&project.id).await.unwrap();
+        let ids: Vec<&str> = rows.iter().map(|t| t.id.as_str()).collect();
+        let locked = db
+            .lock_translations(&project.id, &select(&ids[..2]), Some("Signed off".to_string()), "pm".to_string())
+            .await
+            .unwrap();
+        assert_eq!((locked.affected, locked.skipped), (2, 0));
+
+        let update = db.update_translation(ids[0], Some("Hallo".to_string()), None, None, None, None).await;
+        assert!(matches!(update, Err(DbError::Locked { reason: Some(ref reason), .. }) if reason == "Signed off"));
+
+        // The file changes the first locked string, drops the second and
+        // drops the unlocked one.
+        let summary = db
+            .reimport_resource(&file.id, b"{}", vec![unit("hello", "Hello there")])
+            .await
+            .unwrap();
+        assert_eq!(summary.locked, ["hello", "bye"]);
+        assert_eq!(summary.obsoleted, ["open"]);
+        assert!(summary.changed.is_empty());
+
+        let hello = db.get_translation(ids[0]).await.unwrap().unwrap();
+        assert_eq!((hello.source_text.as_str(), hello.obsolete), ("Hello", false));
+        assert!(!db.get_translation(ids[1]).await.unwrap().unwrap().obsolete);
+        assert!(db.get_translation(ids[2]).await.unwrap().unwrap().obsolete);
+    }
+
+    #[tokio::test]
+    async fn repair_skips_locked_rows() {
+        let db = database().await;
+        let project = project(&db).await;
+        let first = db.create_translation(project.id.clone(), "One".to_string()).await.unwrap();
+        let second = db.create_translation(project.id.clone(), "Two".to_string()).await.unwrap();
+        sqlx::query("UPDATE translations SET status = 'Finished'").execute(&db.pool).await.unwrap();
+        db.lock_translations(&project.id, &select(&[&first.id]), None, "pm".to_string()).await.unwrap();
+
+        let report = db.repair_invalid_enum_values(false).await.unwrap();
+        assert_eq!(report.rows.iter().map(|row| row.id.as_str()).collect::<Vec<_>>(), [second.id.as_str()]);
+        assert_eq!(report.locked.iter().map(|row| row.id.as_str()).collect::<Vec<_>>(), [first.id.as_str()]);
+        let status: String = sqlx::query_scalar("SELECT status FROM translations WHERE id = ?")
+            .bind(&first.id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "Finished");
+    }
+
+    #[tokio::test]
+    async fn obsolete_rows_are_not_matched_in_analysis() {
+        let db = database().await;
+        let memory = project(&db).await;
+        let row = db.create_translation(memory.id.clone(), "Hello world".to_string()).await.unwrap();
+        db.update_translation(&row.id, Some("Hallo Welt".to_string()), None, Some(TranslationStatus::Validated), None, None)
+            .await
+            .unwrap();
+        let project = project(&db).await;
+        db.create_translation(project.id.clone(), "Hello world".to_string()).await.unwrap();
+        let analyzed = [project.id];
+
+        let exact = |report: AnalysisReport| {
+            let band = report.projects[0].by_band.iter().find(|b| b.band == analysis::MatchBand::Exact);
+            band.map_or(0, |b| b.counts.segments)
+        };
+        assert_eq!(exact(db.analyze_projects(&analyzed).await.unwrap()), 1);
+
+        sqlx::query("UPDATE translations SET obsolete = 1 WHERE id = ?").bind(&row.id).execute(&db.pool).await.unwrap();
+        assert_eq!(exact(db.analyze_projects(&analyzed).await.unwrap()), 0);
+    }
+
+    #[tokio::test]
+    async fn repetitions_follow_source_and_context() {
+        let db = database().await;
+        let project = project(&db).await;
+        let with_context = |key: &str, context: &str| ResourceUnit { context: Some(context.to_string()), ..unit(key, "Open") };
+        let units = vec![with_context("menu.open", "menu"), with_context("file.open", "menu"), with_context("door", "door")];
+        db.import_translations(&project.id, units).await.unwrap();
+        let rows = db.get_translations(&project.id).await.unwrap();
+
+        let groups = db.get_repetition_groups(&project.id).await.unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].context.as_deref(), Some("menu"));
+        assert_eq!(groups[0].translation_ids, [rows[0].id.clone(), rows[1].id.clone()]);
+
+        db.update_translation(&rows[0].id, Some("Öffnen".to_string()), None, None, None, None).await.unwrap();
+        let draft = db.propagate_translation(&rows[0].id, &PropagationOptions::default(), None).await;
+        assert!(matches!(draft, Err(DbError::Invalid(_))));
+
+        db.update_translation(&rows[0].id, None, None, Some(TranslationStatus::Validated), None, None).await.unwrap();
+        let result = db.propagate_translation(&rows[0].id, &PropagationOptions::default(), None).await.unwrap();
+        assert_eq!((result.updated, result.skipped_context), (1, 1));
+        assert_eq!(result.updated_ids, [rows[1].id.clone()]);
+        let door = db.get_translation(&rows[2].id).await.unwrap().unwrap();
+        assert_eq!(door.target_text, None);
+    }
+
+    #[tokio::test]
+    async fn reimport_updates_changed_and_missing_keys() {
+        let db = database().await;
+        let project = project(&db).await;
+        let units = vec![unit("hello", "Hello"), unit("bye", "Bye"), unit("old", "Old")];
+        let file = db
+            .import_resource(&project.id, "en.json", ResourceFormat::I18nextJson, b"{}", units)
+            .await
+            .unwrap()
+            .file;
+        let rows = db.get_translations(&project.id).await.unwrap();
+        for (row, target) in rows.iter().zip(["Hallo", "Tschüss"]) {
+            db.update_translation(&row.id, Some(target.to_string()), None, Some(TranslationStatus::Validated), None, None)
+                .await
+                .unwrap();
+        }
+
+        let units = vec![unit("hello", "Hello"), unit("bye", "Goodbye"), unit("new", "New")];
+        let summary = db.reimport_resource(&file.id, b"{\"hello\": \"Hello\"}", units).await.unwrap();
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(summary.changed, ["bye"]);
+        assert_eq!(summary.added, ["new"]);
+        assert_eq!(summary.obsoleted, ["old"]);
+        assert!(summary.locked.is_empty());
+
+        let bye = db.get_translation(&rows[1].id).await.unwrap().unwrap();
+        assert_eq!((bye.source_text.as_str(), bye.target_text.as_deref()), ("Goodbye", None));
+        assert_eq!((bye.previous_source.as_deref(), bye.fuzzy_target.as_deref()), (Some("Bye"), Some("Tschüss")));
+        assert_eq!(bye.status, TranslationStatus::Draft);
+        assert!(db.get_translation(&rows[2].id).await.unwrap().unwrap().obsolete);
+        let keys: Vec<_> = db
+            .get_translations(&project.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.resource_key.unwrap(), t.target_text))
+            .collect();
+        assert_eq!(
+            keys,
+            [("hello".to_string(), Some("Hallo".to_string())), ("bye".to_string(), None), ("new".to_string(), None)]
+        );
+        let (_, content) = db.get_source_file(&file.id).await.unwrap().unwrap();
+        assert_eq!(content, b"{\"hello\": \"Hello\"}");
+    }
+
+    #[tokio::test]
+    async fn split_and_merge_segments() {
+        let db = database().await;
+        let project = project(&db).await;
+        let units = vec![unit("1", "Hello there. How are you?"), unit("2", "Bye.")];
+        db.import_resource(&project.id, "en.md", ResourceFormat::Markdown, b"", units).await.unwrap();
+        let rows = db.get_translations(&project.id).await.unwrap();
+        db.update_translation(&rows[0].id, Some("Hallo. Wie geht's?".to_string()), None, None, None, None)
+            .await
+            .unwrap();
+
+        let parts = db.split_translation(&rows[0].id, &[13]).await.unwrap();
+        assert_eq!(parts[0].id, rows[0].id);
+        let segments = |rows: Vec<Translation>| {
+            rows.into_iter()
+                .map(|t| (t.resource_key.unwrap(), t.source_text, t.sequence))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            segments(db.get_translations(&project.id).await.unwrap()),
+            [
+                ("1#1".to_string(), "Hello there.".to_string(), 1),
+                ("1#2".to_string(), "How are you?".to_string(), 2),
+                ("2".to_string(), "Bye.".to_string(), 3)
+            ]
+        );
+        assert_eq!(parts[0].target_text, None);
+        assert_eq!(parts[0].fuzzy_target.as_deref(), Some("Hallo. Wie geht's?"));
+
+        let ids: Vec<String> = parts.iter().map(|t| t.id.clone()).collect();
+        let merged = db.merge_translations(&ids).await.unwrap();
+        assert_eq!(merged.resource_key.as_deref(), Some("1"));
+        assert_eq!(merged.source_text, "Hello there. How are you?");
+        assert_eq!(db.get_translations(&project.id).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn move_and_reorder_segments() {
+        let db = database().await;
+        let project = project(&db).await;
+        let mut ids = Vec::new();
+        for source in ["A", "B", "C", "D"] {
+            ids.push(db.create_translation(project.id.clone(), source.to_string()).await.unwrap().id);
+        }
+        let order = |rows: Vec<Translation>| rows.into_iter().map(|t| t.source_text).collect::<Vec<_>>().concat();
+
+        db.move_translations(&project.id, &[ids[3].clone(), ids[1].clone()], Some(&ids[0])).await.unwrap();
+        assert_eq!(order(db.get_translations(&project.id).await.unwrap()), "ABDC");
+        db.move_translations(&project.id, &[ids[2].clone()], None).await.unwrap();
+        assert_eq!(order(db.get_translations(&project.id).await.unwrap()), "CABD");
+        let itself = db.move_translations(&project.id, &[ids[0].clone()], Some(&ids[0])).await;
+        assert!(matches!(itself, Err(DbError::Invalid(_))));
+
+        // A and D swap places; C and B stay where they are.
+        db.reorder_translations(&project.id, &[ids[3].clone(), ids[0].clone()]).await.unwrap();
+        assert_eq!(order(db.get_translations(&project.id).await.unwrap()), "CDBA");
+        let repeated = db.reorder_translations(&project.id, &[ids[0].clone(), ids[0].clone()]).await;
+        assert!(matches!(repeated, Err(DbError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn pages_follow_the_cursor() {
+        let db = database().await;
+        let project = project(&db).await;
+        for source in ["b", "a", "b", "c", "a"] {
+            db.create_translation(project.id.clone(), source.to_string()).await.unwrap();
+        }
+
+        for descending in [false, true] {
+            let mut query = TranslationQuery {
+                sort: TranslationSort::Source,
+                descending,
+                limit: Some(2),
+                ..Default::default()
+            };
+            let mut sources = Vec::new();
+            let mut ids = HashSet::new();
+            loop {
+                let page = db.query_translations(&project.id, &query).await.unwrap();
+                assert_eq!(page.total, 5);
+                for translation in page.items {
+                    assert!(ids.insert(translation.id));
+                    sources.push(translation.source_text);
+                }
+                match page.next_cursor {
+                    Some(cursor) => query.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            let expected = if descending { "cbbaa" } else { "aabbc" };
+            assert_eq!(sources.concat(), expected);
+        }
+
+        let query = TranslationQuery {
+            cursor: Some("nonsense".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(db.query_translations(&project.id, &query).await, Err(DbError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn bulk_create_reports_duplicate_keys() {
+        let db = database().await;
+        let project = project(&db).await;
+        let item = |source: &str, key: &str, context: Option<&str>| NewTranslation {
+            source_text: source.to_string(),
+            target_text: None,
+            notes: None,
+            resource_key: Some(key.to_string()),
+            context: context.map(str::to_string),
+        };
+        db.create_translations_bulk(&project.id, vec![item("Save", "save", None)]).await.unwrap();
+
+        let items = vec![
+            item("Open", "open", None),
+            item("Save again", "save", None),
+            item("Open file", "open", Some("menu")),
+            item("Open too", "open", None),
+            item("No key", "", None),
+            item("No key either", "", None),
+        ];
+        let result = db.create_translations_bulk(&project.id, items).await.unwrap();
+        assert_eq!((result.succeeded, result.failed), (4, 2));
+        let errors: Vec<Option<&str>> = result.items.iter().map(|item| item.error.as_deref()).collect();
+        assert_eq!(
+            errors,
+            [None, Some("Key save already exists"), None, Some("Key open already exists"), None, None]
+        );
+        assert!(result.items[1].id.is_none());
 
-        Ok(messages)
+        let rows: Vec<_> = db
+            .get_translations(&project.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.source_text, t.resource_key, t.sequence))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("Save".to_string(), Some("save".to_string()), 1),
+                ("Open".to_string(), Some("open".to_string()), 2),
+                ("Open file".to_string(), Some("open".to_string()), 3),
+                ("No key".to_string(), None, 4),
+                ("No key either".to_string(), None, 5)
+            ]
+        );
     }
 }
@@ -0,0 +1,613 @@
+//! Android `res/values/strings.xml`. Strings are keyed by resource name,
+//! array items as `name[index]` and plural items as `name[quantity]`.
+//! Plurals get an item for each CLDR category of the target language, even
+//! the ones an English template has no item for; those start from the
+//! template's `other` text. Inline markup such as `<xliff:g>` or `<b>` is
+//! kept verbatim in the text so it survives the round trip.
+
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::path::{Path, PathBuf};
+
+use super::{FormatError, ImportOptions, ResourceUnit, TargetLookup};
+use crate::icu;
+
+enum Container {
+    Array { name: String, index: usize },
+    /// `items` holds the quantity and raw text of each item read so far.
+    Plurals { name: String, items: Vec<(String, String)> },
+}
+
+pub fn parse(content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+    let content = decode(content)?;
+    let mut reader = Reader::from_str(content);
+    let mut units = Vec::new();
+    let mut container: Option<Container> = None;
+    let mut comment: Option<String> = None;
+    let categories = plural_categories(options.target_locale.as_deref());
+
+    loop {
+        match reader.read_event().map_err(parse_error)? {
+            Event::Comment(text) => {
+                let text = text.decode().map_err(parse_error)?;
+                comment = Some(text.trim().to_string());
+            }
+            Event::Start(e) => {
+                let end = e.to_end().into_owned();
+                match e.name().as_ref() {
+                    b"string" => {
+                        let name = attribute(&e, "name")?.unwrap_or_default();
+                        let raw = reader.read_text(end.name()).map_err(parse_error)?;
+                        if is_translatable(&e)? {
+                            units.push(unit(name, &raw, comment.take()));
+                        }
+                        comment = None;
+                    }
+                    b"string-array" | b"plurals" if !is_translatable(&e)? => {
+                        reader.read_to_end(end.name()).map_err(parse_error)?;
+                        comment = None;
+                    }
+                    b"string-array" => {
+                        let name = attribute(&e, "name")?.unwrap_or_default();
+                        container = Some(Container::Array { name, index: 0 });
+                    }
+                    b"plurals" => {
+                        let name = attribute(&e, "name")?.unwrap_or_default();
+                        container = Some(Container::Plurals { name, items: Vec::new() });
+                    }
+                    b"item" if container.is_some() => {
+                        let quantity = attribute(&e, "quantity")?;
+                        let raw = reader.read_text(end.name()).map_err(parse_error)?;
+                        if let Some(key) = item_key(&mut container, quantity, &raw) {
+                            units.push(unit(key, &raw, comment.clone()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if matches!(e.name().as_ref(), b"string-array" | b"plurals") {
+                    if let (Some(Container::Plurals { name, items }), Some(categories)) = (container.take(), &categories) {
+                        for category in categories {
+                            if items.iter().any(|(quantity, _)| quantity == category) {
+                                continue;
+                            }
+                            if let Some(raw) = other_item(&items) {
+                                units.push(unit(format!("{}[{}]", name, category), raw, comment.clone()));
+                            }
+                        }
+                    }
+                    container = None;
+                    comment = None;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(units)
+}
+
+fn unit(key: String, raw: &str, comment: Option<String>) -> ResourceUnit {
+    let source_text = inner_to_text(raw);
+    let placeholders = placeholders(raw);
+
+    ResourceUnit {
+        key,
+        source_text,
+        developer_comment: comment.filter(|c| !c.is_empty()),
+        notes: Some(format!("Placeholders: {}", placeholders.join(", "))).filter(|_| !placeholders.is_empty()),
+        ..Default::default()
+    }
+}
+
+/// The key for the next `<item>` of the current array or plurals element,
+/// recording plural items as they are read.
+fn item_key(container: &mut Option<Container>, quantity: Option<String>, raw: &str) -> Option<String> {
+    match container {
+        Some(Container::Array { name, index }) => {
+            let key = format!("{}[{}]", name, index);
+            *index += 1;
+            Some(key)
+        }
+        Some(Container::Plurals { name, items }) => {
+            let quantity = quantity.unwrap_or_default();
+            let key = format!("{}[{}]", name, quantity);
+            items.push((quantity, raw.to_string()));
+            Some(key)
+        }
+        None => None,
+    }
+}
+
+/// The text a plural category the template lacks starts from.
+fn other_item(items: &[(String, String)]) -> Option<&str> {
+    items
+        .iter()
+        .find(|(quantity, _)| quantity == "other")
+        .or(items.last())
+        .map(|(_, raw)| raw.as_str())
+}
+
+/// The CLDR cardinal categories of `locale`, when its rules are known.
+fn plural_categories(locale: Option<&str>) -> Option<Vec<String>> {
+    locale.and_then(icu::plural_categories).map(|categories| categories.cardinal)
+}
+
+/// Writes a localized `strings.xml` from the source template. Only
+/// translatable strings, arrays and plurals are kept; other resource types
+/// (dimensions, colors, ids) belong in the default `values` folder only.
+/// Plurals are written with the target language's categories when its
+/// rules are known, and with the template's otherwise.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let template = decode(template).map_err(|e| FormatError::Write(e.to_string()))?;
+    let mut reader = Reader::from_str(template);
+    let mut writer = Writer::new(Vec::new());
+    let mut container: Option<Container> = None;
+    let mut depth = 0;
+    // Whitespace before an element is held back so it can be dropped along
+    // with the element.
+    let mut pending: Option<Event> = None;
+    // The whitespace before the first item of the current plurals, repeated
+    // before each item written.
+    let mut item_indent: Option<Event> = None;
+    let categories = plural_categories(targets.target_locale());
+
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Text(ref text) if text.iter().all(u8::is_ascii_whitespace) => {
+                pending = Some(event.into_owned());
+                continue;
+            }
+            Event::Start(ref e) => {
+                let end = e.to_end().into_owned();
+                let keep = match e.name().as_ref() {
+                    _ if depth == 0 => true,
+                    b"string" => {
+                        let name = attribute(e, "name").map_err(to_write)?.unwrap_or_default();
+                        let raw = reader.read_text(end.name()).map_err(write_error)?;
+                        if is_translatable(e).map_err(to_write)? {
+                            write_string(&mut writer, &mut pending, event.clone(), &name, &raw, targets)?;
+                        }
+                        pending = None;
+                        continue;
+                    }
+                    b"item" if container.is_some() => {
+                        let quantity = attribute(e, "quantity").map_err(to_write)?;
+                        let raw = reader.read_text(end.name()).map_err(write_error)?;
+                        let plural = matches!(container, Some(Container::Plurals { .. }));
+                        if let Some(key) = item_key(&mut container, quantity, &raw) {
+                            // Plural items are written when the plurals ends.
+                            if plural {
+                                item_indent = item_indent.or_else(|| pending.take());
+                            } else {
+                                write_string(&mut writer, &mut pending, event.clone(), &key, &raw, targets)?;
+                            }
+                        }
+                        pending = None;
+                        continue;
+                    }
+                    b"string-array" | b"plurals" if is_translatable(e).map_err(to_write)? => {
+                        let name = attribute(e, "name").map_err(to_write)?.unwrap_or_default();
+                        container = Some(if e.name().as_ref() == b"plurals" {
+                            item_indent = None;
+                            Container::Plurals { name, items: Vec::new() }
+                        } else {
+                            Container::Array { name, index: 0 }
+                        });
+                        true
+                    }
+                    _ => false,
+                };
+
+                if !keep {
+                    reader.read_to_end(end.name()).map_err(write_error)?;
+                    pending = None;
+                    continue;
+                }
+                depth += 1;
+            }
+            Event::Empty(ref e)
+                if depth > 0 && (e.name().as_ref() != b"string" || !is_translatable(e).map_err(to_write)?) =>
+            {
+                pending = None;
+                continue;
+            }
+            Event::End(ref e) => {
+                depth -= 1;
+                if let Some(Container::Plurals { name, items }) = &container {
+                    write_plural_items(&mut writer, &item_indent, name, items, categories.as_deref(), targets)?;
+                }
+                if matches!(e.name().as_ref(), b"string-array" | b"plurals") {
+                    container = None;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        if let Some(whitespace) = pending.take() {
+            writer.write_event(whitespace).map_err(write_error)?;
+        }
+        writer.write_event(event).map_err(write_error)?;
+    }
+
+    if let Some(whitespace) = pending.take() {
+        writer.write_event(whitespace).map_err(write_error)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// Writes the items of a plurals: one per category of the target language,
+/// or the template's own when its rules aren't known.
+fn write_plural_items(
+    writer: &mut Writer<Vec<u8>>,
+    indent: &Option<Event>,
+    name: &str,
+    items: &[(String, String)],
+    categories: Option<&[String]>,
+    targets: &TargetLookup,
+) -> Result<(), FormatError> {
+    let quantities: Vec<&str> = match categories {
+        Some(categories) => categories.iter().map(String::as_str).collect(),
+        None => items.iter().map(|(quantity, _)| quantity.as_str()).collect(),
+    };
+    for quantity in quantities {
+        let raw = match items.iter().find(|(item, _)| item == quantity) {
+            Some((_, raw)) => raw.as_str(),
+            None => match other_item(items) {
+                Some(raw) => raw,
+                None => continue,
+            },
+        };
+        let start = BytesStart::new("item").with_attributes([("quantity", quantity)]);
+        let key = format!("{}[{}]", name, quantity);
+        write_string(writer, &mut indent.clone(), Event::Start(start), &key, raw, targets)?;
+    }
+    Ok(())
+}
+
+fn write_string(
+    writer: &mut Writer<Vec<u8>>,
+    pending: &mut Option<Event>,
+    start: Event,
+    key: &str,
+    raw: &str,
+    targets: &TargetLookup,
+) -> Result<(), FormatError> {
+    let source = inner_to_text(raw);
+    let text = match targets.resolve(key, &source) {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let cdata = raw.trim_start().starts_with("<![CDATA[");
+
+    let end = match &start {
+        Event::Start(e) => e.to_end().into_owned(),
+        _ => return Err(FormatError::Write("expected a start tag".to_string())),
+    };
+
+    if let Some(whitespace) = pending.take() {
+        writer.write_event(whitespace).map_err(write_error)?;
+    }
+    writer.write_event(start).map_err(write_error)?;
+    writer
+        .write_event(Event::Text(BytesText::from_escaped(text_to_inner(text, cdata, &tag_names(raw)))))
+        .map_err(write_error)?;
+    writer.write_event(Event::End(end)).map_err(write_error)?;
+    Ok(())
+}
+
+/// `res/values/strings.xml` → `res/values-<qualifier>/strings.xml`.
+pub fn export_path(source_path: &str, target_locale: &str) -> PathBuf {
+    let source = Path::new(source_path);
+    let file_name = source.file_name().map(|n| n.to_os_string()).unwrap_or_else(|| "strings.xml".into());
+    let res = source.parent().and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    res.join(format!("values-{}", locale_qualifier(target_locale))).join(file_name)
+}
+
+/// Android resource qualifier for a BCP 47 locale: `pt-BR` → `pt-rBR`,
+/// anything with a script or variant → `b+zh+Hans`.
+pub fn locale_qualifier(locale: &str) -> String {
+    let parts: Vec<&str> = locale.split(['-', '_']).filter(|p| !p.is_empty()).collect();
+    match parts.as_slice() {
+        [language] => language.to_lowercase(),
+        [language, region] if region.len() == 2 || (region.len() == 3 && region.chars().all(|c| c.is_ascii_digit())) => {
+            format!("{}-r{}", language.to_lowercase(), region.to_uppercase())
+        }
+        _ => format!("b+{}", parts.join("+")),
+    }
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+fn attribute(e: &quick_xml::events::BytesStart, name: &str) -> Result<Option<String>, FormatError> {
+    match e.try_get_attribute(name).map_err(parse_error)? {
+        Some(attr) => Ok(Some(attr.unescape_value().map_err(parse_error)?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn is_translatable(e: &quick_xml::events::BytesStart) -> Result<bool, FormatError> {
+    Ok(attribute(e, "translatable")?.as_deref() != Some("false"))
+}
+
+/// Turns the raw inner XML of a string into the text shown to translators:
+/// entities and Android escapes are resolved, CDATA is unwrapped and inline
+/// tags are kept as written.
+fn inner_to_text(raw: &str) -> String {
+    let mut text = String::new();
+    let mut rest = raw;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            text.push_str(&android_unescape(&after[..end]));
+            rest = after.get(end + 3..).unwrap_or("");
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").map(|i| i + 3).unwrap_or(after.len());
+            rest = &after[end..];
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').map(|i| i + 1).unwrap_or(rest.len());
+            text.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let chunk = quick_xml::escape::unescape(&rest[..end])
+                .map(|c| c.into_owned())
+                .unwrap_or_else(|_| rest[..end].to_string());
+            text.push_str(&android_unescape(&chunk));
+            rest = &rest[end..];
+        }
+    }
+
+    text
+}
+
+/// The inverse of `inner_to_text`. Only tags named in `tags` (those used in
+/// the source string) are written as markup; anything else that looks like a
+/// tag is escaped as text.
+fn text_to_inner(text: &str, cdata: bool, tags: &[String]) -> String {
+    let quoted = text.starts_with(' ') || text.ends_with(' ') || text.contains("  ");
+
+    let mut inner = String::new();
+    if cdata {
+        inner.push_str("<![CDATA[");
+        inner.push_str(&android_escape(text, true).replace("]]>", "]]]]><![CDATA[>"));
+        inner.push_str("]]>");
+    } else {
+        let mut rest = text;
+        let mut first = true;
+        while !rest.is_empty() {
+            match inline_tag_len(rest, tags) {
+                Some(len) => {
+                    inner.push_str(&rest[..len]);
+                    rest = &rest[len..];
+                }
+                None => {
+                    let end = rest
+                        .char_indices()
+                        .skip(1)
+                        .find(|(_, c)| *c == '<')
+                        .map(|(i, _)| i)
+                        .unwrap_or(rest.len());
+                    let chunk = android_escape(&rest[..end], first);
+                    inner.push_str(&quick_xml::escape::partial_escape(chunk.as_str()));
+                    rest = &rest[end..];
+                }
+            }
+            first = false;
+        }
+    }
+
+    if quoted {
+        format!("\"{}\"", inner)
+    } else {
+        inner
+    }
+}
+
+/// Length of the inline tag (`<b>`, `</xliff:g>`, `<xliff:g id="x">`) at the
+/// start of `text`, if it is one of `tags`.
+fn inline_tag_len(text: &str, tags: &[String]) -> Option<usize> {
+    let after = text.strip_prefix('<')?;
+    let name = after.strip_prefix('/').unwrap_or(after);
+    let name_len = name.find([' ', '\t', '\n', '/', '>']).unwrap_or(name.len());
+    if !tags.iter().any(|tag| tag == &name[..name_len]) {
+        return None;
+    }
+    let end = after.find(['>', '<'])?;
+    if after.as_bytes()[end] == b'>' {
+        Some(end + 2)
+    } else {
+        None
+    }
+}
+
+/// Names of the elements used inside a string's raw XML.
+fn tag_names(raw: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = raw;
+
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        if after.starts_with("![CDATA[") || after.starts_with("!--") {
+            let close = if after.starts_with("![CDATA[") { "]]>" } else { "-->" };
+            rest = after.find(close).map(|i| &after[i..]).unwrap_or("");
+            continue;
+        }
+        let name = after.strip_prefix('/').unwrap_or(after);
+        let len = name.find([' ', '\t', '\n', '/', '>']).unwrap_or(name.len());
+        if len > 0 && !names.iter().any(|n| n == &name[..len]) {
+            names.push(name[..len].to_string());
+        }
+        rest = after;
+    }
+
+    names
+}
+
+fn android_unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(decoded) => out.push(decoded),
+                        None => {
+                            out.push_str("\\u");
+                            out.push_str(&hex);
+                        }
+                    }
+                }
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            // Unescaped double quotes only delimit whitespace-preserving runs.
+            '"' => {}
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn android_escape(text: &str, at_start: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    if at_start && (text.starts_with('@') || text.starts_with('?')) {
+        out.push('\\');
+    }
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Describes `<xliff:g>` placeholders, e.g. `%1$s (id=name, example=Bob)`.
+fn placeholders(raw: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = raw;
+
+    while let Some(start) = rest.find("<xliff:g") {
+        let after = &rest[start..];
+        let Some(open_end) = after.find('>') else { break };
+        let tag = &after[..open_end];
+        let body = &after[open_end + 1..];
+        let close = body.find("</xliff:g>").unwrap_or(body.len());
+
+        let mut details = Vec::new();
+        for name in ["id", "example"] {
+            if let Some(value) = tag_attribute(tag, name) {
+                details.push(format!("{}={}", name, value));
+            }
+        }
+        let content = inner_to_text(&body[..close]);
+        if details.is_empty() {
+            found.push(content);
+        } else {
+            found.push(format!("{} ({})", content, details.join(", ")));
+        }
+
+        rest = &body[close..];
+    }
+
+    found
+}
+
+fn tag_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+fn parse_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Parse(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Write(e.to_string())
+}
+
+fn to_write(e: FormatError) -> FormatError {
+    match e {
+        FormatError::Parse(reason) => FormatError::Write(reason),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::AndroidStrings;
+
+    const TEMPLATE: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <!-- Greeting -->
+    <string name="hello">Hello %1$s</string>
+    <string name="quote">Don\'t \"stop\"</string>
+    <string name="version" translatable="false">1.0</string>
+    <plurals name="songs">
+        <item quantity="one">%d song</item>
+        <item quantity="other">%d songs</item>
+    </plurals>
+</resources>
+"#;
+
+    #[test]
+    fn strings_and_plurals() {
+        let units = parse(AndroidStrings, TEMPLATE, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["hello", "quote", "songs[one]", "songs[other]"]);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Greeting"));
+        round_trip(AndroidStrings, TEMPLATE);
+    }
+
+    #[test]
+    fn target_plural_categories() {
+        let units = parse(AndroidStrings, TEMPLATE, "pl");
+        let plurals: Vec<(&str, &str)> = units[2..].iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(
+            plurals,
+            [("songs[one]", "%d song"), ("songs[other]", "%d songs"), ("songs[few]", "%d songs"), ("songs[many]", "%d songs")]
+        );
+
+        let targets = [
+            ("songs[one]", "%d piosenka"),
+            ("songs[few]", "%d piosenki"),
+            ("songs[many]", "%d piosenek"),
+            ("songs[other]", "%d piosenki"),
+        ];
+        let out = String::from_utf8(write(AndroidStrings, TEMPLATE, &targets, "pl", false)).unwrap();
+        assert!(out.contains(
+            "    <plurals name=\"songs\">
+        <item quantity=\"one\">%d piosenka</item>
+        <item quantity=\"few\">%d piosenki</item>
+        <item quantity=\"many\">%d piosenek</item>
+        <item quantity=\"other\">%d piosenki</item>
+    </plurals>"
+        ));
+
+        // Japanese only has `other`, so the template's `one` is dropped.
+        let out = String::from_utf8(write(AndroidStrings, TEMPLATE, &[("songs[other]", "%d 曲")], "ja", false)).unwrap();
+        assert!(out.contains("    <plurals name=\"songs\">\n        <item quantity=\"other\">%d 曲</item>\n    </plurals>"));
+    }
+}
@@ -0,0 +1,330 @@
+//! Legacy Apple `.strings` files: `"key" = "value";` pairs, in UTF-16 or
+//! UTF-8. The comment right before an entry becomes its developer
+//! comment. The writer
+//! only replaces the value literals, so comments and layout are preserved and
+//! the file is saved in its original encoding.
+
+use std::ops::Range;
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8 { bom: bool },
+    Utf16Le { bom: bool },
+    Utf16Be { bom: bool },
+}
+
+struct Entry {
+    key: String,
+    value: String,
+    comment: Option<String>,
+    /// The quoted value literal, quotes included.
+    value_span: Range<usize>,
+    /// The whole entry from its leading comment (if any) to the `;` and the
+    /// rest of that line.
+    span: Range<usize>,
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let (text, _) = decode(content).map_err(FormatError::Parse)?;
+    let entries = scan(&text).map_err(FormatError::Parse)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| ResourceUnit {
+            key: entry.key,
+            source_text: entry.value,
+            developer_comment: entry.comment,
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Writes the template with every value replaced by its translation. Entries
+/// without one are removed together with their comment when there is no
+/// fallback.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let (text, encoding) = decode(template).map_err(FormatError::Write)?;
+    let entries = scan(&text).map_err(FormatError::Write)?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for entry in &entries {
+        match targets.resolve(&entry.key, &entry.value) {
+            Some(value) => {
+                out.push_str(&text[copied..entry.value_span.start]);
+                out.push_str(&quote(value));
+                copied = entry.value_span.end;
+            }
+            None => {
+                out.push_str(&text[copied..entry.span.start]);
+                copied = entry.span.end;
+            }
+        }
+    }
+    out.push_str(&text[copied..]);
+
+    Ok(encode(&out, encoding))
+}
+
+fn decode(content: &[u8]) -> Result<(String, Encoding), String> {
+    let (encoding, body) = match content {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => (Encoding::Utf8 { bom: true }, rest),
+        [0xFF, 0xFE, rest @ ..] => (Encoding::Utf16Le { bom: true }, rest),
+        [0xFE, 0xFF, rest @ ..] => (Encoding::Utf16Be { bom: true }, rest),
+        // Without a byte order mark, ASCII text in UTF-16 still shows up as
+        // every other byte being zero.
+        [0, b, ..] if *b != 0 => (Encoding::Utf16Be { bom: false }, content),
+        [b, 0, ..] if *b != 0 => (Encoding::Utf16Le { bom: false }, content),
+        _ => (Encoding::Utf8 { bom: false }, content),
+    };
+
+    let text = match encoding {
+        Encoding::Utf8 { .. } => String::from_utf8(body.to_vec()).map_err(|e| e.to_string())?,
+        Encoding::Utf16Le { .. } | Encoding::Utf16Be { .. } => {
+            if body.len() % 2 != 0 {
+                return Err("truncated UTF-16 content".to_string());
+            }
+            let units: Vec<u16> = body
+                .chunks_exact(2)
+                .map(|pair| match encoding {
+                    Encoding::Utf16Le { .. } => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            String::from_utf16(&units).map_err(|e| e.to_string())?
+        }
+    };
+
+    Ok((text, encoding))
+}
+
+fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 { bom } => {
+            let mut out = if bom { vec![0xEF, 0xBB, 0xBF] } else { Vec::new() };
+            out.extend_from_slice(text.as_bytes());
+            out
+        }
+        Encoding::Utf16Le { bom } => {
+            let mut out = if bom { vec![0xFF, 0xFE] } else { Vec::new() };
+            out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            out
+        }
+        Encoding::Utf16Be { bom } => {
+            let mut out = if bom { vec![0xFE, 0xFF] } else { Vec::new() };
+            out.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            out
+        }
+    }
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn line_and_column(&self) -> String {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        format!("line {}, column {}", line, column)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at {}", message, self.line_and_column())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    /// Reads a `/* */` or `//` comment if one starts here.
+    fn comment(&mut self) -> Result<Option<String>, String> {
+        let rest = self.rest();
+        if let Some(body) = rest.strip_prefix("/*") {
+            let end = body.find("*/").ok_or_else(|| self.error("unterminated comment"))?;
+            self.pos += 2 + end + 2;
+            Ok(Some(body[..end].trim().to_string()))
+        } else if let Some(body) = rest.strip_prefix("//") {
+            let end = body.find('\n').unwrap_or(body.len());
+            self.pos += 2 + end;
+            Ok(Some(body[..end].trim().to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    /// Reads a quoted literal, or a bare word as older files allow for keys
+    /// and values.
+    fn literal(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            let word: String = self
+                .rest()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '$' | ':' | '/'))
+                .collect();
+            if word.is_empty() {
+                return Err(self.error("expected a quoted string"));
+            }
+            self.pos += word.len();
+            return Ok(word);
+        }
+
+        self.pos += 1;
+        let mut value = String::new();
+        // High half of a surrogate pair written as two `\U` escapes.
+        let mut high_surrogate: Option<u16> = None;
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            let is_unicode_escape = c == '\\' && matches!(chars.clone().next(), Some((_, 'U' | 'u')));
+            if !is_unicode_escape && high_surrogate.take().is_some() {
+                value.push(char::REPLACEMENT_CHARACTER);
+            }
+            match c {
+                '"' => {
+                    self.pos += offset + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('0') => value.push('\0'),
+                    Some('U') | Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let unit = u16::from_str_radix(&hex, 16).map_err(|_| self.error("invalid \\U escape"))?;
+                        match high_surrogate.take() {
+                            None if (0xD800..0xDC00).contains(&unit) => high_surrogate = Some(unit),
+                            Some(high) => {
+                                let pair = char::decode_utf16([high, unit]).next().and_then(Result::ok);
+                                value.push(pair.unwrap_or(char::REPLACEMENT_CHARACTER));
+                            }
+                            None => value.push(char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+                        }
+                    }
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+}
+
+fn scan(text: &str) -> Result<Vec<Entry>, String> {
+    let mut scanner = Scanner { text, pos: 0 };
+    let mut entries = Vec::new();
+    // The last comment seen, with where it starts and ends.
+    let mut comment: Option<(String, Range<usize>)> = None;
+
+    loop {
+        scanner.skip_whitespace();
+        if scanner.peek().is_none() {
+            break;
+        }
+
+        let start = scanner.pos;
+        if let Some(note) = scanner.comment()? {
+            comment = Some((note, start..scanner.pos));
+            continue;
+        }
+
+        let key = scanner.literal()?;
+        scanner.skip_whitespace();
+        scanner.expect('=')?;
+        scanner.skip_whitespace();
+        let value_start = scanner.pos;
+        let value = scanner.literal()?;
+        let value_end = scanner.pos;
+        scanner.skip_whitespace();
+        scanner.expect(';')?;
+
+        // Swallow the rest of the line so a removed entry leaves no gap.
+        let line_end = match scanner.rest().find('\n') {
+            Some(newline) if scanner.rest()[..newline].trim().is_empty() => scanner.pos + newline + 1,
+            _ => scanner.pos,
+        };
+        // A comment separated from the entry by a blank line is a file or
+        // section header rather than a note on this entry.
+        let (comment, entry_start) = match comment.take() {
+            Some((note, span)) if text[span.end..start].matches('\n').count() <= 1 => {
+                (Some(note).filter(|n| !n.is_empty()), span.start)
+            }
+            _ => (None, start),
+        };
+        let line_start = text[..entry_start].rfind('\n').map_or(0, |i| i + 1);
+        let entry_start = if text[line_start..entry_start].trim().is_empty() { line_start } else { entry_start };
+
+        entries.push(Entry {
+            key,
+            value,
+            comment,
+            value_span: value_start..value_end,
+            span: entry_start..line_end,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::AppleStrings;
+
+    const TEMPLATE: &[u8] = b"/* Greeting */\n\"hello\" = \"Hello \\\"%@\\\"\";\nbare = \"Bare\";\n";
+
+    #[test]
+    fn comments_and_escapes() {
+        let units = parse(AppleStrings, TEMPLATE, "de");
+        let entries: Vec<(&str, &str)> = units.iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(entries, [("hello", "Hello \"%@\""), ("bare", "Bare")]);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Greeting"));
+        round_trip(AppleStrings, TEMPLATE);
+
+        let out = write(AppleStrings, TEMPLATE, &[("bare", "Nackt \"x\"")], "de", true);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/* Greeting */\n\"hello\" = \"Hello \\\"%@\\\"\";\nbare = \"Nackt \\\"x\\\"\";\n"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Flutter Application Resource Bundles (`.arb`). Every string entry becomes
+//! one unit. Its `@key` metadata gives the developer comment (description), the
+//! context and notes describing the placeholders. Global `@@` attributes are
+//! copied, with `@@locale` set to the target locale on export.
+
+use serde_json::{Map, Value};
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let root: Value = serde_json::from_slice(content).map_err(|e| FormatError::Parse(e.to_string()))?;
+    let object = root
+        .as_object()
+        .ok_or_else(|| FormatError::Parse("expected a JSON object at the top level".to_string()))?;
+
+    Ok(object
+        .iter()
+        .filter(|(key, _)| !key.starts_with('@'))
+        .filter_map(|(key, value)| {
+            let text = value.as_str()?;
+            let metadata = object.get(&format!("@{}", key));
+            Some(ResourceUnit {
+                key: key.clone(),
+                source_text: text.to_string(),
+                context: metadata.and_then(|m| m.get("context")).and_then(Value::as_str).map(str::to_string),
+                developer_comment: metadata
+                    .and_then(|m| m.get("description"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                notes: metadata.and_then(describe),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+/// Notes describing the placeholders in the `@key` metadata of an entry.
+fn describe(metadata: &Value) -> Option<String> {
+    let placeholders = metadata.get("placeholders").and_then(Value::as_object)?;
+    let described: Vec<String> = placeholders
+        .iter()
+        .map(|(name, details)| {
+            let mut about = Vec::new();
+            if let Some(kind) = details.get("type").and_then(Value::as_str) {
+                about.push(kind.to_string());
+            }
+            if let Some(example) = details.get("example").and_then(Value::as_str) {
+                about.push(format!("e.g. {}", example));
+            }
+            if let Some(description) = details.get("description").and_then(Value::as_str) {
+                about.push(description.to_string());
+            }
+            if about.is_empty() {
+                format!("{{{}}}", name)
+            } else {
+                format!("{{{}}} ({})", name, about.join(", "))
+            }
+        })
+        .collect();
+
+    if described.is_empty() {
+        None
+    } else {
+        Some(format!("Placeholders: {}", described.join("; ")))
+    }
+}
+
+/// Writes the template with every string replaced by its translation, keeping
+/// entry order and metadata. Entries without one are removed along with their
+/// metadata when there is no fallback.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let root: Value = serde_json::from_slice(template).map_err(|e| FormatError::Write(e.to_string()))?;
+    let object = root
+        .as_object()
+        .ok_or_else(|| FormatError::Write("template is not a JSON object".to_string()))?;
+
+    let mut out = Map::new();
+    for (key, value) in object {
+        if key == "@@locale" {
+            if let Some(locale) = targets.target_locale() {
+                out.insert(key.clone(), Value::String(locale.replace('-', "_")));
+                continue;
+            }
+        }
+        let entry = match key.strip_prefix('@') {
+            // Global attributes are kept; metadata follows its entry.
+            Some(name) if !name.starts_with('@') => object
+                .get(name)
+                .and_then(Value::as_str)
+                .map_or(Some(value.clone()), |source| targets.resolve(name, source).map(|_| value.clone())),
+            Some(_) => Some(value.clone()),
+            None => match value.as_str() {
+                Some(source) => targets.resolve(key, source).map(|text| Value::String(text.to_string())),
+                None => Some(value.clone()),
+            },
+        };
+        if let Some(entry) = entry {
+            out.insert(key.clone(), entry);
+        }
+    }
+
+    let mut out = serde_json::to_vec_pretty(&Value::Object(out)).map_err(|e| FormatError::Write(e.to_string()))?;
+    out.push(b'\n');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Arb;
+
+    const TEMPLATE: &[u8] = br#"{
+  "@@locale": "en",
+  "hello": "Hello {name}",
+  "@hello": { "description": "Greeting", "context": "home", "placeholders": { "name": { "example": "Ann" } } },
+  "items": "{count, plural, one{# item} other{# items}}"
+}
+"#;
+
+    #[test]
+    fn metadata_and_locale() {
+        let units = parse(Arb, TEMPLATE, "de");
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Greeting"));
+        assert_eq!(units[0].context.as_deref(), Some("home"));
+        assert_eq!(units[0].notes.as_deref(), Some("Placeholders: {name} (e.g. Ann)"));
+        assert_eq!(units[1].source_text, "{count, plural, one{# item} other{# items}}");
+        round_trip(Arb, TEMPLATE);
+
+        let out = String::from_utf8(write(Arb, TEMPLATE, &[("hello", "Hallo {name}")], "de", false)).unwrap();
+        assert!(out.starts_with("{\n  \"@@locale\": \"de\",\n  \"hello\": \"Hallo {name}\",\n  \"@hello\": {"));
+        assert!(!out.contains("\"items\""));
+    }
+}
@@ -0,0 +1,502 @@
+//! Word `.docx` documents. Every paragraph with text, including those in
+//! table cells, headers, footers and notes, becomes one segment. Runs whose
+//! formatting differs from the paragraph's main formatting become paired
+//! tags, hyperlinks and tracked insertions wrap their runs the same way, and
+//! everything else inside a paragraph (fields, images, page breaks,
+//! bookmarks) is protected as a standalone tag. The rest of the package is
+//! copied unchanged, so styles, numbering and media survive the round trip.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+
+use quick_xml::escape::{partial_escape, unescape};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::placeholders::{self, Placeholder, Segment, SegmentBuilder, Token};
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+/// Paragraph-level elements whose runs stay translatable inside them.
+const WRAPPERS: &[&[u8]] = &[b"w:hyperlink", b"w:ins", b"w:smartTag"];
+
+enum Item {
+    Text { properties: String, text: String },
+    /// Markup copied as is, wrapped in a run of its own when it came from one.
+    Raw(String),
+    Open(String),
+    Close(String),
+}
+
+struct Paragraph {
+    segment: Segment,
+    /// Run properties of the untagged text.
+    base: String,
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).map_err(parse_error)?;
+
+    let mut segments = Vec::new();
+    for name in text_parts(&archive) {
+        let xml = read_part(&mut archive, &name)?;
+        segments.extend(scan(&xml)?.into_iter().map(|paragraph| paragraph.segment));
+    }
+
+    Ok(placeholders::units(segments))
+}
+
+/// Writes the template package with every translated paragraph rebuilt from
+/// its translation. Untranslated paragraphs and all other parts of the
+/// package are left untouched.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let mut archive = ZipArchive::new(Cursor::new(template)).map_err(write_error)?;
+
+    let mut rewritten = HashMap::new();
+    let mut number = 0;
+    for name in text_parts(&archive) {
+        let xml = read_part(&mut archive, &name).map_err(to_write)?;
+        let mut out = String::with_capacity(xml.len());
+        let mut copied = 0;
+        for paragraph in scan(&xml).map_err(to_write)? {
+            number += 1;
+            if let Some(text) = targets.document_target(&number.to_string()) {
+                out.push_str(&xml[copied..paragraph.segment.span.start]);
+                out.push_str(&runs(&text, &paragraph));
+                copied = paragraph.segment.span.end;
+            }
+        }
+        out.push_str(&xml[copied..]);
+        rewritten.insert(name, out.into_bytes());
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index).map_err(write_error)?;
+        match rewritten.remove(file.name()) {
+            Some(content) => {
+                let name = file.name().to_string();
+                drop(file);
+                writer.start_file(name, options).map_err(write_error)?;
+                writer.write_all(&content).map_err(write_error)?;
+            }
+            None => writer.raw_copy_file(file).map_err(write_error)?,
+        }
+    }
+
+    Ok(writer.finish().map_err(write_error)?.into_inner())
+}
+
+/// The parts of the package that hold document text, body first.
+fn text_parts(archive: &ZipArchive<Cursor<&[u8]>>) -> Vec<String> {
+    let rank = |name: &str| {
+        let file = name.strip_prefix("word/")?.strip_suffix(".xml")?;
+        match file {
+            "document" => Some(0),
+            _ if file.starts_with("header") => Some(1),
+            _ if file.starts_with("footer") => Some(2),
+            "footnotes" => Some(3),
+            "endnotes" => Some(4),
+            _ => None,
+        }
+    };
+
+    let mut parts: Vec<(u8, String)> = archive
+        .file_names()
+        .filter_map(|name| Some((rank(name)?, name.to_string())))
+        .collect();
+    parts.sort();
+    parts.into_iter().map(|(_, name)| name).collect()
+}
+
+fn read_part(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String, FormatError> {
+    let mut file = archive.by_name(name).map_err(parse_error)?;
+    let mut xml = String::new();
+    file.read_to_string(&mut xml).map_err(parse_error)?;
+    Ok(xml)
+}
+
+fn scan(xml: &str) -> Result<Vec<Paragraph>, FormatError> {
+    let mut reader = Reader::from_str(xml);
+    let mut paragraphs = Vec::new();
+
+    loop {
+        match reader.read_event().map_err(parse_error)? {
+            Event::Start(e) if e.name().as_ref() == b"w:p" => {
+                if let Some(paragraph) = paragraph(&mut reader, xml)? {
+                    paragraphs.push(paragraph);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(paragraphs)
+}
+
+/// Reads the content of a `<w:p>` up to its end tag.
+fn paragraph(reader: &mut Reader<&[u8]>, xml: &str) -> Result<Option<Paragraph>, FormatError> {
+    let mut items: Vec<(Item, Range<usize>)> = Vec::new();
+    let mut heading = false;
+
+    loop {
+        let before = position(reader);
+        match reader.read_event().map_err(parse_error)? {
+            Event::Start(e) => {
+                let end = e.to_end().into_owned();
+                match e.name().as_ref() {
+                    b"w:pPr" => {
+                        reader.read_to_end(end.name()).map_err(parse_error)?;
+                        heading = is_heading(&xml[before..position(reader)]);
+                    }
+                    b"w:r" => run(reader, xml, before, &mut items)?,
+                    name if WRAPPERS.contains(&name) => {
+                        items.push((Item::Open(xml[before..position(reader)].to_string()), before..position(reader)));
+                    }
+                    _ => {
+                        reader.read_to_end(end.name()).map_err(parse_error)?;
+                        let after = position(reader);
+                        items.push((Item::Raw(xml[before..after].to_string()), before..after));
+                    }
+                }
+            }
+            Event::Empty(e) => match e.name().as_ref() {
+                // Spelling and grammar marks are recomputed by Word.
+                b"w:pPr" | b"w:proofErr" => {}
+                _ => {
+                    let after = position(reader);
+                    items.push((Item::Raw(xml[before..after].to_string()), before..after));
+                }
+            },
+            Event::End(e) if e.name().as_ref() == b"w:p" => break,
+            Event::End(_) => {
+                let after = position(reader);
+                items.push((Item::Close(xml[before..after].to_string()), before..after));
+            }
+            Event::Eof => return Err(FormatError::Parse("unexpected end of file inside <w:p>".to_string())),
+            _ => {}
+        }
+    }
+
+    // Markup of its own before the first or after the last text, such as a
+    // bookmark, stays outside the segment so translators don't have to place
+    // it. Markup sharing a run with text can't be split off.
+    let standalone = |index: usize| matches!(items[index].0, Item::Raw(_));
+    let mut first = 0;
+    while first < items.len()
+        && standalone(first)
+        && items.get(first + 1).is_none_or(|next| next.1.start >= items[first].1.end)
+    {
+        first += 1;
+    }
+    let mut last = items.len();
+    while last > first && standalone(last - 1) && (last - 1 == first || items[last - 2].1.end <= items[last - 1].1.start) {
+        last -= 1;
+    }
+    if first == last {
+        return Ok(None);
+    }
+    let span = items[first].1.start..items[last - 1].1.end;
+    let items = &items[first..last];
+
+    // The formatting that covers the most text outside hyperlinks is left
+    // untagged; ties go to the one that comes first.
+    let mut lengths: Vec<(&str, usize)> = Vec::new();
+    let mut depth = 0;
+    for (item, _) in items {
+        match item {
+            Item::Open(_) => depth += 1,
+            Item::Close(_) => depth -= 1,
+            Item::Text { properties, text } if depth == 0 => match lengths.iter_mut().find(|(seen, _)| seen == properties) {
+                Some((_, length)) => *length += text.chars().count(),
+                None => lengths.push((properties, text.chars().count())),
+            },
+            _ => {}
+        }
+    }
+    let base = lengths
+        .iter()
+        .fold(None, |best: Option<(&str, usize)>, &(properties, length)| match best {
+            Some((_, best_length)) if best_length >= length => best,
+            _ => Some((properties, length)),
+        })
+        .map(|(properties, _)| properties.to_string())
+        .unwrap_or_default();
+
+    let mut builder = SegmentBuilder::default();
+    let mut formatting: Option<(&str, usize)> = None;
+    let mut wrappers = Vec::new();
+    for (item, _) in items {
+        if !matches!(item, Item::Text { .. } | Item::Raw(_)) {
+            if let Some((_, index)) = formatting.take() {
+                builder.close(index, "");
+            }
+        }
+        match item {
+            Item::Text { properties, text } => {
+                if formatting.map(|(open, _)| open) != Some(properties.as_str()) {
+                    if let Some((_, index)) = formatting.take() {
+                        builder.close(index, "");
+                    }
+                    if *properties != base {
+                        formatting = Some((properties, builder.open(properties, &formatting_label(properties))));
+                    }
+                }
+                builder.text(text);
+            }
+            Item::Raw(markup) => builder.standalone(markup, label(markup)),
+            Item::Open(markup) => wrappers.push(builder.open(markup, label(markup))),
+            Item::Close(markup) => match wrappers.pop() {
+                Some(index) => builder.close(index, markup),
+                None => builder.standalone(markup, label(markup)),
+            },
+        }
+    }
+    if let Some((_, index)) = formatting.take() {
+        builder.close(index, "");
+    }
+
+    if !builder.has_text() {
+        return Ok(None);
+    }
+    let (text, placeholders) = builder.finish();
+    Ok(Some(Paragraph {
+        segment: Segment {
+            text,
+            placeholders,
+            span,
+            heading,
+        },
+        base,
+    }))
+}
+
+/// Whether paragraph properties make the paragraph a heading: a heading or
+/// title style, or an outline level.
+fn is_heading(properties: &str) -> bool {
+    properties.contains("<w:pStyle w:val=\"Heading")
+        || properties.contains("<w:pStyle w:val=\"Title\"")
+        || properties.contains("<w:outlineLvl ")
+}
+
+/// Reads a `<w:r>` up to its end tag, adding its text and other content as
+/// items that all span the whole run.
+fn run(
+    reader: &mut Reader<&[u8]>,
+    xml: &str,
+    start: usize,
+    items: &mut Vec<(Item, Range<usize>)>,
+) -> Result<(), FormatError> {
+    let mut properties = String::new();
+    let mut content = Vec::new();
+
+    loop {
+        let before = position(reader);
+        match reader.read_event().map_err(parse_error)? {
+            Event::Start(e) => {
+                let end = e.to_end().into_owned();
+                match e.name().as_ref() {
+                    b"w:t" => {
+                        let raw = reader.read_text(end.name()).map_err(parse_error)?;
+                        let text = unescape(&raw).map_err(parse_error)?.into_owned();
+                        content.push(Ok(text));
+                    }
+                    name => {
+                        let is_properties = name == b"w:rPr";
+                        reader.read_to_end(end.name()).map_err(parse_error)?;
+                        let markup = xml[before..position(reader)].to_string();
+                        if is_properties {
+                            properties = markup;
+                        } else {
+                            content.push(Err(markup));
+                        }
+                    }
+                }
+            }
+            Event::Empty(e) => match e.name().as_ref() {
+                b"w:rPr" => properties = xml[before..position(reader)].to_string(),
+                b"w:t" | b"w:lastRenderedPageBreak" => {}
+                // Plain tabs and line breaks read as text, the way
+                // `text_run` writes them back. Page breaks stay markup.
+                b"w:tab" => content.push(Ok("\t".to_string())),
+                b"w:br" if e.attributes().next().is_none() => content.push(Ok("\n".to_string())),
+                _ => content.push(Err(xml[before..position(reader)].to_string())),
+            },
+            Event::End(e) if e.name().as_ref() == b"w:r" => break,
+            Event::Eof => return Err(FormatError::Parse("unexpected end of file inside <w:r>".to_string())),
+            _ => {}
+        }
+    }
+
+    let range = start..position(reader);
+    for piece in content {
+        let item = match piece {
+            Ok(text) => Item::Text {
+                properties: properties.clone(),
+                text,
+            },
+            Err(markup) => Item::Raw(format!("<w:r>{}{}</w:r>", properties, markup)),
+        };
+        items.push((item, range.clone()));
+    }
+    Ok(())
+}
+
+/// A short name for run formatting, such as `b` or `b+i`, going by the
+/// toggles it sets.
+fn formatting_label(properties: &str) -> String {
+    let mut labels = Vec::new();
+    for element in properties.split('<').skip(1) {
+        let label = match placeholders::tag_name(&format!("<{}", element)) {
+            Some("b") => "b",
+            Some("i") => "i",
+            Some("u") => "u",
+            Some("strike" | "dstrike") => "s",
+            _ => continue,
+        };
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    if labels.is_empty() {
+        "format".to_string()
+    } else {
+        labels.join("+")
+    }
+}
+
+/// A short name for other markup: the element inside a run, or the element
+/// itself, such as `drawing` or `hyperlink`.
+fn label(markup: &str) -> &str {
+    let mut inner = markup.strip_prefix("<w:r>").unwrap_or(markup);
+    if inner.starts_with("<w:rPr") {
+        let end = match inner.find("</w:rPr>") {
+            Some(end) => end + "</w:rPr>".len(),
+            None => inner.find('>').map_or(0, |end| end + 1),
+        };
+        inner = &inner[end..];
+    }
+    placeholders::tag_name(inner).unwrap_or("tag")
+}
+
+/// Builds the runs of a translated paragraph. Formatting tags have an empty
+/// closing part and the run properties as their opening part; the markup of
+/// every other tag is copied as is.
+fn runs(text: &str, paragraph: &Paragraph) -> String {
+    let is_formatting = |placeholder: &Placeholder| placeholder.closing.as_deref() == Some("");
+    let mut out = String::new();
+    let mut formatting: Vec<&str> = Vec::new();
+
+    for token in placeholders::tokens(text, &paragraph.segment.placeholders) {
+        match token {
+            Token::Text(text) => {
+                let properties = formatting.last().copied().unwrap_or(&paragraph.base);
+                out.push_str(&text_run(properties, text));
+            }
+            Token::Open(placeholder) if is_formatting(placeholder) => formatting.push(&placeholder.opening),
+            Token::Close(placeholder) if is_formatting(placeholder) => {
+                if let Some(index) = formatting.iter().rposition(|open| *open == placeholder.opening) {
+                    formatting.remove(index);
+                }
+            }
+            Token::Standalone(placeholder) if is_formatting(placeholder) => {}
+            Token::Open(placeholder) => out.push_str(&placeholder.opening),
+            Token::Close(placeholder) => out.push_str(placeholder.closing.as_deref().unwrap_or_default()),
+            Token::Standalone(placeholder) => {
+                out.push_str(&placeholder.opening);
+                out.push_str(placeholder.closing.as_deref().unwrap_or_default());
+            }
+        }
+    }
+
+    out
+}
+
+/// A run with `text`, turning line breaks and tabs the translator typed into
+/// their Word equivalents.
+fn text_run(properties: &str, text: &str) -> String {
+    let mut out = format!("<w:r>{}", properties);
+    for (line_number, line) in text.split('\n').enumerate() {
+        if line_number > 0 {
+            out.push_str("<w:br/>");
+        }
+        for (part_number, part) in line.split('\t').enumerate() {
+            if part_number > 0 {
+                out.push_str("<w:tab/>");
+            }
+            if !part.is_empty() {
+                out.push_str("<w:t xml:space=\"preserve\">");
+                out.push_str(&partial_escape(part));
+                out.push_str("</w:t>");
+            }
+        }
+    }
+    out.push_str("</w:r>");
+    out
+}
+
+fn position(reader: &Reader<&[u8]>) -> usize {
+    reader.buffer_position() as usize
+}
+
+fn parse_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Parse(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Write(e.to_string())
+}
+
+fn to_write(e: FormatError) -> FormatError {
+    match e {
+        FormatError::Parse(reason) => FormatError::Write(reason),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Docx;
+
+    fn package(body: &str) -> Vec<u8> {
+        let document = format!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{}</w:body></w:document>"#,
+            body
+        );
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("word/document.xml", SimpleFileOptions::default()).unwrap();
+        writer.write_all(document.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn formatted_runs_become_tags() {
+        let template = package(
+            r#"<w:p><w:r><w:t xml:space="preserve">Hello </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>world</w:t></w:r><w:r><w:br/></w:r><w:r><w:t>!</w:t></w:r></w:p><w:p><w:r><w:t>Second paragraph</w:t></w:r></w:p>"#,
+        );
+        let units = parse(Docx, &template, "de");
+        let texts: Vec<&str> = units.iter().map(|unit| unit.source_text.as_str()).collect();
+        assert_eq!(texts, ["Hello <1>world</1>\n!", "Second paragraph"]);
+        round_trip(Docx, &template);
+
+        let out = write(Docx, &template, &[("1", "Hallo <1>Welt</1>\n!")], "de", true);
+        let mut archive = ZipArchive::new(Cursor::new(out)).unwrap();
+        let mut document = String::new();
+        archive.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+        assert!(document.contains(
+            r#"<w:t xml:space="preserve">Hallo </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Welt</w:t></w:r><w:r><w:br/>"#
+        ));
+        assert!(document.contains("<w:t>Second paragraph</w:t>"));
+    }
+}
@@ -0,0 +1,314 @@
+//! Project Fluent (`.ftl`) resources. The value of every message and term
+//! becomes one unit keyed by its identifier (`welcome`, `-brand`), and every
+//! attribute one keyed by `identifier.attribute`. Select expressions stay in
+//! the pattern so translators can add the variants their language needs. The
+//! `#` comment right before an entry becomes its developer comment; group and
+//! resource comments are copied unchanged.
+
+use std::ops::Range;
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+/// Continuation lines written for patterns that had none.
+const DEFAULT_INDENT: &str = "    ";
+
+struct Entry {
+    comment: Option<String>,
+    /// The entry's lines including the comment right before it.
+    span: Range<usize>,
+    parts: Vec<Part>,
+}
+
+/// The value or one attribute of an entry.
+struct Part {
+    key: String,
+    text: String,
+    /// From the first to the last character of the pattern.
+    span: Range<usize>,
+    /// The indentation of the pattern's continuation lines.
+    indent: String,
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let text = decode(content)?;
+
+    Ok(scan(text)
+        .into_iter()
+        .flat_map(|entry| {
+            let comment = entry.comment;
+            entry.parts.into_iter().map(move |part| ResourceUnit {
+                key: part.key,
+                source_text: part.text,
+                developer_comment: comment.clone(),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+/// Writes the template with every pattern replaced by its translation. An
+/// entry is removed along with its comment only when none of its parts has a
+/// translation and there is no fallback, since a message can't lose its value
+/// and keep its attributes.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let text = decode(template).map_err(|e| FormatError::Write(e.to_string()))?;
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for entry in scan(text) {
+        let resolved: Vec<Option<&str>> = entry
+            .parts
+            .iter()
+            .map(|part| targets.resolve(&part.key, &part.text))
+            .collect();
+        if resolved.iter().all(Option::is_none) {
+            out.push_str(&text[copied..entry.span.start]);
+            copied = entry.span.end;
+            continue;
+        }
+        for (part, resolved) in entry.parts.iter().zip(resolved) {
+            out.push_str(&text[copied..part.span.start]);
+            out.push_str(&indent(resolved.unwrap_or(&part.text), &part.indent, newline));
+            copied = part.span.end;
+        }
+    }
+    out.push_str(&text[copied..]);
+
+    let mut bytes = Vec::with_capacity(out.len() + 3);
+    if template.starts_with(b"\xEF\xBB\xBF") {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    bytes.extend_from_slice(out.as_bytes());
+    Ok(bytes)
+}
+
+fn scan(text: &str) -> Vec<Entry> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+
+    let mut entries = Vec::new();
+    let mut comment: Vec<&str> = Vec::new();
+    let mut comment_start: Option<usize> = None;
+    let mut index = 0;
+
+    while index < lines.len() {
+        let (line_start, line) = lines[index];
+        let content = line.trim_end_matches(['\n', '\r']);
+        index += 1;
+
+        if let Some(body) = content.strip_prefix('#') {
+            if body.starts_with('#') {
+                // Group and resource comments don't belong to an entry.
+                comment.clear();
+                comment_start = None;
+            } else {
+                comment_start.get_or_insert(line_start);
+                comment.push(body.strip_prefix(' ').unwrap_or(body));
+            }
+            continue;
+        }
+
+        let Some((id, value_offset)) = entry_start(content) else {
+            comment.clear();
+            comment_start = None;
+            continue;
+        };
+
+        // Indented lines continue the entry. Blank lines only do when more
+        // indented lines follow.
+        let mut body = vec![(line_start + value_offset, &content[value_offset..])];
+        let mut end = line_start + line.len();
+        let mut next = index;
+        while next < lines.len() {
+            let (start, line) = lines[next];
+            let content = line.trim_end_matches(['\n', '\r']);
+            if content.trim().is_empty() {
+                next += 1;
+                continue;
+            }
+            if !content.starts_with(' ') {
+                break;
+            }
+            for &(start, line) in &lines[index..=next] {
+                body.push((start, line.trim_end_matches(['\n', '\r'])));
+            }
+            end = start + line.len();
+            next += 1;
+            index = next;
+        }
+
+        entries.push(Entry {
+            comment: Some(comment.join("\n")).filter(|c| !c.trim().is_empty()),
+            span: comment_start.unwrap_or(line_start)..end,
+            parts: parts(text, &id, &body),
+        });
+        comment.clear();
+        comment_start = None;
+    }
+
+    entries
+}
+
+/// The identifier of the message or term starting on `line`, and the offset
+/// of its value after the `=`.
+fn entry_start(line: &str) -> Option<(String, usize)> {
+    let name = line.strip_prefix('-').unwrap_or(line);
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let id_end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(line.len());
+    let rest = &line[id_end..];
+    let after = rest.trim_start().strip_prefix('=')?;
+    Some((line[..id_end].to_string(), line.len() - after.len()))
+}
+
+/// Splits the lines after the `=` into the value and the attributes.
+fn parts(text: &str, id: &str, body: &[(usize, &str)]) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut key = id.to_string();
+    let mut lines: Vec<(usize, &str)> = Vec::new();
+    let mut depth = 0;
+
+    for (number, &(start, line)) in body.iter().enumerate() {
+        if number > 0 && depth == 0 {
+            if let Some((name, value_offset)) = attribute_start(line) {
+                parts.extend(part(text, &key, &lines));
+                key = format!("{}.{}", id, name);
+                lines = vec![(start + value_offset, &line[value_offset..])];
+                continue;
+            }
+        }
+        depth = placeable_depth(line, depth);
+        lines.push((start, line));
+    }
+    parts.extend(part(text, &key, &lines));
+
+    parts
+}
+
+fn attribute_start(line: &str) -> Option<(String, usize)> {
+    let trimmed = line.trim_start();
+    let name = trimmed.strip_prefix('.')?;
+    let name_end = name
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(name.len());
+    if name_end == 0 {
+        return None;
+    }
+    let after = name[name_end..].trim_start().strip_prefix('=')?;
+    Some((name[..name_end].to_string(), line.len() - after.len()))
+}
+
+/// The pattern on `lines`, or `None` when they hold only whitespace.
+fn part(text: &str, key: &str, lines: &[(usize, &str)]) -> Option<Part> {
+    let start = lines.iter().find_map(|&(start, line)| {
+        let offset = line.find(|c: char| !c.is_whitespace())?;
+        Some(start + offset)
+    })?;
+    let end = lines.iter().rev().find_map(|&(start, line)| {
+        let trimmed = line.trim_end();
+        (!trimmed.trim_start().is_empty()).then_some(start + trimmed.len())
+    })?;
+
+    let raw = &text[start..end];
+    let indent_width = raw
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min();
+    let pattern = match indent_width {
+        Some(width) => {
+            let mut lines = raw.lines();
+            let first = lines.next().unwrap_or_default();
+            std::iter::once(first)
+                .chain(lines.map(|line| line.get(width..).unwrap_or("")))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        None => raw.to_string(),
+    };
+
+    Some(Part {
+        key: key.to_string(),
+        text: pattern,
+        span: start..end,
+        indent: indent_width.map_or(DEFAULT_INDENT.to_string(), |width| " ".repeat(width)),
+    })
+}
+
+/// The placeable nesting depth after `line`, skipping braces inside string
+/// literals.
+fn placeable_depth(line: &str, mut depth: usize) -> usize {
+    let mut in_string = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' if depth > 0 => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Indents every line after the first so the pattern continues the entry.
+fn indent(pattern: &str, indent: &str, newline: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    for (number, line) in pattern.lines().enumerate() {
+        if number > 0 {
+            out.push_str(newline);
+            if !line.is_empty() {
+                out.push_str(indent);
+            }
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Fluent;
+
+    const TEMPLATE: &[u8] = b"## Group comment\n\n# Greets the user\nwelcome = Welcome, { $user }!\n    .title = Hello\n\n-brand = Gaia\n\nemails = { $count ->\n        [one] One email\n       *[other] { $count } emails\n    }\n";
+
+    #[test]
+    fn messages_attributes_and_terms() {
+        let units = parse(Fluent, TEMPLATE, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["welcome", "welcome.title", "-brand", "emails"]);
+        assert_eq!(units[1].developer_comment.as_deref(), Some("Greets the user"));
+        assert_eq!(units[3].source_text, "{ $count ->\n    [one] One email\n   *[other] { $count } emails\n}");
+        round_trip(Fluent, TEMPLATE);
+
+        let targets = [("welcome.title", "Hallo\nund willkommen"), ("-brand", "Gaia")];
+        let out = write(Fluent, TEMPLATE, &targets, "de", false);
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "## Group comment\n\n# Greets the user\nwelcome = Welcome, { $user }!\n    .title = Hallo\n    und willkommen\n\n-brand = Gaia\n\n"
+        );
+        let written: Vec<(String, String)> = parse(Fluent, &out, "de")
+            .into_iter()
+            .map(|unit| (unit.key, unit.source_text))
+            .collect();
+        assert_eq!(written[1], ("welcome.title".to_string(), "Hallo\nund willkommen".to_string()));
+    }
+}
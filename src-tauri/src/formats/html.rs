@@ -0,0 +1,276 @@
+//! HTML documents. Text between block-level elements becomes one segment
+//! each, with inline elements such as links, emphasis and `<code>` protected
+//! as placeholders. Scripts, styles and `<pre>` blocks are not translatable
+//! and are copied unchanged. Entities are decoded for translators and text is
+//! escaped again on export.
+
+use std::ops::Range;
+
+use super::placeholders::{self, Segment, SegmentBuilder};
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+/// Elements that sit inside running text rather than starting a new block.
+const INLINE: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "data", "del", "dfn", "em", "i", "img", "ins", "kbd", "label",
+    "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var", "wbr",
+];
+
+/// Elements without an end tag.
+const VOID: &[&str] = &["br", "img", "wbr"];
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Elements whose content is copied unchanged. `<code>` is inline; the others
+/// are blocks.
+const RAW: &[&str] = &["script", "style", "pre", "textarea", "code"];
+
+enum Token {
+    Text,
+    Tag { name: String, closing: bool, self_closing: bool },
+    /// An element whose content is not translatable, from its start tag to
+    /// its end tag.
+    Raw { name: String },
+    Comment,
+    /// Doctypes and processing instructions.
+    Declaration,
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let text = decode(content)?;
+    Ok(placeholders::units(scan(text)))
+}
+
+/// Writes the template with every translated segment replaced.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let bom = template.starts_with(b"\xEF\xBB\xBF");
+    let text = decode(template).map_err(|e| FormatError::Write(e.to_string()))?;
+
+    let mut out = if bom { "\u{FEFF}".to_string() } else { String::new() };
+    out.push_str(&placeholders::splice(text, &scan(text), targets, |text| {
+        html_escape::encode_text(text).into_owned()
+    }));
+    Ok(out.into_bytes())
+}
+
+/// A segment being collected.
+struct Current {
+    builder: SegmentBuilder,
+    start: usize,
+    end: usize,
+    open_elements: Vec<(String, usize)>,
+}
+
+fn scan(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut current: Option<Current> = None;
+    let mut heading = false;
+
+    for (token, range) in tokenize(text) {
+        let raw = &text[range.clone()];
+        let inline = match &token {
+            Token::Text => current.is_some() || !raw.trim().is_empty(),
+            Token::Tag { name, .. } => INLINE.contains(&name.as_str()),
+            Token::Raw { name } => name == "code",
+            Token::Comment => current.is_some(),
+            Token::Declaration => false,
+        };
+        if !inline {
+            finish(&mut current, &mut segments, heading);
+            if let Token::Tag { name, closing, .. } = &token {
+                if HEADINGS.contains(&name.as_str()) {
+                    heading = !closing;
+                }
+            }
+            continue;
+        }
+
+        let segment = current.get_or_insert_with(|| {
+            let start = range.start + (raw.len() - raw.trim_start().len());
+            Current {
+                builder: SegmentBuilder::default(),
+                start,
+                end: start,
+                open_elements: Vec::new(),
+            }
+        });
+
+        match token {
+            Token::Text => {
+                let decoded = html_escape::decode_html_entities(raw);
+                segment.builder.text(&collapse_whitespace(&decoded));
+                if !raw.trim().is_empty() {
+                    segment.end = range.start + raw.trim_end().len();
+                }
+                continue;
+            }
+            Token::Tag { name, closing: false, self_closing } => {
+                if self_closing || VOID.contains(&name.as_str()) {
+                    segment.builder.standalone(raw, &name);
+                } else {
+                    let index = segment.builder.open(raw, &name);
+                    segment.open_elements.push((name, index));
+                }
+            }
+            Token::Tag { name, closing: true, .. } => {
+                match segment.open_elements.iter().rposition(|(open, _)| *open == name) {
+                    Some(position) => {
+                        let (_, index) = segment.open_elements.remove(position);
+                        segment.builder.close(index, raw);
+                    }
+                    None => segment.builder.standalone(raw, &name),
+                }
+            }
+            Token::Raw { name } => segment.builder.standalone(raw, &name),
+            _ => segment.builder.standalone(raw, "comment"),
+        }
+        segment.end = range.end;
+    }
+    finish(&mut current, &mut segments, heading);
+
+    segments
+}
+
+fn finish(current: &mut Option<Current>, segments: &mut Vec<Segment>, heading: bool) {
+    let Some(segment) = current.take() else {
+        return;
+    };
+    if !segment.builder.has_text() {
+        return;
+    }
+    let (text, placeholders) = segment.builder.finish();
+    // Whitespace around the segment is left out of its span, so leave it out
+    // of the text too.
+    segments.push(Segment {
+        text: text.trim().to_string(),
+        placeholders,
+        span: segment.start..segment.end,
+        heading,
+    });
+}
+
+/// Splits the document into text, tags and the other markup between them.
+fn tokenize(text: &str) -> Vec<(Token, Range<usize>)> {
+    // Tag names are matched case-insensitively; ASCII lowercasing keeps the
+    // byte offsets.
+    let lower = text.to_ascii_lowercase();
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    let mut text_start = 0;
+
+    while let Some(offset) = text[position..].find('<') {
+        let start = position + offset;
+        let rest = &lower[start..];
+        let next = rest.as_bytes().get(1).copied();
+
+        let token = if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(text.len(), |end| start + end + 3);
+            Some((Token::Comment, end))
+        } else if matches!(next, Some(b'!' | b'?')) {
+            let end = rest.find('>').map_or(text.len(), |end| start + end + 1);
+            Some((Token::Declaration, end))
+        } else if next.is_some_and(|b| b.is_ascii_alphabetic() || b == b'/') {
+            tag_end(bytes, start).map(|end| {
+                let inner = &lower[start + 1..end - 1];
+                let closing = inner.starts_with('/');
+                let name: String = inner
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(|c| !c.is_whitespace() && *c != '/' && *c != '>')
+                    .collect();
+                let self_closing = inner.ends_with('/');
+                if !closing && !self_closing && RAW.contains(&name.as_str()) {
+                    let close = format!("</{}", name);
+                    let end = lower[end..]
+                        .find(&close)
+                        .and_then(|close_start| {
+                            let close_start = end + close_start;
+                            lower[close_start..].find('>').map(|close_end| close_start + close_end + 1)
+                        })
+                        .unwrap_or(text.len());
+                    (Token::Raw { name }, end)
+                } else {
+                    (Token::Tag { name, closing, self_closing }, end)
+                }
+            })
+        } else {
+            None
+        };
+
+        let Some((token, end)) = token else {
+            // A `<` that doesn't start markup is part of the text.
+            position = start + 1;
+            continue;
+        };
+        if text_start < start {
+            tokens.push((Token::Text, text_start..start));
+        }
+        tokens.push((token, start..end));
+        position = end;
+        text_start = end;
+    }
+    if text_start < text.len() {
+        tokens.push((Token::Text, text_start..text.len()));
+    }
+
+    tokens
+}
+
+/// The end of the tag starting at `start`, after its `>`. Quoted attribute
+/// values may contain `>`.
+fn tag_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (offset, byte) in bytes[start..].iter().enumerate() {
+        match (quote, byte) {
+            (None, b'"' | b'\'') => quote = Some(*byte),
+            (Some(open), _) if open == *byte => quote = None,
+            (None, b'>') => return Some(start + offset + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Runs of whitespace render as a single space outside `<pre>`.
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() && c != '\u{A0}' {
+            if !in_whitespace {
+                out.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            out.push(c);
+            in_whitespace = false;
+        }
+    }
+    out
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Html;
+
+    const TEMPLATE: &[u8] = b"<!DOCTYPE html>\n<html><head><title>Page</title></head>\n<body>\n<h1>Tom &amp; Jerry</h1>\n<p>Click <a href=\"/x\">here</a>.</p>\n<pre>keep   this</pre>\n<script>var s = '<p>no</p>';</script>\n</body></html>\n";
+
+    #[test]
+    fn text_blocks_with_protected_tags() {
+        let units = parse(Html, TEMPLATE, "de");
+        let texts: Vec<&str> = units.iter().map(|unit| unit.source_text.as_str()).collect();
+        assert_eq!(texts, ["Page", "Tom & Jerry", "Click <1>here</1>."]);
+        round_trip(Html, TEMPLATE);
+
+        let targets = [("2", "Tom & Jerry < Itchy"), ("3", "Klicke <1>hier</1>.")];
+        let out = String::from_utf8(write(Html, TEMPLATE, &targets, "de", true)).unwrap();
+        assert!(out.contains("<h1>Tom &amp; Jerry &lt; Itchy</h1>\n<p>Klicke <a href=\"/x\">hier</a>.</p>"));
+        assert!(out.contains("<pre>keep   this</pre>\n<script>var s = '<p>no</p>';</script>"));
+    }
+}
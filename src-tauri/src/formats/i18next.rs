@@ -0,0 +1,295 @@
+//! i18next JSON resources, flat or nested. Each string leaf becomes one unit
+//! keyed by its dotted path (`common.buttons.ok`); plural variants such as
+//! `items_one` / `items_other` are kept as separate units. Plurals get a unit
+//! for each CLDR category of the target language, even the ones the template
+//! has no form for; those start from its `_other` form.
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::{FormatError, ImportOptions, ResourceUnit, TargetLookup};
+use crate::icu::{self, PluralCategories};
+
+const KEY_SEPARATOR: &str = ".";
+
+const PLURAL_SUFFIXES: [&str; 6] = ["zero", "one", "two", "few", "many", "other"];
+
+pub fn parse(content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+    let root: Value = serde_json::from_slice(content).map_err(|e| FormatError::Parse(e.to_string()))?;
+    let object = root
+        .as_object()
+        .ok_or_else(|| FormatError::Parse("expected a JSON object at the top level".to_string()))?;
+
+    let categories = options.target_locale.as_deref().and_then(icu::plural_categories);
+    let mut units = Vec::new();
+    collect(object, "", categories.as_ref(), &mut units);
+    Ok(units)
+}
+
+fn collect(
+    object: &Map<String, Value>,
+    prefix: &str,
+    categories: Option<&PluralCategories>,
+    units: &mut Vec<ResourceUnit>,
+) {
+    let mut missing = missing_plurals(object, categories);
+    for (name, value) in object {
+        let key = join_key(prefix, name);
+        match value {
+            Value::String(text) => units.push(ResourceUnit {
+                notes: describe(name, text),
+                key,
+                source_text: text.clone(),
+                ..Default::default()
+            }),
+            Value::Object(children) => collect(children, &key, categories, units),
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let item_key = join_key(&key, &index.to_string());
+                    match item {
+                        Value::String(text) => units.push(ResourceUnit {
+                            key: item_key,
+                            source_text: text.clone(),
+                            notes: describe(name, text),
+                            ..Default::default()
+                        }),
+                        Value::Object(children) => collect(children, &item_key, categories, units),
+                        _ => {}
+                    }
+                }
+            }
+            // Numbers, booleans and nulls are not translatable; the writer
+            // copies them from the template unchanged.
+            _ => {}
+        }
+        for (form, other) in missing.remove(name.as_str()).unwrap_or_default() {
+            units.push(ResourceUnit {
+                notes: describe(&form, other),
+                key: join_key(prefix, &form),
+                source_text: other.to_string(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// The plural forms of the target language that `object` has no key for,
+/// with the text of their `_other` form, by the name of the plural's last
+/// key so they can follow it.
+fn missing_plurals<'o>(
+    object: &'o Map<String, Value>,
+    categories: Option<&PluralCategories>,
+) -> HashMap<&'o str, Vec<(String, &'o str)>> {
+    let mut missing = HashMap::new();
+    let Some(categories) = categories else {
+        return missing;
+    };
+
+    let mut last_forms: HashMap<&str, &str> = HashMap::new();
+    for name in object.keys() {
+        if let Some((base, _)) = plural_parts(name) {
+            last_forms.insert(base, name);
+        }
+    }
+    for (base, last) in last_forms {
+        let Some(other) = object.get(&format!("{}_other", base)).and_then(Value::as_str) else {
+            continue;
+        };
+        let required = if base.ends_with("_ordinal") {
+            &categories.ordinal
+        } else {
+            &categories.cardinal
+        };
+        let forms: Vec<(String, &str)> = required
+            .iter()
+            .map(|category| format!("{}_{}", base, category))
+            .filter(|form| !object.contains_key(form))
+            .map(|form| (form, other))
+            .collect();
+        if !forms.is_empty() {
+            missing.insert(last, forms);
+        }
+    }
+    missing
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", prefix, KEY_SEPARATOR, name)
+    }
+}
+
+/// Notes that help the translator: which plural form a key is, and which
+/// `{{placeholders}}` must be kept.
+fn describe(name: &str, text: &str) -> Option<String> {
+    let mut notes = Vec::new();
+
+    if let Some(category) = plural_category(name) {
+        notes.push(format!("Plural form: {}", category));
+    }
+
+    let placeholders = interpolations(text);
+    if !placeholders.is_empty() {
+        notes.push(format!("Placeholders: {}", placeholders.join(", ")));
+    }
+
+    if notes.is_empty() {
+        None
+    } else {
+        Some(notes.join("\n"))
+    }
+}
+
+fn plural_category(name: &str) -> Option<String> {
+    let (base, suffix) = plural_parts(name)?;
+    if base.ends_with("_ordinal") {
+        Some(format!("ordinal {}", suffix))
+    } else {
+        Some(suffix.to_string())
+    }
+}
+
+/// The base name and category of a plural key such as `items_few`.
+fn plural_parts(name: &str) -> Option<(&str, &str)> {
+    let (base, suffix) = name.rsplit_once('_')?;
+    if base.is_empty() || !PLURAL_SUFFIXES.contains(&suffix) {
+        return None;
+    }
+    Some((base, suffix))
+}
+
+fn interpolations(text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let placeholder = format!("{{{{{}}}}}", &after[..end]);
+                if !found.contains(&placeholder) {
+                    found.push(placeholder);
+                }
+                rest = &after[end + 2..];
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+/// Writes the template with every string replaced by its translation, keeping
+/// key order and nesting. Plural forms of the target language the template
+/// lacks follow the plural's last key, falling back to its `_other` form.
+/// Array items without a translation are written as `null` when there is no
+/// fallback, so the items after them keep their index.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let root: Value = serde_json::from_slice(template).map_err(|e| FormatError::Write(e.to_string()))?;
+    let object = root
+        .as_object()
+        .ok_or_else(|| FormatError::Write("template is not a JSON object".to_string()))?;
+
+    let categories = targets.target_locale().and_then(icu::plural_categories);
+    let translated = Value::Object(translate_object(object, "", categories.as_ref(), targets));
+    let mut out = serde_json::to_vec_pretty(&translated).map_err(|e| FormatError::Write(e.to_string()))?;
+    out.push(b'\n');
+    Ok(out)
+}
+
+fn translate_object(
+    object: &Map<String, Value>,
+    prefix: &str,
+    categories: Option<&PluralCategories>,
+    targets: &TargetLookup,
+) -> Map<String, Value> {
+    let mut missing = missing_plurals(object, categories);
+    let mut out = Map::new();
+    for (name, value) in object {
+        let key = join_key(prefix, name);
+        if let Some(value) = translate_value(value, &key, categories, targets) {
+            out.insert(name.clone(), value);
+        }
+        for (form, other) in missing.remove(name.as_str()).unwrap_or_default() {
+            if let Some(text) = targets.resolve(&join_key(prefix, &form), other) {
+                out.insert(form, Value::String(text.to_string()));
+            }
+        }
+    }
+    out
+}
+
+fn translate_value(
+    value: &Value,
+    key: &str,
+    categories: Option<&PluralCategories>,
+    targets: &TargetLookup,
+) -> Option<Value> {
+    match value {
+        Value::String(source) => targets.resolve(key, source).map(|text| Value::String(text.to_string())),
+        Value::Object(children) => Some(Value::Object(translate_object(children, key, categories, targets))),
+        Value::Array(items) => Some(Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    translate_value(item, &join_key(key, &index.to_string()), categories, targets).unwrap_or(Value::Null)
+                })
+                .collect(),
+        )),
+        other => Some(other.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::I18nextJson;
+    use serde_json::{json, Value};
+
+    fn written(template: &[u8], targets: &[(&str, &str)], locale: &str, fallback_to_source: bool) -> Value {
+        serde_json::from_slice(&write(I18nextJson, template, targets, locale, fallback_to_source)).unwrap()
+    }
+
+    #[test]
+    fn nested_keys() {
+        let template = br#"{"title": "Hello {{name}}", "menu": {"open": "Open", "recent": ["One", "Two"]}, "version": 2}"#;
+        let units = parse(I18nextJson, template, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["title", "menu.open", "menu.recent.0", "menu.recent.1"]);
+        assert_eq!(units[0].notes.as_deref(), Some("Placeholders: {{name}}"));
+        round_trip(I18nextJson, template);
+    }
+
+    #[test]
+    fn target_plural_forms() {
+        let template = br#"{"items_one": "{{count}} item", "items_other": "{{count}} items", "place_ordinal_one": "{{count}}st", "place_ordinal_other": "{{count}}th"}"#;
+        let units = parse(I18nextJson, template, "pl");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["items_one", "items_other", "items_few", "items_many", "place_ordinal_one", "place_ordinal_other"]);
+        assert_eq!(units[3].source_text, "{{count}} items");
+        assert_eq!(units[3].notes.as_deref(), Some("Plural form: many\nPlaceholders: {{count}}"));
+
+        let targets = [("items_one", "{{count}} plik"), ("items_few", "{{count}} pliki"), ("items_many", "{{count}} plików")];
+        assert_eq!(
+            written(template, &targets, "pl", false),
+            json!({"items_one": "{{count}} plik", "items_few": "{{count}} pliki", "items_many": "{{count}} plików"})
+        );
+        assert_eq!(
+            written(template, &[], "en", true),
+            json!({
+                "items_one": "{{count}} item", "items_other": "{{count}} items",
+                "place_ordinal_one": "{{count}}st", "place_ordinal_other": "{{count}}th",
+                "place_ordinal_two": "{{count}}th", "place_ordinal_few": "{{count}}th",
+            })
+        );
+    }
+
+    #[test]
+    fn untranslated_array_items_keep_their_place() {
+        let template = br#"{"steps": ["One", "Two", "Three"]}"#;
+        let targets = [("steps.2", "Drei")];
+        assert_eq!(written(template, &targets, "de", false), json!({"steps": [null, null, "Drei"]}));
+        assert_eq!(written(template, &targets, "de", true), json!({"steps": ["One", "Two", "Drei"]}));
+    }
+}
@@ -0,0 +1,175 @@
+//! The structured form of segment text that editors work with: runs of text
+//! between tags and variables, each with its id and a short name to show.
+//! It is built from the stored text and placeholders, and turned back into
+//! stored text when an edited translation is saved, so every format reads
+//! and writes it through the same two representations.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::placeholders::{self, Placeholder, PlaceholderKind, Token};
+use super::ResourceUnit;
+use crate::icu;
+
+/// C and Objective-C format specifiers (`%d`, `%1$s`, `%@`, `%lld`) and
+/// stringsdict variables (`%#@count@`). `%%` is matched so it can be skipped.
+pub const PRINTF: &str = r"%%|%(?:\d+\$)?#@\w+@|%(?:\d+\$)?[-+ 0#']*\d*(?:\.\d+)?(?:hh|h|ll|l|q|z|t|j|L)?[@dDiuUxXoOfFeEgGcCsSpaA]";
+/// ICU and `MessageFormat` arguments: `{name}`, `{0}`, `{0,number,#.#}`.
+pub const BRACES: &str = r"\{\s*\w+\s*(?:,\s*(?:number|date|time)[^{}]*)?\}";
+/// i18next interpolation and nesting: `{{name}}`, `$t(key)`.
+pub const I18NEXT: &str = r"\{\{[^{}]+\}\}|\$t\([^)]*\)";
+/// .NET composite format items: `{0}`, `{1,-8}`, `{2:N2}`.
+pub const DOTNET: &str = r"\{\d+(?:,-?\d+)?(?::[^{}]*)?\}";
+/// Qt arguments: `%1`, `%L1`, `%n`.
+pub const QT: &str = r"%L?\d+|%n";
+/// Rails (`%{name}`, `%<name>d`) and Symfony (`%name%`, `{name}`) variables.
+pub const RUBY: &str = r"%\{\w+\}|%<\w+>[-+ 0#]*\d*(?:\.\d+)?[a-zA-Z]|%\w+%|\{\w+\}";
+/// Fluent variable and term references: `{ $name }`, `{ -brand }`.
+pub const FLUENT: &str = r"\{\s*[$-][\w-]+\s*\}";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InlineElement {
+    Text(String),
+    /// The start of paired markup, such as a link.
+    Open { id: u32, display: String },
+    Close { id: u32, display: String },
+    /// Markup without content of its own, such as an image or a line break.
+    Standalone { id: u32, display: String },
+    /// A variable of the file format, such as `%1$s` or `{name}`.
+    Variable { id: u32, display: String },
+}
+
+/// The elements of `text`, resolving its numbered tags and variables against
+/// `placeholders`.
+pub fn content(text: &str, placeholders: &[Placeholder]) -> Vec<InlineElement> {
+    let variables: Vec<&Placeholder> = placeholders
+        .iter()
+        .filter(|p| p.kind == PlaceholderKind::Variable && !p.opening.is_empty())
+        .collect();
+
+    let mut elements = Vec::new();
+    for token in placeholders::tokens(text, placeholders) {
+        let element = match token {
+            Token::Text(text) => {
+                push_text(&mut elements, text, &variables);
+                continue;
+            }
+            Token::Open(p) => InlineElement::Open { id: p.id, display: p.label().to_string() },
+            Token::Close(p) => InlineElement::Close { id: p.id, display: p.label().to_string() },
+            Token::Standalone(p) => InlineElement::Standalone { id: p.id, display: p.label().to_string() },
+        };
+        elements.push(element);
+    }
+    elements
+}
+
+/// Writes `elements` back as stored text. Every tag and variable has to be
+/// one of `placeholders`.
+pub fn to_text(elements: &[InlineElement], placeholders: &[Placeholder]) -> Result<String, String> {
+    let find = |id: u32, kind: PlaceholderKind| {
+        placeholders
+            .iter()
+            .find(|p| p.id == id && p.kind == kind)
+            .ok_or_else(|| format!("Segment has no placeholder {}", id))
+    };
+
+    let mut text = String::new();
+    for element in elements {
+        match element {
+            InlineElement::Text(part) => text.push_str(part),
+            InlineElement::Open { id, .. } => {
+                find(*id, PlaceholderKind::Tag)?;
+                text.push_str(&format!("<{}>", id));
+            }
+            InlineElement::Close { id, .. } => {
+                find(*id, PlaceholderKind::Tag)?;
+                text.push_str(&format!("</{}>", id));
+            }
+            InlineElement::Standalone { id, .. } => {
+                find(*id, PlaceholderKind::Tag)?;
+                text.push_str(&format!("<{}/>", id));
+            }
+            InlineElement::Variable { id, .. } => text.push_str(&find(*id, PlaceholderKind::Variable)?.opening),
+        }
+    }
+    Ok(text)
+}
+
+/// Records the variables `pattern` finds in each unit's source text as
+/// placeholders, numbered after the unit's tags. A variable used more than
+/// once is recorded once.
+pub(crate) fn mark_variables(units: Vec<ResourceUnit>, pattern: &str) -> Vec<ResourceUnit> {
+    let pattern = Regex::new(pattern).expect("variable patterns are valid");
+    units
+        .into_iter()
+        .map(|unit| {
+            let codes: Vec<String> = pattern.find_iter(&unit.source_text).map(|m| m.as_str().to_string()).collect();
+            add_variables(unit, codes)
+        })
+        .collect()
+}
+
+/// Like `mark_variables` with `BRACES`, for ICU messages: the text of a
+/// plural or select option is in braces too, so only a brace naming one of
+/// the message's arguments is a variable. Messages that don't parse are
+/// marked by the pattern alone.
+pub(crate) fn mark_message_arguments(units: Vec<ResourceUnit>) -> Vec<ResourceUnit> {
+    let pattern = Regex::new(BRACES).expect("variable patterns are valid");
+    units
+        .into_iter()
+        .map(|unit| {
+            let arguments = icu::parse(&unit.source_text).ok().map(|elements| icu::arguments(&elements));
+            let codes: Vec<String> = pattern
+                .find_iter(&unit.source_text)
+                .map(|m| m.as_str())
+                .filter(|code| {
+                    let name = code[1..].split([',', '}']).next().unwrap_or_default().trim();
+                    arguments.as_ref().is_none_or(|arguments| arguments.contains(name))
+                })
+                .map(str::to_string)
+                .collect();
+            add_variables(unit, codes)
+        })
+        .collect()
+}
+
+fn add_variables(mut unit: ResourceUnit, codes: Vec<String>) -> ResourceUnit {
+    let mut next = unit.placeholders.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+    for code in codes {
+        if code == "%%" || unit.placeholders.iter().any(|p| p.opening == code) {
+            continue;
+        }
+        unit.placeholders.push(Placeholder {
+            id: next,
+            opening: code,
+            closing: None,
+            display: None,
+            kind: PlaceholderKind::Variable,
+        });
+        next += 1;
+    }
+    unit
+}
+
+/// Adds a run of text, split around the variables in it. Where two
+/// variables start at the same place the longer one wins.
+fn push_text(elements: &mut Vec<InlineElement>, mut text: &str, variables: &[&Placeholder]) {
+    while !text.is_empty() {
+        let next = variables
+            .iter()
+            .filter_map(|variable| text.find(&variable.opening).map(|start| (start, *variable)))
+            .min_by_key(|(start, variable)| (*start, std::cmp::Reverse(variable.opening.len())));
+        let Some((start, variable)) = next else {
+            elements.push(InlineElement::Text(text.to_string()));
+            break;
+        };
+        if start > 0 {
+            elements.push(InlineElement::Text(text[..start].to_string()));
+        }
+        elements.push(InlineElement::Variable {
+            id: variable.id,
+            display: variable.label().to_string(),
+        });
+        text = &text[start + variable.opening.len()..];
+    }
+}
@@ -0,0 +1,232 @@
+//! Markdown documents. Paragraphs, headings, list items and table cells
+//! become one segment each, with links, emphasis, code spans and inline HTML
+//! protected as placeholders. Code blocks, HTML blocks and front matter are
+//! not translatable and are copied unchanged.
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use super::placeholders::{self, Segment, SegmentBuilder};
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let text = decode(content)?;
+    Ok(placeholders::units(scan(text)))
+}
+
+/// Writes the template with every translated segment replaced. Translations
+/// are Markdown themselves, so they are written as they are.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let bom = template.starts_with(b"\xEF\xBB\xBF");
+    let text = decode(template).map_err(|e| FormatError::Write(e.to_string()))?;
+
+    let mut out = if bom { "\u{FEFF}".to_string() } else { String::new() };
+    out.push_str(&placeholders::splice(text, &scan(text), targets, str::to_string));
+    Ok(out.into_bytes())
+}
+
+/// A segment being collected.
+struct Current {
+    builder: SegmentBuilder,
+    start: usize,
+    end: usize,
+    /// The end of the last event, where the closing markup of a span starts.
+    cursor: usize,
+    /// A span whose opening markup runs up to the next event.
+    pending: Option<(usize, usize)>,
+    open_spans: Vec<usize>,
+    /// Nesting depth inside an autolink, whose text is protected as a whole.
+    opaque: usize,
+}
+
+fn scan(text: &str) -> Vec<Segment> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut segments = Vec::new();
+    let mut current: Option<Current> = None;
+    // Nesting depth inside code blocks, HTML blocks and front matter.
+    let mut raw = 0;
+    let mut heading = false;
+
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        match &event {
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => {
+                finish(&mut current, &mut segments, heading);
+                raw += 1;
+                continue;
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                raw -= 1;
+                continue;
+            }
+            _ if raw > 0 => continue,
+            // The checkbox of a task list item is part of the list syntax.
+            Event::TaskListMarker(_) => continue,
+            _ if !is_inline(&event) => {
+                finish(&mut current, &mut segments, heading);
+                heading = matches!(event, Event::Start(Tag::Heading { .. }));
+                continue;
+            }
+            _ => {}
+        }
+
+        let segment = current.get_or_insert_with(|| Current {
+            builder: SegmentBuilder::default(),
+            start: range.start,
+            end: range.start,
+            cursor: range.start,
+            pending: None,
+            open_spans: Vec::new(),
+            opaque: 0,
+        });
+
+        if segment.opaque > 0 {
+            match event {
+                Event::Start(_) => segment.opaque += 1,
+                Event::End(_) => segment.opaque -= 1,
+                _ => {}
+            }
+            segment.cursor = range.end;
+            segment.end = range.end;
+            continue;
+        }
+
+        if let Event::End(_) = event {
+            let index = segment.open_spans.pop();
+            match segment.pending.take() {
+                // A span without content, like an empty link, is kept whole.
+                Some((pending, start)) if Some(pending) == index => {
+                    segment.builder.set_opening(pending, &text[start..range.end]);
+                }
+                _ => {
+                    if let Some(index) = index {
+                        segment.builder.close(index, &text[segment.cursor..range.end]);
+                    }
+                }
+            }
+            segment.cursor = range.end;
+            segment.end = range.end;
+            continue;
+        }
+
+        if let Some((pending, start)) = segment.pending.take() {
+            segment.builder.set_opening(pending, &text[start..range.start]);
+        }
+        match event {
+            Event::Text(_) => segment.builder.text(&text[range.clone()]),
+            // Line breaks inside a paragraph don't change how it renders.
+            Event::SoftBreak => segment.builder.text(" "),
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => {
+                segment.builder.standalone(&text[range.clone()], "link");
+                segment.opaque = 1;
+            }
+            Event::Start(tag) => {
+                let display = match tag {
+                    Tag::Emphasis => "i",
+                    Tag::Strong => "b",
+                    Tag::Strikethrough => "s",
+                    Tag::Link { .. } => "link",
+                    Tag::Image { .. } => "image",
+                    _ => "span",
+                };
+                let index = segment.builder.open("", display);
+                segment.pending = Some((index, range.start));
+                segment.open_spans.push(index);
+            }
+            Event::Code(code) => segment.builder.standalone(&text[range.clone()], &format!("`{}`", code)),
+            Event::InlineHtml(html) => {
+                segment.builder.standalone(&text[range.clone()], placeholders::tag_name(&html).unwrap_or("html"))
+            }
+            Event::HardBreak => segment.builder.standalone(&text[range.clone()], "br"),
+            _ => segment.builder.standalone(&text[range.clone()], &text[range.clone()]),
+        }
+        segment.cursor = range.end;
+        segment.end = range.end;
+    }
+    finish(&mut current, &mut segments, heading);
+
+    segments
+}
+
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Text(_)
+        | Event::Code(_)
+        | Event::InlineMath(_)
+        | Event::InlineHtml(_)
+        | Event::FootnoteReference(_)
+        | Event::SoftBreak
+        | Event::HardBreak => true,
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ),
+        Event::End(tag) => matches!(
+            tag,
+            TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link
+                | TagEnd::Image
+        ),
+        _ => false,
+    }
+}
+
+fn finish(current: &mut Option<Current>, segments: &mut Vec<Segment>, heading: bool) {
+    let Some(segment) = current.take() else {
+        return;
+    };
+    if !segment.builder.has_text() {
+        return;
+    }
+    let (text, placeholders) = segment.builder.finish();
+    segments.push(Segment {
+        text,
+        placeholders,
+        span: segment.start..segment.end,
+        heading,
+    });
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Markdown;
+
+    const TEMPLATE: &[u8] = b"---\ntitle: Demo\n---\n# Getting *started*\n\nRead the [guide](https://x.io \"Guide\") and run `npm i`.\n\n```sh\necho hi\n```\n\n- First item\n";
+
+    #[test]
+    fn inline_markup_is_protected() {
+        let units = parse(Markdown, TEMPLATE, "de");
+        let texts: Vec<&str> = units.iter().map(|unit| unit.source_text.as_str()).collect();
+        assert_eq!(texts, ["Getting <1>started</1>", "Read the <1>guide</1> and run <2/>.", "First item"]);
+        round_trip(Markdown, TEMPLATE);
+
+        let targets = [("1", "Erste <1>Schritte</1>"), ("2", "Lies die <1>Anleitung</1> und führe <2/> aus.")];
+        let out = String::from_utf8(write(Markdown, TEMPLATE, &targets, "de", true)).unwrap();
+        assert_eq!(
+            out,
+            "---\ntitle: Demo\n---\n# Erste *Schritte*\n\nLies die [Anleitung](https://x.io \"Guide\") und führe `npm i` aus.\n\n```sh\necho hi\n```\n\n- First item\n"
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::database::{Translation, TranslationStatus};
+use crate::segmentation::{self, SegmentationRules};
+
+pub mod android;
+pub mod apple_strings;
+pub mod arb;
+pub mod docx;
+pub mod fluent;
+pub mod html;
+pub mod i18next;
+pub mod inline;
+pub mod markdown;
+pub mod placeholders;
+pub mod properties;
+pub mod qt_ts;
+pub mod resx;
+pub mod stringsdict;
+pub mod subtitles;
+pub mod xcstrings;
+pub mod yaml;
+
+pub use placeholders::Placeholder;
+
+/// File formats GAIA can import translations from and export them back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+pub enum ResourceFormat {
+    I18nextJson,
+    AndroidStrings,
+    AppleStrings,
+    AppleStringsDict,
+    XcStrings,
+    SubRip,
+    WebVtt,
+    JavaProperties,
+    Resx,
+    QtTs,
+    Markdown,
+    Html,
+    Docx,
+    Arb,
+    Fluent,
+    Yaml,
+}
+
+impl ResourceFormat {
+    pub fn parse(&self, content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+        let units = match self {
+            ResourceFormat::I18nextJson => i18next::parse(content, options),
+            ResourceFormat::AndroidStrings => android::parse(content, options),
+            ResourceFormat::AppleStrings => apple_strings::parse(content),
+            ResourceFormat::AppleStringsDict => stringsdict::parse(content, options),
+            ResourceFormat::XcStrings => xcstrings::parse(content, options),
+            ResourceFormat::SubRip => subtitles::parse(content, subtitles::Flavor::SubRip),
+            ResourceFormat::WebVtt => subtitles::parse(content, subtitles::Flavor::WebVtt),
+            ResourceFormat::JavaProperties => properties::parse(content),
+            ResourceFormat::Resx => resx::parse(content),
+            ResourceFormat::QtTs => qt_ts::parse(content, options),
+            ResourceFormat::Markdown => markdown::parse(content).map(|units| options.segment(units)),
+            ResourceFormat::Html => html::parse(content).map(|units| options.segment(units)),
+            ResourceFormat::Docx => docx::parse(content).map(|units| options.segment(units)),
+            ResourceFormat::Arb => arb::parse(content),
+            ResourceFormat::Fluent => fluent::parse(content),
+            ResourceFormat::Yaml => yaml::parse(content),
+        }?;
+        // Nested keys are grouped under their parent.
+        let units = match self {
+            ResourceFormat::I18nextJson | ResourceFormat::Yaml => units
+                .into_iter()
+                .map(|unit| ResourceUnit {
+                    section: unit.key.rsplit_once('.').map(|(parent, _)| parent.to_string()),
+                    ..unit
+                })
+                .collect(),
+            _ => units,
+        };
+        Ok(match self.variable_pattern() {
+            Some(_) if self.is_icu() => inline::mark_message_arguments(units),
+            Some(pattern) => inline::mark_variables(units, pattern),
+            None => units,
+        })
+    }
+
+    /// Whether the format is a document, whose segments are keyed by their
+    /// position rather than by a name.
+    pub fn is_document(&self) -> bool {
+        matches!(self, ResourceFormat::Markdown | ResourceFormat::Html | ResourceFormat::Docx)
+    }
+
+    /// Whether the format's strings are ICU MessageFormat messages.
+    pub fn is_icu(&self) -> bool {
+        matches!(self, ResourceFormat::Arb)
+    }
+
+    /// How variables are written in the format's strings.
+    fn variable_pattern(&self) -> Option<&'static str> {
+        match self {
+            ResourceFormat::I18nextJson => Some(inline::I18NEXT),
+            ResourceFormat::AndroidStrings
+            | ResourceFormat::AppleStrings
+            | ResourceFormat::AppleStringsDict
+            | ResourceFormat::XcStrings => Some(inline::PRINTF),
+            ResourceFormat::JavaProperties | ResourceFormat::Arb => Some(inline::BRACES),
+            ResourceFormat::Resx => Some(inline::DOTNET),
+            ResourceFormat::QtTs => Some(inline::QT),
+            ResourceFormat::Fluent => Some(inline::FLUENT),
+            ResourceFormat::Yaml => Some(inline::RUBY),
+            ResourceFormat::SubRip
+            | ResourceFormat::WebVtt
+            | ResourceFormat::Markdown
+            | ResourceFormat::Html
+            | ResourceFormat::Docx => None,
+        }
+    }
+
+    /// Rebuilds `template` (the original source file) with the target text of
+    /// `translations` filled in.
+    pub fn write(
+        &self,
+        template: &[u8],
+        translations: &[Translation],
+        options: &ExportOptions,
+    ) -> Result<Vec<u8>, FormatError> {
+        let targets = TargetLookup::new(translations, options);
+        match self {
+            ResourceFormat::I18nextJson => i18next::write(template, &targets),
+            ResourceFormat::AndroidStrings => android::write(template, &targets),
+            ResourceFormat::AppleStrings => apple_strings::write(template, &targets),
+            ResourceFormat::AppleStringsDict => stringsdict::write(template, &targets),
+            ResourceFormat::XcStrings => xcstrings::write(template, &targets),
+            ResourceFormat::SubRip => subtitles::write(template, &targets, subtitles::Flavor::SubRip),
+            ResourceFormat::WebVtt => subtitles::write(template, &targets, subtitles::Flavor::WebVtt),
+            ResourceFormat::JavaProperties => properties::write(template, &targets),
+            ResourceFormat::Resx => resx::write(template, &targets),
+            ResourceFormat::QtTs => qt_ts::write(template, &targets),
+            ResourceFormat::Markdown => markdown::write(template, &targets),
+            ResourceFormat::Html => html::write(template, &targets),
+            ResourceFormat::Docx => docx::write(template, &targets),
+            ResourceFormat::Arb => arb::write(template, &targets),
+            ResourceFormat::Fluent => fluent::write(template, &targets),
+            ResourceFormat::Yaml => yaml::write(template, &targets),
+        }
+    }
+
+    /// Where the translated file goes when the caller does not pick a path.
+    /// Android resources go to `values-<locale>`, Apple resources to
+    /// `<locale>.lproj`, and string catalogs are updated in place since they
+    /// hold every language. Java bundles, Qt files and ARB files carry the
+    /// locale as an underscore suffix (`Messages_de.properties`, `app_de.ts`,
+    /// `app_de.arb`). Otherwise a directory or file stem named after the
+    /// source locale (`locales/en/app.json`, `en.json`, `messages.en.yaml`) is
+    /// swapped for the target locale, or the locale is added to the file name.
+    pub fn default_export_path(&self, source_path: &str, source_locale: Option<&str>, target_locale: &str) -> PathBuf {
+        let source = Path::new(source_path);
+
+        match self {
+            ResourceFormat::AndroidStrings => return android::export_path(source_path, target_locale),
+            ResourceFormat::XcStrings => return source.to_path_buf(),
+            ResourceFormat::AppleStrings | ResourceFormat::AppleStringsDict => {
+                let lproj = format!("{}.lproj", target_locale);
+                let mut swapped = false;
+                let path: PathBuf = source
+                    .iter()
+                    .map(|part| match part.to_str() {
+                        Some(name) if !swapped && name.ends_with(".lproj") => {
+                            swapped = true;
+                            std::ffi::OsStr::new(lproj.as_str())
+                        }
+                        _ => part,
+                    })
+                    .collect();
+                if swapped {
+                    return path;
+                }
+            }
+            ResourceFormat::JavaProperties | ResourceFormat::QtTs | ResourceFormat::Arb => {
+                let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("export");
+                let base = source_locale
+                    .and_then(|locale| stem.strip_suffix(&format!("_{}", locale.replace('-', "_"))))
+                    .unwrap_or(stem);
+                let name = match source.extension().and_then(|s| s.to_str()) {
+                    Some(extension) => format!("{}_{}.{}", base, target_locale.replace('-', "_"), extension),
+                    None => format!("{}_{}", base, target_locale.replace('-', "_")),
+                };
+                return source.with_file_name(name);
+            }
+            _ => {}
+        }
+
+        if let Some(source_locale) = source_locale {
+            let mut swapped = false;
+            let components: PathBuf = source
+                .iter()
+                .map(|part| {
+                    if !swapped && part == source_locale && Some(part) != source.file_name() {
+                        swapped = true;
+                        std::ffi::OsStr::new(target_locale)
+                    } else {
+                        part
+                    }
+                })
+                .collect();
+            if swapped {
+                return components;
+            }
+        }
+
+        let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("export");
+        let extension = source.extension().and_then(|s| s.to_str());
+        let stem = match source_locale {
+            Some(locale) if stem == locale => target_locale.to_string(),
+            Some(locale) => match stem.strip_suffix(&format!(".{}", locale)) {
+                Some(base) => format!("{}.{}", base, target_locale),
+                None => format!("{}.{}", stem, target_locale),
+            },
+            None => format!("{}.{}", stem, target_locale),
+        };
+        match extension {
+            Some(extension) => source.with_file_name(format!("{}.{}", stem, extension)),
+            None => source.with_file_name(stem),
+        }
+    }
+}
+
+/// One translatable entry read from a resource file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceUnit {
+    pub key: String,
+    /// Disambiguates units that share a key.
+    pub context: Option<String>,
+    /// The part of the file the unit belongs to, such as the heading above a
+    /// paragraph or the parent of a nested key.
+    pub section: Option<String>,
+    /// The comment written next to the string in the file.
+    pub developer_comment: Option<String>,
+    pub max_length: Option<i64>,
+    pub source_text: String,
+    pub target_text: Option<String>,
+    pub notes: Option<String>,
+    pub status: Option<TranslationStatus>,
+    /// The workflow status, for files that record one. It is kept over
+    /// `status`, which only names its category.
+    pub workflow_status: Option<String>,
+    /// Cue timing in milliseconds, for subtitle files.
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    /// Markup behind the numbered tags in `source_text`, for documents, and
+    /// the variables it uses.
+    pub placeholders: Vec<Placeholder>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    pub source_locale: Option<String>,
+    pub target_locale: Option<String>,
+    /// Rules for splitting document paragraphs into sentences. Paragraphs
+    /// are imported whole when there are none.
+    #[serde(skip)]
+    pub segmentation: Option<SegmentationRules>,
+}
+
+impl ImportOptions {
+    fn segment(&self, units: Vec<ResourceUnit>) -> Vec<ResourceUnit> {
+        match &self.segmentation {
+            Some(rules) => placeholders::segment_units(units, rules, self.source_locale.as_deref().unwrap_or_default()),
+            None => units,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportOptions {
+    pub target_locale: Option<String>,
+    /// Write the source text for entries that have no translation yet, so the
+    /// exported file is complete. When false, such entries are left out.
+    #[serde(default = "default_true")]
+    pub fallback_to_source: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Translations by resource key, as the writers need them.
+pub struct TargetLookup<'a> {
+    translations: HashMap<&'a str, &'a Translation>,
+    /// The sentences a document segment was split into, by the segment's key.
+    parts: HashMap<&'a str, Vec<(u32, &'a Translation)>>,
+    target_locale: Option<String>,
+    fallback_to_source: bool,
+}
+
+impl<'a> TargetLookup<'a> {
+    fn new(translations: &'a [Translation], options: &ExportOptions) -> Self {
+        let translations: HashMap<&str, &Translation> = translations
+            .iter()
+            .filter_map(|t| Some((t.resource_key.as_deref()?, t)))
+            .collect();
+
+        let mut parts: HashMap<&str, Vec<(u32, &Translation)>> = HashMap::new();
+        for translation in translations.values() {
+            let Some((base, number)) = translation.resource_key.as_deref().and_then(split_key) else {
+                continue;
+            };
+            parts.entry(base).or_default().push((number, *translation));
+        }
+        for list in parts.values_mut() {
+            list.sort_by_key(|(number, _)| *number);
+        }
+
+        TargetLookup {
+            translations,
+            parts,
+            target_locale: options.target_locale.clone(),
+            fallback_to_source: options.fallback_to_source,
+        }
+    }
+
+    /// The text to write for `key`, or `None` when the entry should be left
+    /// out of the exported file.
+    pub fn resolve<'s>(&self, key: &str, source: &'s str) -> Option<&'s str>
+    where
+        'a: 's,
+    {
+        match self.target(key) {
+            Some(target) => Some(target),
+            None if self.fallback_to_source => Some(source),
+            None => None,
+        }
+    }
+
+    /// The non-empty target text for `key`, without any fallback.
+    pub fn target(&self, key: &str) -> Option<&'a str> {
+        self.translations
+            .get(key)
+            .and_then(|t| t.target_text.as_deref())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// The target text for a document segment, joined from its sentences when
+    /// it was split. Untranslated sentences keep their source text; `None`
+    /// when nothing in the segment is translated.
+    pub fn document_target(&self, key: &str) -> Option<Cow<'a, str>> {
+        if let Some(target) = self.target(key) {
+            return Some(Cow::Borrowed(target));
+        }
+        let parts = self.parts.get(key)?;
+        if parts.iter().all(|(_, t)| t.target_text.as_deref().is_none_or(str::is_empty)) {
+            return None;
+        }
+
+        let mut joined = String::new();
+        for (_, translation) in parts {
+            let text = translation
+                .target_text
+                .as_deref()
+                .filter(|text| !text.is_empty())
+                .unwrap_or(&translation.source_text);
+            if !joined.is_empty() {
+                joined.push_str(segmentation::joiner(&joined, text));
+            }
+            joined.push_str(text);
+        }
+        Some(Cow::Owned(joined))
+    }
+
+    pub fn status(&self, key: &str) -> Option<TranslationStatus> {
+        self.translations.get(key).map(|t| t.status)
+    }
+
+    pub fn target_locale(&self) -> Option<&str> {
+        self.target_locale.as_deref()
+    }
+}
+
+/// The segment key and sentence number of a key written `<key>#<n>`.
+pub(crate) fn split_key(key: &str) -> Option<(&str, u32)> {
+    let (base, number) = key.rsplit_once('#')?;
+    if base.is_empty() || number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((base, number.parse().ok()?))
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Parse(String),
+    Write(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Parse(reason) => write!(f, "Could not parse file: {}", reason),
+            FormatError::Write(reason) => write!(f, "Could not write file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn translation(key: &str, source: &str, target: &str) -> Translation {
+        serde_json::from_value(serde_json::json!({
+            "id": key, "project_id": "p", "resource_key": key, "source_text": source, "target_text": target,
+            "notes": null, "status": "Draft", "workflow_status": "Draft", "locked": false, "lock_reason": null,
+            "locked_by": null, "locked_at": null, "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    /// Parses `content` from English into `target_locale`.
+    pub(super) fn parse(format: ResourceFormat, content: &[u8], target_locale: &str) -> Vec<ResourceUnit> {
+        let options = ImportOptions {
+            source_locale: Some("en".to_string()),
+            target_locale: Some(target_locale.to_string()),
+            segmentation: None,
+        };
+        format.parse(content, &options).unwrap()
+    }
+
+    /// Writes `template` for `target_locale` with the given translations by
+    /// key.
+    pub(super) fn write(
+        format: ResourceFormat,
+        template: &[u8],
+        targets: &[(&str, &str)],
+        target_locale: &str,
+        fallback_to_source: bool,
+    ) -> Vec<u8> {
+        let translations: Vec<Translation> = targets.iter().map(|(key, target)| translation(key, "", target)).collect();
+        let options = ExportOptions {
+            target_locale: Some(target_locale.to_string()),
+            fallback_to_source,
+        };
+        format.write(template, &translations, &options).unwrap()
+    }
+
+    /// Parses `template`, translates every unit and writes it back, then
+    /// checks that parsing the result gives the same keys with the
+    /// translations as their text.
+    pub(super) fn round_trip(format: ResourceFormat, template: &[u8]) {
+        let units = parse(format, template, "de");
+        assert!(!units.is_empty(), "{:?}: nothing parsed", format);
+        let targets: Vec<(String, String)> = units
+            .iter()
+            .map(|unit| (unit.key.clone(), format!("DE {}", unit.source_text)))
+            .collect();
+        let pairs: Vec<(&str, &str)> = targets.iter().map(|(key, target)| (key.as_str(), target.as_str())).collect();
+        let written = write(format, template, &pairs, "de", false);
+
+        let actual: Vec<(String, String)> = parse(format, &written, "de")
+            .into_iter()
+            .map(|unit| (unit.key, unit.target_text.unwrap_or(unit.source_text)))
+            .collect();
+        assert_eq!(actual, targets, "{:?}: {}", format, String::from_utf8_lossy(&written));
+    }
+
+    #[test]
+    fn split_segments_are_joined_on_export() {
+        assert_eq!(split_key("intro#12"), Some(("intro", 12)));
+        assert_eq!(split_key("#1"), None);
+        assert_eq!(split_key("intro#a"), None);
+
+        let translations = [
+            translation("1#1", "Hello there.", ""),
+            translation("1#2", "How are you?", "Wie geht's?"),
+            translation("2#1", "Bye.", ""),
+            translation("2#2", "See you.", ""),
+        ];
+        let options = ExportOptions {
+            target_locale: Some("de".to_string()),
+            fallback_to_source: true,
+        };
+        let lookup = TargetLookup::new(&translations, &options);
+        assert_eq!(lookup.document_target("1").as_deref(), Some("Hello there. Wie geht's?"));
+        assert_eq!(lookup.document_target("2"), None);
+
+        let template = b"Hello there. How are you?\n\nBye. See you.\n";
+        let written = ResourceFormat::Markdown.write(template, &translations, &options).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), "Hello there. Wie geht's?\n\nBye. See you.\n");
+    }
+}
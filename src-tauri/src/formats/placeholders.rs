@@ -0,0 +1,398 @@
+//! Inline markup protection for document formats. Markup inside a segment,
+//! such as links, emphasis or HTML tags, is replaced by numbered tags (`<1>`,
+//! `</1>`, `<2/>`) so translators can move it around without being able to
+//! break it, and put back when the document is written. Segments can be split
+//! into sentences on import; their translations are joined again on export.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::segmentation::{self, SegmentationRules};
+
+use super::{ResourceUnit, TargetLookup};
+
+/// The original markup behind one numbered tag in a segment, or a variable
+/// of the file format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placeholder {
+    pub id: u32,
+    /// The markup `<id>` stands for, or `<id/>` when there is no closing part.
+    /// For variables, the variable as written.
+    pub opening: String,
+    /// The markup `</id>` stands for, for paired markup such as links.
+    pub closing: Option<String>,
+    /// A short name for the editor to show instead of the markup, such as
+    /// `b` or `link`.
+    #[serde(default)]
+    pub display: Option<String>,
+    #[serde(default)]
+    pub kind: PlaceholderKind,
+}
+
+impl Placeholder {
+    /// What the editor shows for the placeholder.
+    pub fn label(&self) -> &str {
+        self.display.as_deref().unwrap_or(&self.opening)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaceholderKind {
+    /// Markup replaced by a numbered tag in the segment text.
+    #[default]
+    Tag,
+    /// A variable such as `%1$s` or `{name}`, left in the text as it is.
+    Variable,
+}
+
+enum Piece {
+    Text(String),
+    Open(usize),
+    Close(usize),
+}
+
+/// Collects the text and markup of one segment in document order.
+#[derive(Default)]
+pub(crate) struct SegmentBuilder {
+    pieces: Vec<Piece>,
+    placeholders: Vec<Placeholder>,
+}
+
+impl SegmentBuilder {
+    pub fn text(&mut self, text: &str) {
+        match self.pieces.last_mut() {
+            Some(Piece::Text(last)) => last.push_str(text),
+            _ => self.pieces.push(Piece::Text(text.to_string())),
+        }
+    }
+
+    /// Adds the opening part of paired markup, or standalone markup when it
+    /// is never closed. Returns the index to pass to `set_opening`/`close`.
+    pub fn open(&mut self, markup: &str, display: &str) -> usize {
+        let index = self.placeholders.len();
+        self.placeholders.push(Placeholder {
+            id: index as u32 + 1,
+            opening: markup.to_string(),
+            closing: None,
+            display: Some(display.to_string()),
+            kind: PlaceholderKind::Tag,
+        });
+        self.pieces.push(Piece::Open(index));
+        index
+    }
+
+    pub fn standalone(&mut self, markup: &str, display: &str) {
+        self.open(markup, display);
+    }
+
+    pub fn set_opening(&mut self, index: usize, markup: &str) {
+        self.placeholders[index].opening = markup.to_string();
+    }
+
+    pub fn close(&mut self, index: usize, markup: &str) {
+        self.placeholders[index].closing = Some(markup.to_string());
+        self.pieces.push(Piece::Close(index));
+    }
+
+    /// Whether the segment has anything to translate besides markup.
+    pub fn has_text(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Text(text) if text.chars().any(char::is_alphanumeric)))
+    }
+
+    /// The segment text with numbered tags, and the markup behind them.
+    pub fn finish(self) -> (String, Vec<Placeholder>) {
+        let mut text = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(part) => text.push_str(part),
+                Piece::Open(index) => {
+                    let placeholder = &self.placeholders[*index];
+                    match placeholder.closing {
+                        Some(_) => text.push_str(&format!("<{}>", placeholder.id)),
+                        None => text.push_str(&format!("<{}/>", placeholder.id)),
+                    }
+                }
+                Piece::Close(index) => text.push_str(&format!("</{}>", self.placeholders[*index].id)),
+            }
+        }
+        (text, self.placeholders)
+    }
+}
+
+/// A piece of segment text with its numbered tags resolved.
+pub(crate) enum Token<'a> {
+    Text(&'a str),
+    Open(&'a Placeholder),
+    Close(&'a Placeholder),
+    Standalone(&'a Placeholder),
+}
+
+/// Splits `text` into text and the placeholders its numbered tags refer to.
+/// Tags that don't match a placeholder are kept as text.
+pub(crate) fn tokens<'a>(text: &'a str, placeholders: &'a [Placeholder]) -> Vec<Token<'a>> {
+    spanned_tokens(text, placeholders).into_iter().map(|(_, token)| token).collect()
+}
+
+/// Like `tokens`, with the byte range of `text` each token covers.
+fn spanned_tokens<'a>(text: &'a str, placeholders: &'a [Placeholder]) -> Vec<(Range<usize>, Token<'a>)> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut position = 0;
+
+    while let Some(offset) = text[position..].find('<') {
+        let start = position + offset;
+        let Some((length, token)) = tag_at(&text[start..], placeholders) else {
+            position = start + 1;
+            continue;
+        };
+        if text_start < start {
+            tokens.push((text_start..start, Token::Text(&text[text_start..start])));
+        }
+        tokens.push((start..start + length, token));
+        position = start + length;
+        text_start = position;
+    }
+    if text_start < text.len() {
+        tokens.push((text_start..text.len(), Token::Text(&text[text_start..])));
+    }
+
+    tokens
+}
+
+/// Replaces the numbered tags in `text` with their markup, passing the text
+/// between them through `escape`. Placeholders the translation left out are
+/// dropped.
+pub fn restore(text: &str, placeholders: &[Placeholder], escape: impl Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len());
+    for token in tokens(text, placeholders) {
+        match token {
+            Token::Text(text) => out.push_str(&escape(text)),
+            Token::Open(placeholder) => out.push_str(&placeholder.opening),
+            Token::Close(placeholder) => out.push_str(placeholder.closing.as_deref().unwrap_or_default()),
+            // Paired markup written as a standalone tag keeps both parts.
+            Token::Standalone(placeholder) => {
+                out.push_str(&placeholder.opening);
+                out.push_str(placeholder.closing.as_deref().unwrap_or_default());
+            }
+        }
+    }
+    out
+}
+
+/// The byte offsets in `text` where the segmentation rules start a new
+/// sentence. Tags are left out while the rules run, so they can't hide a
+/// sentence end; markup that opens at the end of one sentence and closes in
+/// the next one keeps both in one piece.
+pub fn sentence_breaks(text: &str, placeholders: &[Placeholder], rules: &SegmentationRules, language: &str) -> Vec<usize> {
+    let tokens = spanned_tokens(text, placeholders);
+    let mut plain = String::new();
+    // Where each run of plain text starts, in `plain` and in `text`.
+    let mut runs = Vec::new();
+    for (span, token) in &tokens {
+        if let Token::Text(part) = token {
+            runs.push((plain.len(), span.start));
+            plain.push_str(part);
+        }
+    }
+
+    rules
+        .breaks(&plain, language)
+        .into_iter()
+        .filter_map(|offset| {
+            // A break between two runs goes after the tags between them, so
+            // closing markup stays with the sentence it closes.
+            let run = runs.partition_point(|(start, _)| *start <= offset) - 1;
+            let (plain_start, text_start) = runs[run];
+            Some(text_start + offset - plain_start).filter(|&position| depth_at(&tokens, position) == 0)
+        })
+        .collect()
+}
+
+/// Splits `text` at the byte offsets in `positions`. Each part keeps the
+/// placeholders its tags use, and loses the whitespace around it. Fails when
+/// a position is inside a tag or between a pair of tags, or when a part
+/// would be empty.
+pub fn split(text: &str, placeholders: &[Placeholder], positions: &[usize]) -> Result<Vec<(String, Vec<Placeholder>)>, String> {
+    let tokens = spanned_tokens(text, placeholders);
+    let mut bounds = vec![0];
+    for &position in positions {
+        if position <= *bounds.last().unwrap_or(&0) || position >= text.len() || !text.is_char_boundary(position) {
+            return Err(format!("Cannot split at offset {}", position));
+        }
+        if tokens.iter().any(|(span, token)| !matches!(token, Token::Text(_)) && span.start < position && position < span.end) {
+            return Err(format!("Cannot split inside a tag at offset {}", position));
+        }
+        if depth_at(&tokens, position) != 0 {
+            return Err(format!("Cannot split between a pair of tags at offset {}", position));
+        }
+        bounds.push(position);
+    }
+    bounds.push(text.len());
+
+    bounds
+        .windows(2)
+        .map(|pair| {
+            let range = segmentation::trimmed(text, pair[0]..pair[1]).ok_or("Cannot split off an empty segment")?;
+            let used: Vec<Placeholder> = placeholders
+                .iter()
+                .filter(|placeholder| match placeholder.kind {
+                    PlaceholderKind::Tag => tokens.iter().any(|(span, token)| {
+                        range.start <= span.start
+                            && span.end <= range.end
+                            && matches!(token, Token::Open(p) | Token::Close(p) | Token::Standalone(p) if p.id == placeholder.id)
+                    }),
+                    PlaceholderKind::Variable => text[range.clone()].contains(&placeholder.opening),
+                })
+                .cloned()
+                .collect();
+            Ok((text[range].to_string(), used))
+        })
+        .collect()
+}
+
+/// Splits every unit into sentences. A unit that holds more than one gets a
+/// unit per sentence, keyed `<key>#<n>` from 1; `TargetLookup` puts their
+/// translations back together on export.
+pub(crate) fn segment_units(units: Vec<ResourceUnit>, rules: &SegmentationRules, language: &str) -> Vec<ResourceUnit> {
+    let mut segmented = Vec::with_capacity(units.len());
+    for unit in units {
+        let breaks = sentence_breaks(&unit.source_text, &unit.placeholders, rules, language);
+        let parts = match split(&unit.source_text, &unit.placeholders, &breaks) {
+            Ok(parts) if parts.len() > 1 => parts,
+            _ => {
+                segmented.push(unit);
+                continue;
+            }
+        };
+        for (index, (source_text, placeholders)) in parts.into_iter().enumerate() {
+            segmented.push(ResourceUnit {
+                key: format!("{}#{}", unit.key, index + 1),
+                source_text,
+                placeholders,
+                ..unit.clone()
+            });
+        }
+    }
+    segmented
+}
+
+/// How many pairs of tags are open at byte offset `position`.
+fn depth_at(tokens: &[(Range<usize>, Token<'_>)], position: usize) -> i32 {
+    tokens
+        .iter()
+        .take_while(|(span, _)| span.end <= position)
+        .map(|(_, token)| match token {
+            Token::Open(_) => 1,
+            Token::Close(_) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The length of the numbered tag at the start of `text` and what it stands
+/// for.
+fn tag_at<'a>(text: &str, placeholders: &'a [Placeholder]) -> Option<(usize, Token<'a>)> {
+    let end = text.find('>')?;
+    let inner = &text[1..end];
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+    let (standalone, digits) = match inner.strip_suffix('/') {
+        Some(digits) => (true, digits),
+        None => (false, inner),
+    };
+    if closing && standalone || digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let id: u32 = digits.parse().ok()?;
+    let placeholder = placeholders
+        .iter()
+        .find(|p| p.id == id && p.kind == PlaceholderKind::Tag)?;
+
+    let token = match (closing, standalone, &placeholder.closing) {
+        (true, _, Some(_)) => Token::Close(placeholder),
+        (true, _, None) => return None,
+        (false, true, _) | (false, false, None) => Token::Standalone(placeholder),
+        (false, false, Some(_)) => Token::Open(placeholder),
+    };
+    Some((end + 1, token))
+}
+
+/// The element name of an HTML or XML tag, without any namespace prefix.
+pub(crate) fn tag_name(markup: &str) -> Option<&str> {
+    let rest = markup.strip_prefix('<')?;
+    let rest = rest.strip_prefix('/').unwrap_or(rest);
+    let name = rest.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next()?;
+    let name = name.rsplit(':').next()?;
+    (!name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')).then_some(name)
+}
+
+/// A translatable run of a document and the byte range of the template it
+/// came from.
+pub(crate) struct Segment {
+    pub text: String,
+    pub placeholders: Vec<Placeholder>,
+    pub span: Range<usize>,
+    /// The segment is a heading, which starts a new section.
+    pub heading: bool,
+}
+
+/// Document segments have no names of their own, so they are keyed by their
+/// position, starting at 1. Each belongs to the section of the heading
+/// before it, headings included.
+pub(crate) fn units(segments: Vec<Segment>) -> Vec<ResourceUnit> {
+    let mut section = None;
+    segments
+        .into_iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            if segment.heading {
+                section = Some(plain_text(&segment.text, &segment.placeholders));
+            }
+            ResourceUnit {
+                key: (index + 1).to_string(),
+                section: section.clone(),
+                source_text: segment.text,
+                placeholders: segment.placeholders,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// `text` without its numbered tags.
+fn plain_text(text: &str, placeholders: &[Placeholder]) -> String {
+    tokens(text, placeholders)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Writes `template` with every translated segment replaced. Untranslated
+/// segments keep their source text whatever the fallback setting, since a
+/// document with paragraphs missing is of no use to anyone.
+pub(crate) fn splice(
+    template: &str,
+    segments: &[Segment],
+    targets: &TargetLookup,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut copied = 0;
+    for (index, segment) in segments.iter().enumerate() {
+        if let Some(text) = targets.document_target(&(index + 1).to_string()) {
+            out.push_str(&template[copied..segment.span.start]);
+            out.push_str(&restore(&text, &segment.placeholders, &escape));
+            copied = segment.span.end;
+        }
+    }
+    out.push_str(&template[copied..]);
+    out
+}
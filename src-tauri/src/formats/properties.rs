@@ -0,0 +1,278 @@
+//! Java `.properties` resource bundles. Files are ISO-8859-1 with `\uXXXX`
+//! escapes, or UTF-8 as Java 9 allows; values may continue over several
+//! lines with a trailing backslash. The `#` or `!` comment lines right before
+//! an entry become its developer comment.
+
+use std::ops::Range;
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Latin1,
+    Utf8,
+}
+
+struct Entry {
+    key: String,
+    value: String,
+    comment: Option<String>,
+    /// From the first character of the value to the end of the logical line.
+    value_span: Range<usize>,
+    /// The entry's lines including the comment lines right before it.
+    span: Range<usize>,
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let (text, _) = decode(content);
+
+    Ok(scan(&text)
+        .into_iter()
+        .filter(|entry| !entry.value.is_empty())
+        .map(|entry| ResourceUnit {
+            key: entry.key,
+            source_text: entry.value,
+            developer_comment: entry.comment,
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Writes the template with every value replaced by its translation, in the
+/// template's encoding. Entries without one are removed along with their
+/// comment when there is no fallback.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let (text, encoding) = decode(template);
+
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for entry in scan(&text).into_iter().filter(|entry| !entry.value.is_empty()) {
+        match targets.resolve(&entry.key, &entry.value) {
+            Some(value) => {
+                out.push_str(&text[copied..entry.value_span.start]);
+                out.push_str(&escape_value(value, encoding));
+                copied = entry.value_span.end;
+            }
+            None => {
+                out.push_str(&text[copied..entry.span.start]);
+                copied = entry.span.end;
+            }
+        }
+    }
+    out.push_str(&text[copied..]);
+
+    Ok(match encoding {
+        Encoding::Utf8 => out.into_bytes(),
+        // Every character outside Latin-1 was escaped above.
+        Encoding::Latin1 => out.chars().map(|c| c as u8).collect(),
+    })
+}
+
+/// UTF-8 when the file is valid UTF-8, ISO-8859-1 otherwise, which is how
+/// `PropertyResourceBundle` reads it too.
+fn decode(content: &[u8]) -> (String, Encoding) {
+    match std::str::from_utf8(content) {
+        Ok(text) if !text.is_ascii() => (text.to_string(), Encoding::Utf8),
+        Ok(text) => (text.to_string(), Encoding::Latin1),
+        Err(_) => (content.iter().map(|b| *b as char).collect(), Encoding::Latin1),
+    }
+}
+
+fn scan(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut comment: Vec<&str> = Vec::new();
+    let mut comment_start: Option<usize> = None;
+    let mut offset = 0;
+
+    let mut lines = text.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start();
+
+        if trimmed.trim_end().is_empty() {
+            comment.clear();
+            comment_start = None;
+            continue;
+        }
+        if let Some(body) = trimmed.strip_prefix(['#', '!']) {
+            comment_start.get_or_insert(line_start);
+            comment.push(body.trim());
+            continue;
+        }
+
+        // Join continuation lines into one logical line.
+        let mut end = line_start + line.len();
+        let mut logical = line.trim_end_matches(['\n', '\r']).to_string();
+        while ends_with_continuation(&logical) {
+            logical.pop();
+            match lines.next() {
+                Some(next) => {
+                    offset += next.len();
+                    end = offset;
+                    logical.push_str(next.trim_start().trim_end_matches(['\n', '\r']));
+                }
+                None => break,
+            }
+        }
+
+        let indent = line.len() - trimmed.len();
+        let (key, value_offset) = split_key(&logical[indent..]);
+        // A value that only starts on a continuation line is replaced from
+        // the first line's backslash on.
+        let first_line = line.trim_end_matches(['\n', '\r']);
+        let value_start = if logical.len() > first_line.len() && indent + value_offset >= first_line.len() - 1 {
+            line_start + first_line.len() - 1
+        } else {
+            line_start + indent + value_offset
+        };
+        let value_end = end - line_ending(&text[..end]);
+        let notes = comment.iter().filter(|l| !l.is_empty()).copied().collect::<Vec<_>>().join("\n");
+
+        entries.push(Entry {
+            key: unescape(key),
+            value: unescape(&logical[indent + value_offset..]),
+            comment: Some(notes).filter(|n| !n.is_empty()),
+            value_span: value_start.min(value_end)..value_end,
+            span: comment_start.unwrap_or(line_start)..end,
+        });
+        comment.clear();
+        comment_start = None;
+    }
+
+    entries
+}
+
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+fn line_ending(text: &str) -> usize {
+    if text.ends_with("\r\n") {
+        2
+    } else if text.ends_with('\n') {
+        1
+    } else {
+        0
+    }
+}
+
+/// Splits a logical line into its raw key and the offset where the value
+/// starts. The key ends at the first unescaped `=`, `:` or whitespace.
+fn split_key(line: &str) -> (&str, usize) {
+    let mut escaped = false;
+    let mut key_end = line.len();
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || c.is_whitespace() {
+            key_end = index;
+            break;
+        }
+    }
+
+    let rest = &line[key_end..];
+    let mut value = rest.trim_start_matches([' ', '\t', '\x0C']);
+    if let Some(after) = value.strip_prefix(['=', ':']) {
+        value = after.trim_start_matches([' ', '\t', '\x0C']);
+    }
+    (&line[..key_end], line.len() - value.len())
+}
+
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    // High half of a surrogate pair written as two `\u` escapes.
+    let mut high_surrogate: Option<u16> = None;
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            if high_surrogate.take().is_some() {
+                out.push(char::REPLACEMENT_CHARACTER);
+            }
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let Ok(unit) = u16::from_str_radix(&hex, 16) else {
+                    out.push_str("\\u");
+                    out.push_str(&hex);
+                    continue;
+                };
+                match high_surrogate.take() {
+                    None if (0xD800..0xDC00).contains(&unit) => high_surrogate = Some(unit),
+                    Some(high) => {
+                        let pair = char::decode_utf16([high, unit]).next().and_then(Result::ok);
+                        out.push(pair.unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    None => out.push(char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+                }
+            }
+            Some(next) => {
+                if high_surrogate.take().is_some() {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                }
+                out.push(match next {
+                    't' => '\t',
+                    'n' => '\n',
+                    'r' => '\r',
+                    'f' => '\x0C',
+                    other => other,
+                });
+            }
+            None => {}
+        }
+    }
+
+    out
+}
+
+fn escape_value(value: &str, encoding: Encoding) -> String {
+    let mut out = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\x0C' => out.push_str("\\f"),
+            // Leading whitespace would otherwise be taken as part of the
+            // separator.
+            ' ' if index == 0 => out.push_str("\\ "),
+            c if encoding == Encoding::Latin1 && !(' '..='~').contains(&c) => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::JavaProperties;
+
+    const TEMPLATE: &[u8] = b"# Greeting\nhello = Hello {0}\nmulti = First \\\n    second\nunicode=Caf\\u00e9\n";
+
+    #[test]
+    fn comments_continuations_and_escapes() {
+        let units = parse(JavaProperties, TEMPLATE, "de");
+        let entries: Vec<(&str, &str)> = units.iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(entries, [("hello", "Hello {0}"), ("multi", "First second"), ("unicode", "Café")]);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Greeting"));
+        round_trip(JavaProperties, TEMPLATE);
+
+        // Latin-1 files escape what they can't hold.
+        let out = write(JavaProperties, TEMPLATE, &[("unicode", "Кафе")], "ru", true);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("unicode=\\u041A\\u0430\\u0444\\u0435\n"));
+    }
+}
@@ -0,0 +1,491 @@
+//! Qt Linguist `.ts` files. Messages are keyed `context/source`, with the
+//! disambiguation comment appended as `#comment` when there is one, or by
+//! their `id` for `qsTrId` strings. Plural messages get one unit per
+//! `<numerusform>` (`key[0]`, `key[1]`, ...): one per CLDR cardinal category
+//! of the target language when its rules are known, and never fewer than
+//! the file has. `type="unfinished"` maps to Draft and finished translations
+//! to Validated; obsolete and vanished messages are skipped.
+
+use quick_xml::escape::{partial_escape, unescape};
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use super::{FormatError, ImportOptions, ResourceUnit, TargetLookup};
+use crate::database::TranslationStatus;
+use crate::icu;
+
+#[derive(Default)]
+struct Message {
+    id: Option<String>,
+    numerus: bool,
+    source: String,
+    comment: Option<String>,
+    extra_comment: Option<String>,
+    translation_type: Option<String>,
+    forms: Vec<String>,
+}
+
+impl Message {
+    fn key(&self, context: &str) -> String {
+        match (&self.id, &self.comment) {
+            (Some(id), _) => id.clone(),
+            (None, Some(comment)) => format!("{}/{}#{}", context, self.source, comment),
+            (None, None) => format!("{}/{}", context, self.source),
+        }
+    }
+
+    fn is_obsolete(&self) -> bool {
+        matches!(self.translation_type.as_deref(), Some("obsolete" | "vanished"))
+    }
+
+    /// The unit keys of this message, one per plural form. `plural_forms` is
+    /// how many forms the target language has, when known.
+    fn unit_keys(&self, context: &str, plural_forms: Option<usize>) -> Vec<String> {
+        let key = self.key(context);
+        if self.numerus {
+            let count = self.forms.len().max(plural_forms.unwrap_or(1)).max(1);
+            (0..count).map(|index| format!("{}[{}]", key, index)).collect()
+        } else {
+            vec![key]
+        }
+    }
+}
+
+/// Parses a `.ts` file. The plural forms follow the target locale of
+/// `options`, or the file's `language` when none is given.
+pub fn parse(content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+    let content = decode(content)?;
+    let mut reader = Reader::from_str(content);
+    let mut units = Vec::new();
+    let mut context = String::new();
+    let mut message: Option<Message> = None;
+    let mut plural_forms = plural_forms_of(options.target_locale.as_deref());
+
+    loop {
+        match reader.read_event().map_err(parse_error)? {
+            Event::Start(e) => {
+                let end = e.to_end().into_owned();
+                match (e.name().as_ref(), &mut message) {
+                    (b"TS", None) if plural_forms.is_none() => {
+                        plural_forms = plural_forms_of(attribute(&e, "language")?.as_deref());
+                    }
+                    (b"name", None) => context = text(&reader.read_text(end.name()).map_err(parse_error)?)?,
+                    (b"message", None) => {
+                        message = Some(Message {
+                            id: attribute(&e, "id")?,
+                            numerus: attribute(&e, "numerus")?.as_deref() == Some("yes"),
+                            ..Default::default()
+                        });
+                    }
+                    (b"source", Some(message)) => message.source = text(&reader.read_text(end.name()).map_err(parse_error)?)?,
+                    (b"comment", Some(message)) => {
+                        let comment = text(&reader.read_text(end.name()).map_err(parse_error)?)?;
+                        message.comment = Some(comment).filter(|c| !c.is_empty());
+                    }
+                    (b"extracomment", Some(message)) => {
+                        let comment = text(&reader.read_text(end.name()).map_err(parse_error)?)?;
+                        message.extra_comment = Some(comment).filter(|c| !c.is_empty());
+                    }
+                    (b"translation", Some(message)) => {
+                        message.translation_type = attribute(&e, "type")?;
+                        if !message.numerus {
+                            message.forms.push(text(&reader.read_text(end.name()).map_err(parse_error)?)?);
+                        }
+                    }
+                    (b"numerusform", Some(message)) => {
+                        message.forms.push(text(&reader.read_text(end.name()).map_err(parse_error)?)?);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(e) => match (e.name().as_ref(), &mut message) {
+                (b"translation", Some(message)) => message.translation_type = attribute(&e, "type")?,
+                (b"numerusform", Some(message)) => message.forms.push(String::new()),
+                _ => {}
+            },
+            Event::End(e) if e.name().as_ref() == b"message" => {
+                if let Some(message) = message.take().filter(|m| !m.is_obsolete() && !m.source.is_empty()) {
+                    units.extend(units_for(&message, &context, plural_forms));
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"context" => context.clear(),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(units)
+}
+
+fn units_for(message: &Message, context: &str, plural_forms: Option<usize>) -> Vec<ResourceUnit> {
+    let keys = message.unit_keys(context, plural_forms);
+    let count = keys.len();
+
+    keys.into_iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let notes = Some(format!("Plural form {} of {}", index + 1, count)).filter(|_| message.numerus);
+
+            let target_text = message.forms.get(index).filter(|t| !t.is_empty()).cloned();
+            let status = target_text.as_ref().map(|_| match message.translation_type.as_deref() {
+                Some("unfinished") => TranslationStatus::Draft,
+                _ => TranslationStatus::Validated,
+            });
+
+            ResourceUnit {
+                key,
+                source_text: message.source.clone(),
+                target_text,
+                context: message.comment.clone(),
+                developer_comment: message.extra_comment.clone(),
+                notes,
+                status,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Writes the template with every translation filled in. Messages are never
+/// removed; ones without a translation are left empty and unfinished, which
+/// is how Linguist marks untranslated text. The `language` attribute is set
+/// to the target locale, and plural messages get a `<numerusform>` for each
+/// of its plural forms.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let template = decode(template).map_err(to_write)?;
+    let plural_forms = plural_forms_of(targets.target_locale());
+    let mut reader = Reader::from_str(template);
+    let mut writer = Writer::new(Vec::new());
+    let mut context = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Start(ref e) if e.name().as_ref() == b"TS" => {
+                let start = match targets.target_locale() {
+                    Some(locale) => with_attribute(e, "language", Some(&locale.replace('-', "_")))?,
+                    None => e.clone().into_owned(),
+                };
+                writer.write_event(Event::Start(start)).map_err(write_error)?;
+                continue;
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"name" => {
+                let end = e.to_end().into_owned();
+                let raw = reader.read_text(end.name()).map_err(write_error)?;
+                context = text(&raw).map_err(to_write)?;
+                writer.write_event(event.clone()).map_err(write_error)?;
+                writer.write_event(Event::Text(BytesText::from_escaped(raw))).map_err(write_error)?;
+                writer.write_event(Event::End(end)).map_err(write_error)?;
+                continue;
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"message" => {
+                let message = Message {
+                    id: attribute(e, "id").map_err(to_write)?,
+                    numerus: attribute(e, "numerus").map_err(to_write)?.as_deref() == Some("yes"),
+                    ..Default::default()
+                };
+                let start = event.clone().into_owned();
+                write_message(&mut reader, &mut writer, start, message, &context, targets, plural_forms)?;
+                continue;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        writer.write_event(event).map_err(write_error)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+/// Where a piece of translated text goes in a buffered message.
+enum Part {
+    Event(Event<'static>),
+    /// The `<translation>` start tag, whose `type` depends on the outcome.
+    TranslationStart(BytesStart<'static>),
+    /// Text of the plural form with this index (0 for singular messages),
+    /// with the template's raw text for messages that are left alone.
+    Slot(usize, String),
+}
+
+fn write_message(
+    reader: &mut Reader<&[u8]>,
+    writer: &mut Writer<Vec<u8>>,
+    start: Event<'static>,
+    mut message: Message,
+    context: &str,
+    targets: &TargetLookup,
+    plural_forms: Option<usize>,
+) -> Result<(), FormatError> {
+    let mut parts = vec![Part::Event(start)];
+
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Start(ref e) if matches!(e.name().as_ref(), b"source" | b"comment") => {
+                let end = e.to_end().into_owned();
+                let raw = reader.read_text(end.name()).map_err(write_error)?;
+                let value = text(&raw).map_err(to_write)?;
+                if e.name().as_ref() == b"source" {
+                    message.source = value;
+                } else {
+                    message.comment = Some(value).filter(|c| !c.is_empty());
+                }
+                parts.push(Part::Event(event.clone().into_owned()));
+                parts.push(Part::Event(Event::Text(BytesText::from_escaped(raw.into_owned()))));
+                parts.push(Part::Event(Event::End(end)));
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"translation" => {
+                let end = e.to_end().into_owned();
+                message.translation_type = attribute(e, "type").map_err(to_write)?;
+                parts.push(Part::TranslationStart(e.clone().into_owned()));
+                if message.numerus {
+                    read_numerus_forms(reader, &mut parts, &mut message)?;
+                    add_numerus_forms(&mut parts, &mut message, plural_forms);
+                } else {
+                    let raw = reader.read_text(end.name()).map_err(write_error)?;
+                    parts.push(Part::Slot(0, raw.into_owned()));
+                    message.forms.push(String::new());
+                }
+                parts.push(Part::Event(Event::End(end)));
+            }
+            Event::Empty(ref e) if e.name().as_ref() == b"translation" => {
+                let start = e.clone().into_owned();
+                let end = start.to_end().into_owned();
+                message.translation_type = attribute(e, "type").map_err(to_write)?;
+                parts.push(Part::TranslationStart(start));
+                if message.numerus {
+                    add_numerus_forms(&mut parts, &mut message, Some(plural_forms.unwrap_or(1)));
+                } else {
+                    parts.push(Part::Slot(0, String::new()));
+                    message.forms.push(String::new());
+                }
+                parts.push(Part::Event(Event::End(end)));
+            }
+            Event::End(ref e) if e.name().as_ref() == b"message" => {
+                parts.push(Part::Event(event.into_owned()));
+                break;
+            }
+            Event::Eof => return Err(FormatError::Write("unexpected end of file inside <message>".to_string())),
+            other => parts.push(Part::Event(other.into_owned())),
+        }
+    }
+
+    let keys = message.unit_keys(context, plural_forms);
+    let texts: Vec<Option<&str>> = if message.is_obsolete() {
+        Vec::new()
+    } else {
+        keys.iter().map(|key| targets.resolve(key, &message.source)).collect()
+    };
+    // Finished only when every form has a confirmed translation.
+    let finished = !keys.is_empty()
+        && keys.iter().all(|key| {
+            targets.target(key).is_some() && !matches!(targets.status(key), Some(TranslationStatus::Draft) | None)
+        });
+
+    for part in parts {
+        let event = match part {
+            Part::Event(event) => event,
+            Part::TranslationStart(start) if message.is_obsolete() => Event::Start(start),
+            Part::TranslationStart(start) => {
+                let translation_type = if finished { None } else { Some("unfinished") };
+                Event::Start(with_attribute(&start, "type", translation_type)?)
+            }
+            Part::Slot(_, raw) if message.is_obsolete() => Event::Text(BytesText::from_escaped(raw)),
+            Part::Slot(index, _) => {
+                let Some(text) = texts.get(index).copied().flatten() else {
+                    continue;
+                };
+                let escaped = partial_escape(text);
+                Event::Text(BytesText::from_escaped(escaped.into_owned()))
+            }
+        };
+        writer.write_event(event).map_err(write_error)?;
+    }
+
+    Ok(())
+}
+
+fn read_numerus_forms(reader: &mut Reader<&[u8]>, parts: &mut Vec<Part>, message: &mut Message) -> Result<(), FormatError> {
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Start(ref e) if e.name().as_ref() == b"numerusform" => {
+                let end = e.to_end().into_owned();
+                let raw = reader.read_text(end.name()).map_err(write_error)?.into_owned();
+                parts.push(Part::Event(event.clone().into_owned()));
+                parts.push(Part::Slot(message.forms.len(), raw));
+                parts.push(Part::Event(Event::End(end)));
+                message.forms.push(String::new());
+            }
+            Event::Empty(ref e) if e.name().as_ref() == b"numerusform" => {
+                let start = e.clone().into_owned();
+                let end = start.to_end().into_owned();
+                parts.push(Part::Event(Event::Start(start)));
+                parts.push(Part::Slot(message.forms.len(), String::new()));
+                parts.push(Part::Event(Event::End(end)));
+                message.forms.push(String::new());
+            }
+            Event::End(ref e) if e.name().as_ref() == b"translation" => return Ok(()),
+            Event::Eof => return Err(FormatError::Write("unexpected end of file inside <translation>".to_string())),
+            other => parts.push(Part::Event(other.into_owned())),
+        }
+    }
+}
+
+/// Adds empty `<numerusform>` slots until the message has `plural_forms`.
+/// They go before the whitespace that closes the translation, indented like
+/// the last form already there.
+fn add_numerus_forms(parts: &mut Vec<Part>, message: &mut Message, plural_forms: Option<usize>) {
+    let wanted = plural_forms.unwrap_or(0);
+    if message.forms.len() >= wanted {
+        return;
+    }
+    let closing = match parts.last() {
+        Some(Part::Event(Event::Text(text))) if is_whitespace(text) => parts.pop(),
+        _ => None,
+    };
+    let start = parts.iter().rposition(|part| matches!(part, Part::TranslationStart(_))).unwrap_or(0);
+    let indent = parts[start..].iter().rev().find_map(|part| match part {
+        Part::Event(Event::Text(text)) if is_whitespace(text) => Some(text.clone()),
+        _ => None,
+    });
+
+    while message.forms.len() < wanted {
+        if let Some(indent) = &indent {
+            parts.push(Part::Event(Event::Text(indent.clone())));
+        }
+        parts.push(Part::Event(Event::Start(BytesStart::new("numerusform"))));
+        parts.push(Part::Slot(message.forms.len(), String::new()));
+        parts.push(Part::Event(Event::End(BytesStart::new("numerusform").to_end().into_owned())));
+        message.forms.push(String::new());
+    }
+    parts.extend(closing);
+}
+
+fn is_whitespace(text: &BytesText) -> bool {
+    text.iter().all(u8::is_ascii_whitespace)
+}
+
+/// How many plural forms `locale` has, when its rules are known.
+fn plural_forms_of(locale: Option<&str>) -> Option<usize> {
+    locale.and_then(icu::plural_categories).map(|categories| categories.cardinal.len())
+}
+
+/// A copy of `start` with attribute `name` set to `value`, or removed when
+/// `value` is `None`.
+fn with_attribute(start: &BytesStart, name: &str, value: Option<&str>) -> Result<BytesStart<'static>, FormatError> {
+    let tag = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut updated = BytesStart::new(tag);
+    let mut replaced = false;
+    for attr in start.attributes() {
+        let attr = attr.map_err(write_error)?;
+        if attr.key.as_ref() == name.as_bytes() {
+            if let Some(value) = value {
+                updated.push_attribute((name, value));
+            }
+            replaced = true;
+        } else {
+            updated.push_attribute(attr);
+        }
+    }
+    if let (false, Some(value)) = (replaced, value) {
+        updated.push_attribute((name, value));
+    }
+    Ok(updated.into_owned())
+}
+
+fn text(raw: &str) -> Result<String, FormatError> {
+    unescape(raw).map(|t| t.into_owned()).map_err(parse_error)
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, FormatError> {
+    match e.try_get_attribute(name).map_err(parse_error)? {
+        Some(attr) => Ok(Some(attr.unescape_value().map_err(parse_error)?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Parse(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Write(e.to_string())
+}
+
+fn to_write(e: FormatError) -> FormatError {
+    match e {
+        FormatError::Parse(reason) => FormatError::Write(reason),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::QtTs;
+
+    const TEMPLATE: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE TS>
+<TS version="2.1" language="en_US">
+<context>
+    <name>MainWindow</name>
+    <message>
+        <location filename="main.cpp" line="3"/>
+        <source>Open &amp; save</source>
+        <comment>toolbar</comment>
+        <translation type="unfinished"></translation>
+    </message>
+    <message numerus="yes">
+        <source>%n file(s)</source>
+        <translation type="unfinished">
+            <numerusform></numerusform>
+            <numerusform></numerusform>
+        </translation>
+    </message>
+    <message>
+        <source>Old</source>
+        <translation type="vanished">Alt</translation>
+    </message>
+</context>
+</TS>
+"#;
+
+    #[test]
+    fn messages() {
+        let units = parse(QtTs, TEMPLATE, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["MainWindow/Open & save#toolbar", "MainWindow/%n file(s)[0]", "MainWindow/%n file(s)[1]"]);
+        round_trip(QtTs, TEMPLATE);
+
+        let out = String::from_utf8(write(QtTs, TEMPLATE, &[("MainWindow/Open & save#toolbar", "Öffnen")], "de", false)).unwrap();
+        assert!(out.contains("language=\"de\""));
+        assert!(out.contains("<translation type=\"unfinished\">Öffnen</translation>"));
+        assert!(out.contains("<translation type=\"vanished\">Alt</translation>"));
+    }
+
+    #[test]
+    fn target_plural_forms() {
+        // Russian has one, few, many and other.
+        let units = parse(QtTs, TEMPLATE, "ru");
+        let forms: Vec<&str> = units[1..].iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(forms, ["MainWindow/%n file(s)[0]", "MainWindow/%n file(s)[1]", "MainWindow/%n file(s)[2]", "MainWindow/%n file(s)[3]"]);
+        assert_eq!(units[3].notes.as_deref(), Some("Plural form 3 of 4"));
+
+        let targets = [("MainWindow/%n file(s)[0]", "%n файл"), ("MainWindow/%n file(s)[2]", "%n файлов")];
+        let out = String::from_utf8(write(QtTs, TEMPLATE, &targets, "ru", false)).unwrap();
+        assert!(out.contains(
+            "        <translation type=\"unfinished\">
+            <numerusform>%n файл</numerusform>
+            <numerusform></numerusform>
+            <numerusform>%n файлов</numerusform>
+            <numerusform></numerusform>
+        </translation>"
+        ));
+    }
+}
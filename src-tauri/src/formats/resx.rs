@@ -0,0 +1,236 @@
+//! .NET `.resx` resources. Every string `<data>` element becomes one unit keyed
+//! by its `name`, with its `<comment>` as developer comment. Typed resources
+//! such as images and WinForms designer metadata (`>>button1.Name`) are not
+//! translatable and are copied unchanged.
+
+use quick_xml::escape::{partial_escape, unescape};
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let content = decode(content)?;
+    let mut reader = Reader::from_str(content);
+    let mut units = Vec::new();
+    // Name, value and comment of the `<data>` element being read.
+    let mut current: Option<(String, Option<String>, Option<String>)> = None;
+
+    loop {
+        match reader.read_event().map_err(parse_error)? {
+            Event::Start(e) => {
+                let end = e.to_end().into_owned();
+                match (e.name().as_ref(), &mut current) {
+                    (b"data", _) => match string_name(&e)? {
+                        Some(name) => current = Some((name, None, None)),
+                        None => {
+                            reader.read_to_end(end.name()).map_err(parse_error)?;
+                        }
+                    },
+                    (b"value", Some((_, value, _))) => {
+                        *value = Some(text(&reader.read_text(end.name()).map_err(parse_error)?)?);
+                    }
+                    (b"comment", Some((_, _, comment))) => {
+                        *comment = Some(text(&reader.read_text(end.name()).map_err(parse_error)?)?);
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"data" => {
+                if let Some((key, Some(value), comment)) = current.take() {
+                    if !value.is_empty() {
+                        units.push(ResourceUnit {
+                            key,
+                            source_text: value,
+                            developer_comment: comment.filter(|c| !c.trim().is_empty()),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(units)
+}
+
+/// Writes the template with every string value replaced by its translation.
+/// Strings without one are removed when there is no fallback; everything
+/// else, including the schema and resheaders, is copied as is.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let template = decode(template).map_err(to_write)?;
+    let mut reader = Reader::from_str(template);
+    let mut writer = Writer::new(Vec::new());
+    // Whitespace before an element is held back so it can be dropped along
+    // with the element.
+    let mut pending: Option<Event<'static>> = None;
+
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Text(ref text) if text.iter().all(u8::is_ascii_whitespace) => {
+                pending = Some(event.into_owned());
+                continue;
+            }
+            Event::Start(ref e) if e.name().as_ref() == b"data" => {
+                if let Some(name) = string_name(e).map_err(to_write)? {
+                    let (events, slot, source) = read_data(&mut reader, event.clone().into_owned())?;
+                    let Some(text) = targets.resolve(&name, &source) else {
+                        pending = None;
+                        continue;
+                    };
+                    if let Some(whitespace) = pending.take() {
+                        writer.write_event(whitespace).map_err(write_error)?;
+                    }
+                    for (index, event) in events.into_iter().enumerate() {
+                        if index == slot {
+                            let escaped = partial_escape(text);
+                            writer
+                                .write_event(Event::Text(BytesText::from_escaped(escaped.as_ref())))
+                                .map_err(write_error)?;
+                        }
+                        writer.write_event(event).map_err(write_error)?;
+                    }
+                    continue;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        if let Some(whitespace) = pending.take() {
+            writer.write_event(whitespace).map_err(write_error)?;
+        }
+        writer.write_event(event).map_err(write_error)?;
+    }
+
+    if let Some(whitespace) = pending.take() {
+        writer.write_event(whitespace).map_err(write_error)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// Reads a `<data>` element up to its end tag. Returns its events with the
+/// text of `<value>` taken out, the index where the new text goes, and the
+/// original text.
+fn read_data(reader: &mut Reader<&[u8]>, start: Event<'static>) -> Result<(Vec<Event<'static>>, usize, String), FormatError> {
+    let mut events = vec![start];
+    let mut slot = None;
+    let mut source = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(write_error)?;
+        match event {
+            Event::Start(ref e) if e.name().as_ref() == b"value" => {
+                let end = e.to_end().into_owned();
+                source = text(&reader.read_text(end.name()).map_err(write_error)?).map_err(to_write)?;
+                events.push(event.into_owned());
+                slot = Some(events.len());
+                events.push(Event::End(end));
+            }
+            Event::Empty(ref e) if e.name().as_ref() == b"value" => {
+                let start: BytesStart<'static> = e.clone().into_owned();
+                let end = start.to_end().into_owned();
+                events.push(Event::Start(start));
+                slot = Some(events.len());
+                events.push(Event::End(end));
+            }
+            Event::End(ref e) if e.name().as_ref() == b"data" => {
+                // A string without a value gets one before the end tag.
+                if slot.is_none() {
+                    events.push(Event::Start(BytesStart::new("value")));
+                    slot = Some(events.len());
+                    events.push(Event::End(BytesStart::new("value").to_end().into_owned()));
+                }
+                events.push(event.into_owned());
+                break;
+            }
+            Event::Eof => return Err(FormatError::Write("unexpected end of file inside <data>".to_string())),
+            other => events.push(other.into_owned()),
+        }
+    }
+
+    Ok((events, slot.unwrap_or(0), source))
+}
+
+/// The name of a `<data>` element that holds a translatable string.
+fn string_name(e: &BytesStart) -> Result<Option<String>, FormatError> {
+    let Some(name) = attribute(e, "name")? else {
+        return Ok(None);
+    };
+    let typed = attribute(e, "type")?.is_some_and(|t| !t.starts_with("System.String"));
+    if typed || attribute(e, "mimetype")?.is_some() || name.starts_with(">>") {
+        return Ok(None);
+    }
+    Ok(Some(name))
+}
+
+fn text(raw: &str) -> Result<String, FormatError> {
+    unescape(raw).map(|t| t.into_owned()).map_err(parse_error)
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, FormatError> {
+    match e.try_get_attribute(name).map_err(parse_error)? {
+        Some(attr) => Ok(Some(attr.unescape_value().map_err(parse_error)?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Parse(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> FormatError {
+    FormatError::Write(e.to_string())
+}
+
+fn to_write(e: FormatError) -> FormatError {
+    match e {
+        FormatError::Parse(reason) => FormatError::Write(reason),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Resx;
+
+    const TEMPLATE: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<root>
+  <resheader name="resmimetype">
+    <value>text/microsoft-resx</value>
+  </resheader>
+  <data name="Hello" xml:space="preserve">
+    <value>Hello &amp; welcome</value>
+    <comment>Shown on start</comment>
+  </data>
+  <data name="Icon" type="System.Drawing.Bitmap, System.Drawing" mimetype="application/x-microsoft.net.object.bytearray.base64">
+    <value>AAAA</value>
+  </data>
+  <data name="&gt;&gt;button1.Name" xml:space="preserve">
+    <value>button1</value>
+  </data>
+</root>
+"#;
+
+    #[test]
+    fn strings_only() {
+        let units = parse(Resx, TEMPLATE, "de");
+        assert_eq!(units.len(), 1);
+        assert_eq!((units[0].key.as_str(), units[0].source_text.as_str()), ("Hello", "Hello & welcome"));
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Shown on start"));
+        round_trip(Resx, TEMPLATE);
+
+        let out = String::from_utf8(write(Resx, TEMPLATE, &[("Hello", "Hallo & willkommen")], "de", false)).unwrap();
+        assert!(out.contains("<value>Hallo &amp; willkommen</value>"));
+        assert!(out.contains("<value>AAAA</value>") && out.contains("<value>button1</value>"));
+    }
+}
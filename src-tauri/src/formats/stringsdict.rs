@@ -0,0 +1,200 @@
+//! Apple `.stringsdict` plural rules. Each entry has a format string such as
+//! `%#@items@` (keyed `name/NSStringLocalizedFormatKey`) and one dictionary
+//! per variable whose plural categories are keyed `name/variable/category`.
+//! Variables get a unit for each CLDR category of the target language, even
+//! the ones the template has no form for; those start from its `other` form.
+
+use plist::{Dictionary, Value};
+use std::io::Cursor;
+
+use super::{FormatError, ImportOptions, ResourceUnit, TargetLookup};
+use crate::icu;
+
+const FORMAT_KEY: &str = "NSStringLocalizedFormatKey";
+
+const PLURAL_CATEGORIES: [&str; 6] = ["zero", "one", "two", "few", "many", "other"];
+
+pub fn parse(content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+    let root = read(content).map_err(FormatError::Parse)?;
+    let target_categories = plural_categories(options.target_locale.as_deref());
+
+    let mut units = Vec::new();
+    for (name, entry) in &root {
+        let Some(entry) = entry.as_dictionary() else {
+            continue;
+        };
+
+        if let Some(format) = entry.get(FORMAT_KEY).and_then(Value::as_string) {
+            units.push(ResourceUnit {
+                key: format!("{}/{}", name, FORMAT_KEY),
+                source_text: format.to_string(),
+                notes: Some("Format string; keep the %#@variable@ references".to_string()),
+                ..Default::default()
+            });
+        }
+
+        for (variable, rules) in variables(entry) {
+            let other = rules.get("other").and_then(Value::as_string);
+            for category in PLURAL_CATEGORIES {
+                let text = match rules.get(category).and_then(Value::as_string) {
+                    Some(text) => Some(text),
+                    None if target_categories.iter().any(|c| c == category) => other,
+                    None => None,
+                };
+                if let Some(text) = text {
+                    units.push(ResourceUnit {
+                        key: format!("{}/{}/{}", name, variable, category),
+                        source_text: text.to_string(),
+                        notes: Some(format!("Plural form: {}", category)),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(units)
+}
+
+/// Writes the template with every format string and plural form replaced by
+/// its translation. Plural forms without one are dropped when there is no
+/// fallback; `other` is always kept since the system requires it. Categories
+/// of the target language the template lacks are added from their
+/// translations, falling back to the template's `other` form.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let mut root = read(template).map_err(FormatError::Write)?;
+    let target_categories = plural_categories(targets.target_locale());
+
+    for (name, entry) in root.iter_mut() {
+        let Some(entry) = entry.as_dictionary_mut() else {
+            continue;
+        };
+
+        if let Some(Value::String(format)) = entry.get_mut(FORMAT_KEY) {
+            let key = format!("{}/{}", name, FORMAT_KEY);
+            if let Some(text) = targets.resolve(&key, format).map(str::to_string) {
+                *format = text;
+            }
+        }
+
+        for (variable, rules) in entry.iter_mut() {
+            let Some(rules) = rules.as_dictionary_mut().filter(|rules| is_plural_rule(rules)) else {
+                continue;
+            };
+            if let Some(other) = rules.get("other").and_then(Value::as_string).map(str::to_string) {
+                for category in &target_categories {
+                    if !rules.contains_key(category) {
+                        rules.insert(category.clone(), Value::String(other.clone()));
+                    }
+                }
+            }
+            for category in PLURAL_CATEGORIES {
+                let Some(Value::String(text)) = rules.get_mut(category) else {
+                    continue;
+                };
+                let key = format!("{}/{}/{}", name, variable, category);
+                match targets.resolve(&key, text).map(str::to_string) {
+                    Some(translated) => *text = translated,
+                    None if category != "other" => {
+                        rules.remove(category);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    Value::Dictionary(root)
+        .to_writer_xml(&mut out)
+        .map_err(|e| FormatError::Write(e.to_string()))?;
+    out.push(b'\n');
+    Ok(out)
+}
+
+fn read(content: &[u8]) -> Result<Dictionary, String> {
+    Value::from_reader(Cursor::new(content))
+        .map_err(|e| e.to_string())?
+        .into_dictionary()
+        .ok_or_else(|| "expected a dictionary at the top level".to_string())
+}
+
+fn variables(entry: &Dictionary) -> impl Iterator<Item = (&String, &Dictionary)> {
+    entry
+        .iter()
+        .filter_map(|(name, value)| Some((name, value.as_dictionary()?)))
+        .filter(|(_, rules)| is_plural_rule(rules))
+}
+
+/// The CLDR cardinal categories of `locale`; none when its rules aren't
+/// known.
+fn plural_categories(locale: Option<&str>) -> Vec<String> {
+    locale
+        .and_then(icu::plural_categories)
+        .map(|categories| categories.cardinal)
+        .unwrap_or_default()
+}
+
+fn is_plural_rule(rules: &Dictionary) -> bool {
+    rules.get("NSStringFormatSpecTypeKey").and_then(Value::as_string) == Some("NSStringPluralRuleType")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::AppleStringsDict;
+
+    const TEMPLATE: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>items</key>
+	<dict>
+		<key>NSStringLocalizedFormatKey</key>
+		<string>%#@count@</string>
+		<key>count</key>
+		<dict>
+			<key>NSStringFormatSpecTypeKey</key>
+			<string>NSStringPluralRuleType</string>
+			<key>NSStringFormatValueTypeKey</key>
+			<string>d</string>
+			<key>one</key>
+			<string>%d item</string>
+			<key>other</key>
+			<string>%d items</string>
+		</dict>
+	</dict>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn format_keys_and_plural_forms() {
+        let units = parse(AppleStringsDict, TEMPLATE, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["items/NSStringLocalizedFormatKey", "items/count/one", "items/count/other"]);
+        round_trip(AppleStringsDict, TEMPLATE);
+    }
+
+    #[test]
+    fn target_plural_categories() {
+        let units = parse(AppleStringsDict, TEMPLATE, "ru");
+        let forms: Vec<(&str, &str)> = units[1..].iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(
+            forms,
+            [("items/count/one", "%d item"), ("items/count/few", "%d items"), ("items/count/many", "%d items"), ("items/count/other", "%d items")]
+        );
+
+        let targets = [("items/count/few", "%d предмета"), ("items/count/many", "%d предметов")];
+        let out = write(AppleStringsDict, TEMPLATE, &targets, "ru", false);
+        // Read back for Japanese, which only has `other`, so no forms are
+        // added. `one` had no translation; `other` is required, so it keeps
+        // the template's text.
+        let written = parse(AppleStringsDict, &out, "ja");
+        let forms: Vec<(&str, &str)> = written[1..].iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(
+            forms,
+            [("items/count/few", "%d предмета"), ("items/count/many", "%d предметов"), ("items/count/other", "%d items")]
+        );
+    }
+}
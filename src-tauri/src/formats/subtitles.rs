@@ -0,0 +1,304 @@
+//! SubRip (`.srt`) and WebVTT (`.vtt`) subtitles. Each cue becomes one unit
+//! keyed by its position in the file (`1`, `2`, ...) with its start and end
+//! time attached. WebVTT `NOTE` blocks become the notes of the cue that
+//! follows. The writer only replaces cue text, so timings and cue settings
+//! come straight from the template.
+
+use std::ops::Range;
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    SubRip,
+    WebVtt,
+}
+
+struct Cue {
+    key: String,
+    start_ms: i64,
+    end_ms: i64,
+    text: String,
+    note: Option<String>,
+    /// The SubRip sequence number line, renumbered on export.
+    index_span: Option<Range<usize>>,
+    text_span: Range<usize>,
+    /// The whole block including the blank lines after it.
+    block_span: Range<usize>,
+}
+
+pub fn parse(content: &[u8], flavor: Flavor) -> Result<Vec<ResourceUnit>, FormatError> {
+    let text = decode(content).map_err(FormatError::Parse)?;
+    let cues = scan(&text.body, flavor).map_err(FormatError::Parse)?;
+
+    Ok(cues
+        .into_iter()
+        .filter(|cue| !cue.text.is_empty())
+        .map(|cue| ResourceUnit {
+            key: cue.key,
+            source_text: cue.text,
+            notes: cue.note,
+            start_ms: Some(cue.start_ms),
+            end_ms: Some(cue.end_ms),
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Writes the template with every cue's text replaced by its translation.
+/// Cues without one are removed when there is no fallback, and SubRip
+/// sequence numbers are rewritten so they stay consecutive.
+pub fn write(template: &[u8], targets: &TargetLookup, flavor: Flavor) -> Result<Vec<u8>, FormatError> {
+    let text = decode(template).map_err(FormatError::Write)?;
+    let body = &text.body;
+    let cues = scan(body, flavor).map_err(FormatError::Write)?;
+
+    let mut out = String::with_capacity(body.len());
+    let mut copied = 0;
+    let mut sequence = 0;
+    for cue in &cues {
+        let translated = if cue.text.is_empty() {
+            Some(String::new())
+        } else {
+            targets.resolve(&cue.key, &cue.text).map(cue_text)
+        };
+        let Some(translated) = translated else {
+            out.push_str(&body[copied..cue.block_span.start]);
+            copied = cue.block_span.end;
+            continue;
+        };
+
+        sequence += 1;
+        if let Some(index_span) = &cue.index_span {
+            out.push_str(&body[copied..index_span.start]);
+            out.push_str(&sequence.to_string());
+            copied = index_span.end;
+        }
+        if !cue.text.is_empty() {
+            out.push_str(&body[copied..cue.text_span.start]);
+            out.push_str(&translated);
+            copied = cue.text_span.end;
+        }
+    }
+    out.push_str(&body[copied..]);
+
+    let mut bytes = Vec::with_capacity(out.len() + 3);
+    if text.bom {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    if text.crlf {
+        bytes.extend_from_slice(out.replace('\n', "\r\n").as_bytes());
+    } else {
+        bytes.extend_from_slice(out.as_bytes());
+    }
+    Ok(bytes)
+}
+
+struct Decoded {
+    body: String,
+    bom: bool,
+    crlf: bool,
+}
+
+fn decode(content: &[u8]) -> Result<Decoded, String> {
+    let (bom, content) = match content.strip_prefix(b"\xEF\xBB\xBF") {
+        Some(rest) => (true, rest),
+        None => (false, content),
+    };
+    let text = std::str::from_utf8(content).map_err(|e| e.to_string())?;
+    let crlf = text.contains("\r\n");
+
+    Ok(Decoded {
+        body: text.replace("\r\n", "\n").replace('\r', "\n"),
+        bom,
+        crlf,
+    })
+}
+
+/// Cue text as it goes into the file: a blank line would end the cue early,
+/// so empty lines are dropped.
+fn cue_text(text: &str) -> String {
+    text.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn scan(text: &str, flavor: Flavor) -> Result<Vec<Cue>, String> {
+    let mut cues = Vec::new();
+    let mut note: Option<String> = None;
+
+    for (position, block) in blocks(text).into_iter().enumerate() {
+        let first = block.lines[0].1;
+
+        if flavor == Flavor::WebVtt {
+            if position == 0 {
+                if !first.starts_with("WEBVTT") {
+                    return Err("missing WEBVTT header".to_string());
+                }
+                continue;
+            }
+            if first == "NOTE" || first.starts_with("NOTE ") || first.starts_with("NOTE\t") {
+                let mut lines = vec![first["NOTE".len()..].trim()];
+                lines.extend(block.lines[1..].iter().map(|(_, line)| line.trim()));
+                let text = lines.into_iter().filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n");
+                note = Some(text).filter(|t| !t.is_empty());
+                continue;
+            }
+            if first.starts_with("STYLE") || first.starts_with("REGION") {
+                continue;
+            }
+        }
+
+        let timing = block
+            .lines
+            .iter()
+            .take(2)
+            .position(|(_, line)| line.contains("-->"))
+            .ok_or_else(|| format!("expected a cue timing on line {}", block.line_number))?;
+        let (start_ms, end_ms) = parse_timing(block.lines[timing].1)
+            .ok_or_else(|| format!("invalid cue timing on line {}", block.line_number + timing))?;
+
+        let index_span = match (flavor, timing) {
+            (Flavor::SubRip, 1) if first.trim().parse::<u64>().is_ok() => {
+                let start = block.lines[0].0;
+                Some(start..start + first.len())
+            }
+            _ => None,
+        };
+
+        let text_lines = &block.lines[timing + 1..];
+        let text_span = match (text_lines.first(), text_lines.last()) {
+            (Some(first), Some(last)) => first.0..last.0 + last.1.len(),
+            _ => {
+                let (offset, line) = block.lines[timing];
+                offset + line.len()..offset + line.len()
+            }
+        };
+
+        cues.push(Cue {
+            key: (cues.len() + 1).to_string(),
+            start_ms,
+            end_ms,
+            text: text[text_span.clone()].to_string(),
+            note: note.take(),
+            index_span,
+            text_span,
+            block_span: block.span,
+        });
+    }
+
+    Ok(cues)
+}
+
+struct Block<'a> {
+    /// Line number of the first line, for error messages.
+    line_number: usize,
+    lines: Vec<(usize, &'a str)>,
+    span: Range<usize>,
+}
+
+/// Splits the file into blank-line separated blocks. Each block's span runs
+/// up to the next block so removing it leaves no extra blank lines behind.
+fn blocks(text: &str) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut offset = 0;
+    let mut in_block = false;
+
+    for (number, line) in text.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            in_block = false;
+        } else {
+            if !in_block {
+                if let Some(previous) = blocks.last_mut() {
+                    previous.span.end = offset;
+                }
+                blocks.push(Block {
+                    line_number: number + 1,
+                    lines: Vec::new(),
+                    span: offset..text.len(),
+                });
+                in_block = true;
+            }
+            if let Some(block) = blocks.last_mut() {
+                block.lines.push((offset, line));
+            }
+        }
+        offset += line.len() + 1;
+    }
+
+    blocks
+}
+
+fn parse_timing(line: &str) -> Option<(i64, i64)> {
+    let (start, rest) = line.split_once("-->")?;
+    // WebVTT cue settings may follow the end time.
+    let end = rest.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+/// Parses `HH:MM:SS,mmm` (SubRip) or `[HH:]MM:SS.mmm` (WebVTT) into
+/// milliseconds.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let (clock, millis) = value.split_once([',', '.'])?;
+    let millis: i64 = millis.parse().ok()?;
+
+    let parts = clock
+        .split(':')
+        .map(|part| part.parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+        [minutes, seconds] => (0, *minutes, *seconds),
+        _ => return None,
+    };
+
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::{SubRip, WebVtt};
+
+    const SRT: &[u8] = b"1\r\n00:00:01,000 --> 00:00:02,000\r\nHello <i>there</i>\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,500\r\nSecond\r\nline\r\n\r\n3\r\n00:00:05,000 --> 00:00:06,000\r\nThird\r\n";
+
+    #[test]
+    fn subrip_cues() {
+        let units = parse(SubRip, SRT, "de");
+        let cues: Vec<(&str, Option<i64>, Option<i64>, &str)> = units
+            .iter()
+            .map(|u| (u.key.as_str(), u.start_ms, u.end_ms, u.source_text.as_str()))
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                ("1", Some(1000), Some(2000), "Hello <i>there</i>"),
+                ("2", Some(3000), Some(4500), "Second\nline"),
+                ("3", Some(5000), Some(6000), "Third"),
+            ]
+        );
+        round_trip(SubRip, SRT);
+
+        // Cues without a translation are left out and the rest renumbered.
+        let out = write(SubRip, SRT, &[("1", "Hallo <i>du</i>"), ("3", "Dritte\nZeile")], "de", false);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1\r\n00:00:01,000 --> 00:00:02,000\r\nHallo <i>du</i>\r\n\r\n2\r\n00:00:05,000 --> 00:00:06,000\r\nDritte\r\nZeile\r\n"
+        );
+    }
+
+    #[test]
+    fn webvtt_notes_and_settings() {
+        let vtt = b"WEBVTT\n\nNOTE Speaker is whispering\n\nintro\n00:01.000 --> 00:02.000 align:start\nHi\n\n01:00:00.000 --> 01:00:01.000\nBye\n";
+        let units = parse(WebVtt, vtt, "de");
+        assert_eq!(units[0].notes.as_deref(), Some("Speaker is whispering"));
+        assert_eq!((units[1].start_ms, units[1].end_ms), (Some(3_600_000), Some(3_601_000)));
+        round_trip(WebVtt, vtt);
+
+        let out = String::from_utf8(write(WebVtt, vtt, &[("1", "Hallo")], "de", true)).unwrap();
+        assert!(out.contains("intro\n00:01.000 --> 00:02.000 align:start\nHallo\n"));
+        assert!(out.ends_with("01:00:00.000 --> 01:00:01.000\nBye\n"));
+    }
+}
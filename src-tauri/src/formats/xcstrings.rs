@@ -0,0 +1,452 @@
+//! Xcode string catalogs (`.xcstrings`), the JSON file that holds every
+//! language of a target. Strings are keyed by their catalog key; plural,
+//! device and substitution variations extend it with the path to the variant
+//! (`items/plural/one`, `title/device/mac`). Plural variations get a case
+//! for each CLDR category of the target language, even the ones the source
+//! language has no case for; those start from its `other` case. The writer
+//! fills in the project's target language and leaves every other language
+//! untouched.
+
+use serde_json::ser::{Formatter, PrettyFormatter};
+use serde_json::{Map, Value};
+use std::io;
+
+use super::{FormatError, ImportOptions, ResourceUnit, TargetLookup};
+use crate::database::TranslationStatus;
+use crate::icu;
+
+pub fn parse(content: &[u8], options: &ImportOptions) -> Result<Vec<ResourceUnit>, FormatError> {
+    let catalog = read(content).map_err(FormatError::Parse)?;
+    let source_language = source_language(&catalog).map_err(FormatError::Parse)?;
+    let categories = plural_categories(options.target_locale.as_deref());
+
+    let mut units = Vec::new();
+    for (key, entry) in strings(&catalog) {
+        if !should_translate(entry) {
+            continue;
+        }
+        let comment = entry.get("comment").and_then(Value::as_str).map(str::to_string);
+        let localizations = entry.get("localizations");
+        let target = options
+            .target_locale
+            .as_deref()
+            .and_then(|locale| localizations?.get(locale));
+
+        let mut leaves = Vec::new();
+        match localizations.and_then(|l| l.get(source_language)) {
+            Some(source) => {
+                let mut source = source.clone();
+                add_plural_categories(&mut source, &categories);
+                collect(&source, key, &mut leaves);
+            }
+            // Strings extracted from code use the key itself as source text
+            // until someone edits it in the catalog.
+            None => leaves.push((key.clone(), key.clone())),
+        }
+
+        for (path, source_text) in leaves {
+            let (target_text, status) = match target.and_then(|node| find(node, &key_suffix(key, &path))) {
+                Some((value, state)) => (Some(value), Some(status_from_state(state))),
+                None => (None, None),
+            };
+            units.push(ResourceUnit {
+                notes: describe(key, &path),
+                developer_comment: comment.clone().filter(|c| !c.is_empty()),
+                key: path,
+                source_text,
+                target_text,
+                status,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(units)
+}
+
+/// Writes the catalog with the target language's localizations replaced by
+/// the project's translations, in the layout Xcode itself uses.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let target_locale = targets
+        .target_locale()
+        .ok_or_else(|| FormatError::Write("a target locale is required for string catalogs".to_string()))?
+        .to_string();
+    let mut catalog = read(template).map_err(FormatError::Write)?;
+    let source_language = source_language(&catalog).map_err(FormatError::Write)?.to_string();
+    let categories = plural_categories(Some(&target_locale));
+
+    if let Some(Value::Object(strings)) = catalog.get_mut("strings") {
+        for (key, entry) in strings.iter_mut() {
+            let Value::Object(entry) = entry else {
+                continue;
+            };
+            if !should_translate(entry) {
+                continue;
+            }
+
+            let mut source = entry
+                .get("localizations")
+                .and_then(|l| l.get(&source_language))
+                .cloned()
+                .unwrap_or_else(|| string_unit(key, "translated"));
+            add_plural_categories(&mut source, &categories);
+            let translated = translate(&source, key, targets);
+
+            let localizations = entry
+                .entry("localizations")
+                .or_insert_with(|| Value::Object(Map::new()));
+            let Value::Object(localizations) = localizations else {
+                continue;
+            };
+            match translated {
+                Some(node) => {
+                    localizations.insert(target_locale.clone(), node);
+                }
+                None => {
+                    localizations.remove(&target_locale);
+                }
+            }
+            // Xcode keeps languages in alphabetical order.
+            let mut sorted: Vec<(String, Value)> = std::mem::take(localizations).into_iter().collect();
+            sorted.sort_by(|a, b| a.0.cmp(&b.0));
+            localizations.extend(sorted);
+            if localizations.is_empty() {
+                entry.remove("localizations");
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, XcodeFormatter::default());
+    serde::Serialize::serialize(&Value::Object(catalog), &mut serializer).map_err(|e| FormatError::Write(e.to_string()))?;
+    Ok(out)
+}
+
+fn read(content: &[u8]) -> Result<Map<String, Value>, String> {
+    match serde_json::from_slice(content).map_err(|e| e.to_string())? {
+        Value::Object(catalog) => Ok(catalog),
+        _ => Err("expected a JSON object at the top level".to_string()),
+    }
+}
+
+fn source_language(catalog: &Map<String, Value>) -> Result<&str, String> {
+    catalog
+        .get("sourceLanguage")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "catalog has no sourceLanguage".to_string())
+}
+
+fn strings(catalog: &Map<String, Value>) -> impl Iterator<Item = (&String, &Map<String, Value>)> {
+    catalog
+        .get("strings")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, entry)| Some((key, entry.as_object()?)))
+}
+
+fn should_translate(entry: &Map<String, Value>) -> bool {
+    entry.get("shouldTranslate").and_then(Value::as_bool) != Some(false)
+}
+
+/// Collects `(key path, text)` for every string unit under a localization
+/// node, following variations and substitutions.
+fn collect(node: &Value, path: &str, leaves: &mut Vec<(String, String)>) {
+    if let Some(value) = node.pointer("/stringUnit/value").and_then(Value::as_str) {
+        leaves.push((path.to_string(), value.to_string()));
+    }
+    if let Some(variations) = node.get("variations").and_then(Value::as_object) {
+        for (kind, cases) in variations {
+            for (case, child) in cases.as_object().into_iter().flatten() {
+                collect(child, &format!("{}/{}/{}", path, kind, case), leaves);
+            }
+        }
+    }
+    if let Some(substitutions) = node.get("substitutions").and_then(Value::as_object) {
+        for (name, child) in substitutions {
+            collect(child, &format!("{}/substitutions/{}", path, name), leaves);
+        }
+    }
+}
+
+/// The CLDR cardinal categories of `locale`; none when its rules aren't
+/// known.
+fn plural_categories(locale: Option<&str>) -> Vec<String> {
+    locale
+        .and_then(icu::plural_categories)
+        .map(|categories| categories.cardinal)
+        .unwrap_or_default()
+}
+
+/// Gives every plural variation under `node` a case for each of
+/// `categories`, copied from its `other` case. Cases are kept in
+/// alphabetical order, as Xcode writes them.
+fn add_plural_categories(node: &mut Value, categories: &[String]) {
+    if let Some(Value::Object(variations)) = node.get_mut("variations") {
+        if let Some(Value::Object(cases)) = variations.get_mut("plural") {
+            if let Some(other) = cases.get("other").cloned() {
+                let mut added = false;
+                for category in categories {
+                    if !cases.contains_key(category) {
+                        cases.insert(category.clone(), other.clone());
+                        added = true;
+                    }
+                }
+                if added {
+                    let mut sorted: Vec<(String, Value)> = std::mem::take(cases).into_iter().collect();
+                    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+                    cases.extend(sorted);
+                }
+            }
+        }
+        for cases in variations.values_mut() {
+            for child in cases.as_object_mut().into_iter().flat_map(|cases| cases.values_mut()) {
+                add_plural_categories(child, categories);
+            }
+        }
+    }
+    if let Some(Value::Object(substitutions)) = node.get_mut("substitutions") {
+        for child in substitutions.values_mut() {
+            add_plural_categories(child, categories);
+        }
+    }
+}
+
+/// The part of a key path below the catalog key, split into segments.
+fn key_suffix(key: &str, path: &str) -> Vec<String> {
+    path[key.len()..]
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Follows a key path suffix into a localization node and returns the value
+/// and state of the string unit found there.
+fn find<'v>(node: &'v Value, suffix: &[String]) -> Option<(String, &'v str)> {
+    let mut node = node;
+    let mut segments = suffix.iter();
+    while let Some(segment) = segments.next() {
+        let group = if segment == "substitutions" { "substitutions" } else { "variations" };
+        let container = node.get(group)?;
+        node = if group == "substitutions" {
+            container.get(segments.next()?)?
+        } else {
+            container.get(segment)?.get(segments.next()?)?
+        };
+    }
+    let unit = node.get("stringUnit")?;
+    let value = unit.get("value")?.as_str()?.to_string();
+    let state = unit.get("state").and_then(Value::as_str).unwrap_or("translated");
+    Some((value, state))
+}
+
+/// Builds the target localization from the source one, with every string
+/// unit replaced and other fields (argument numbers, format specifiers) kept
+/// in place. Returns `None` when no string unit is left to write.
+fn translate(source: &Value, path: &str, targets: &TargetLookup) -> Option<Value> {
+    let mut node = Map::new();
+    let mut has_text = false;
+
+    for (field, value) in source.as_object()? {
+        match field.as_str() {
+            "stringUnit" => {
+                let Some(source_text) = value.get("value").and_then(Value::as_str) else {
+                    continue;
+                };
+                let Some(text) = targets.resolve(path, source_text) else {
+                    continue;
+                };
+                let state = match (targets.target(path), targets.status(path)) {
+                    (None, _) => "new",
+                    (Some(_), Some(TranslationStatus::Draft) | None) => "needs_review",
+                    (Some(_), Some(_)) => "translated",
+                };
+                node.insert(field.clone(), serde_json::json!({ "state": state, "value": text }));
+                has_text = true;
+            }
+            "substitutions" => {
+                let translated: Map<String, Value> = value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(name, child)| {
+                        let child_path = format!("{}/substitutions/{}", path, name);
+                        Some((name.clone(), translate(child, &child_path, targets)?))
+                    })
+                    .collect();
+                if !translated.is_empty() {
+                    node.insert(field.clone(), Value::Object(translated));
+                    has_text = true;
+                }
+            }
+            "variations" => {
+                let mut translated = Map::new();
+                for (kind, cases) in value.as_object().into_iter().flatten() {
+                    let cases: Map<String, Value> = cases
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|(case, child)| {
+                            let child_path = format!("{}/{}/{}", path, kind, case);
+                            Some((case.clone(), translate(child, &child_path, targets)?))
+                        })
+                        .collect();
+                    if !cases.is_empty() {
+                        translated.insert(kind.clone(), Value::Object(cases));
+                    }
+                }
+                if !translated.is_empty() {
+                    node.insert(field.clone(), Value::Object(translated));
+                    has_text = true;
+                }
+            }
+            _ => {
+                node.insert(field.clone(), value.clone());
+            }
+        }
+    }
+
+    has_text.then_some(Value::Object(node))
+}
+
+fn string_unit(value: &str, state: &str) -> Value {
+    serde_json::json!({ "stringUnit": { "state": state, "value": value } })
+}
+
+/// Catalog states that mean the translation still needs work come in as
+/// drafts; only `translated` counts as done.
+fn status_from_state(state: &str) -> TranslationStatus {
+    match state {
+        "translated" => TranslationStatus::Validated,
+        _ => TranslationStatus::Draft,
+    }
+}
+
+fn describe(key: &str, path: &str) -> Option<String> {
+    let mut notes = Vec::new();
+
+    let suffix = key_suffix(key, path);
+    if let Some(position) = suffix.iter().position(|segment| segment == "plural") {
+        if let Some(category) = suffix.get(position + 1) {
+            notes.push(format!("Plural form: {}", category));
+        }
+    }
+    if let Some(position) = suffix.iter().position(|segment| segment == "device") {
+        if let Some(device) = suffix.get(position + 1) {
+            notes.push(format!("Device: {}", device));
+        }
+    }
+
+    if notes.is_empty() {
+        None
+    } else {
+        Some(notes.join("\n"))
+    }
+}
+
+/// Pretty printing as Xcode writes catalogs: two-space indentation and a
+/// space on both sides of each colon, so saving from GAIA doesn't rewrite
+/// every line of the file.
+#[derive(Default)]
+struct XcodeFormatter {
+    pretty: PrettyFormatter<'static>,
+}
+
+impl Formatter for XcodeFormatter {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.pretty.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        self.pretty.begin_object_key(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b" : ")
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.pretty.end_object_value(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::XcStrings;
+    use serde_json::{json, Value};
+
+    const TEMPLATE: &[u8] = br#"{
+  "sourceLanguage" : "en",
+  "strings" : {
+    "Hello" : { "comment" : "Greeting" },
+    "items" : {
+      "localizations" : {
+        "en" : { "variations" : { "plural" : {
+          "one" : { "stringUnit" : { "state" : "translated", "value" : "%lld item" } },
+          "other" : { "stringUnit" : { "state" : "translated", "value" : "%lld items" } }
+        } } }
+      }
+    },
+    "id" : { "shouldTranslate" : false }
+  },
+  "version" : "1.0"
+}"#;
+
+    #[test]
+    fn keys_and_variations() {
+        let units = parse(XcStrings, TEMPLATE, "de");
+        let entries: Vec<(&str, &str)> = units.iter().map(|u| (u.key.as_str(), u.source_text.as_str())).collect();
+        assert_eq!(entries, [("Hello", "Hello"), ("items/plural/one", "%lld item"), ("items/plural/other", "%lld items")]);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Greeting"));
+        round_trip(XcStrings, TEMPLATE);
+    }
+
+    #[test]
+    fn target_plural_categories() {
+        let units = parse(XcStrings, TEMPLATE, "pl");
+        let keys: Vec<&str> = units[1..].iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["items/plural/few", "items/plural/many", "items/plural/one", "items/plural/other"]);
+        assert_eq!(units[2].source_text, "%lld items");
+
+        let targets = [
+            ("items/plural/one", "%lld plik"),
+            ("items/plural/few", "%lld pliki"),
+            ("items/plural/many", "%lld plików"),
+            ("items/plural/other", "%lld pliku"),
+        ];
+        let out: Value = serde_json::from_slice(&write(XcStrings, TEMPLATE, &targets, "pl", false)).unwrap();
+        let unit = |value: &str| json!({ "stringUnit": { "state": "needs_review", "value": value } });
+        assert_eq!(
+            out.pointer("/strings/items/localizations/pl/variations/plural").unwrap(),
+            &json!({
+                "few": unit("%lld pliki"),
+                "many": unit("%lld plików"),
+                "one": unit("%lld plik"),
+                "other": unit("%lld pliku"),
+            })
+        );
+        assert!(out.pointer("/strings/items/localizations/en/variations/plural/few").is_none());
+    }
+}
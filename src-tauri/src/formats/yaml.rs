@@ -0,0 +1,442 @@
+//! YAML locale files, Rails style (`en: { greeting: … }`) or Symfony style (no
+//! locale root). Every string leaf becomes one unit keyed by its dotted path
+//! without the locale root (`users.title`, `day_names.0`); the `#` comment
+//! lines right above a key become its developer comment. Aliases and merge keys
+//! repeat other entries and are not translatable. The writer only replaces
+//! scalar values in the template, so comments, anchors and layout survive.
+
+use std::ops::Range;
+
+use yaml_rust2::parser::{Event, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+use super::{FormatError, ResourceUnit, TargetLookup};
+
+const KEY_SEPARATOR: &str = ".";
+
+struct Leaf {
+    key: String,
+    value: String,
+    style: TScalarStyle,
+    /// Inside a `[…]` or `{…}` collection.
+    flow: bool,
+    /// The scalar as written, including quotes.
+    span: Range<usize>,
+    /// The lines holding the entry, when it is a mapping value.
+    entry_lines: Option<Range<usize>>,
+    comment: Option<String>,
+}
+
+struct Document {
+    /// The locale key at the top of Rails-style files.
+    root: Option<Range<usize>>,
+    leaves: Vec<Leaf>,
+}
+
+enum Container {
+    Mapping { path: String, key: Option<(String, usize)>, flow: bool },
+    Sequence { path: String, index: usize, flow: bool },
+}
+
+impl Container {
+    fn flow(&self) -> bool {
+        match self {
+            Container::Mapping { flow, .. } | Container::Sequence { flow, .. } => *flow,
+        }
+    }
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<ResourceUnit>, FormatError> {
+    let text = decode(content)?;
+
+    Ok(scan(text)?
+        .leaves
+        .into_iter()
+        .map(|leaf| ResourceUnit {
+            key: leaf.key,
+            source_text: leaf.value,
+            developer_comment: leaf.comment,
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Writes the template with every string replaced by its translation and the
+/// locale root renamed to the target locale. Mapping entries without a
+/// translation are removed when there is no fallback; sequence items keep
+/// their source text so the other items don't move.
+pub fn write(template: &[u8], targets: &TargetLookup) -> Result<Vec<u8>, FormatError> {
+    let text = decode(template).map_err(|e| FormatError::Write(e.to_string()))?;
+    let document = scan(text).map_err(|e| match e {
+        FormatError::Parse(reason) => FormatError::Write(reason),
+        other => other,
+    })?;
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    if let (Some(root), Some(locale)) = (&document.root, targets.target_locale()) {
+        edits.push((root.clone(), locale.to_string()));
+    }
+    for leaf in &document.leaves {
+        match (targets.resolve(&leaf.key, &leaf.value), &leaf.entry_lines) {
+            (Some(value), _) => {
+                let indent = column(text, leaf.span.start);
+                edits.push((leaf.span.clone(), scalar(value, leaf.style, leaf.flow, indent)));
+            }
+            (None, Some(lines)) => edits.push((lines.clone(), String::new())),
+            (None, None) => {}
+        }
+    }
+    edits.sort_by_key(|(range, _)| range.start);
+
+    let mut out = String::with_capacity(text.len());
+    if template.starts_with(b"\xEF\xBB\xBF") {
+        out.push('\u{FEFF}');
+    }
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        if range.start < copied {
+            continue;
+        }
+        out.push_str(&text[copied..range.start]);
+        out.push_str(&replacement);
+        copied = range.end;
+    }
+    out.push_str(&text[copied..]);
+
+    Ok(out.into_bytes())
+}
+
+fn scan(text: &str) -> Result<Document, FormatError> {
+    // Markers count characters; spans need byte offsets.
+    let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).collect();
+    let offset = |marker: &Marker| offsets.get(marker.index()).copied().unwrap_or(text.len());
+
+    let mut parser = Parser::new_from_str(text);
+    let mut stack: Vec<Container> = Vec::new();
+    let mut leaves = Vec::new();
+    let mut top_level_keys: Vec<(String, Range<usize>, bool)> = Vec::new();
+
+    loop {
+        let (event, marker) = parser.next_token().map_err(|e| FormatError::Parse(e.to_string()))?;
+        let start = offset(&marker);
+
+        // Mapping keys only name the value that follows.
+        if let (Event::Scalar(name, ..), Some(Container::Mapping { key: key @ None, .. })) = (&event, stack.last_mut()) {
+            *key = Some((name.clone(), start));
+            if stack.len() == 1 {
+                top_level_keys.push((name.clone(), start..start + name.len(), false));
+            }
+            continue;
+        }
+
+        let flow = stack.last().is_some_and(Container::flow);
+        let (path, key_start) = match stack.last_mut() {
+            Some(Container::Mapping { path, key, .. }) => match key {
+                Some((name, key_start)) => (join_key(path, name), Some(*key_start)),
+                None => (path.clone(), None),
+            },
+            Some(Container::Sequence { path, index, .. }) => (join_key(path, &index.to_string()), None),
+            None => (String::new(), None),
+        };
+
+        match event {
+            Event::StreamEnd => break,
+            Event::MappingStart(..) => {
+                if stack.len() == 1 {
+                    if let Some(last) = top_level_keys.last_mut() {
+                        last.2 = true;
+                    }
+                }
+                let flow = text[start..].starts_with('{');
+                stack.push(Container::Mapping { path, key: None, flow });
+                continue;
+            }
+            Event::SequenceStart(..) => {
+                let flow = text[start..].starts_with('[');
+                stack.push(Container::Sequence { path, index: 0, flow });
+                continue;
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                stack.pop();
+            }
+            Event::Scalar(value, style, _, tag) => {
+                let is_string = match &tag {
+                    Some(tag) => tag.suffix == "str",
+                    None => style != TScalarStyle::Plain || is_plain_string(&value),
+                };
+                if is_string && !value.trim().is_empty() && !stack.is_empty() {
+                    let end = scalar_end(text, start, style, flow, &value);
+                    let value = match style {
+                        TScalarStyle::Literal | TScalarStyle::Folded => value.trim_end_matches('\n').to_string(),
+                        _ => value,
+                    };
+                    leaves.push(Leaf {
+                        key: path,
+                        value,
+                        style,
+                        flow,
+                        span: start..end,
+                        entry_lines: key_start
+                            .filter(|_| !flow)
+                            .map(|key_start| line_start(text, key_start)..line_end(text, end)),
+                        comment: key_start.and_then(|key_start| comment_above(text, key_start)),
+                    });
+                }
+            }
+            // Aliases repeat an anchored value.
+            _ => {}
+        }
+
+        // A complete value moves its container on to the next entry.
+        match stack.last_mut() {
+            Some(Container::Mapping { key, .. }) => *key = None,
+            Some(Container::Sequence { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+
+    // A single top-level key holding a mapping and named like a locale is
+    // the Rails locale root.
+    let root = match top_level_keys.as_slice() {
+        [(name, span, true)] if is_locale(name) => Some(span.clone()),
+        _ => None,
+    };
+    if root.is_some() {
+        for leaf in &mut leaves {
+            leaf.key = leaf
+                .key
+                .split_once(KEY_SEPARATOR)
+                .map(|(_, rest)| rest.to_string())
+                .unwrap_or_default();
+        }
+    }
+
+    Ok(Document { root, leaves })
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", prefix, KEY_SEPARATOR, name)
+    }
+}
+
+/// Two-letter language codes with optional subtags (`en`, `pt-BR`,
+/// `zh_Hant`), or three-letter ones with subtags. A bare three-letter key is
+/// more likely a section name like `app` than a locale.
+fn is_locale(name: &str) -> bool {
+    let mut subtags = name.split(['-', '_']);
+    let language = subtags.next().unwrap_or_default();
+    let rest: Vec<&str> = subtags.collect();
+    let language_ok = language.chars().all(|c| c.is_ascii_alphabetic())
+        && (language.len() == 2 || (language.len() == 3 && !rest.is_empty()));
+    language_ok
+        && rest
+            .iter()
+            .all(|subtag| (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Plain scalars that YAML reads as numbers, booleans or null are data, not
+/// text.
+fn is_plain_string(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    !matches!(
+        lower.as_str(),
+        "" | "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n" | ".inf" | "-.inf" | ".nan"
+    ) && value.parse::<f64>().is_err()
+        && !(lower.starts_with("0x") && i64::from_str_radix(&lower[2..], 16).is_ok())
+}
+
+/// The end of the scalar starting at `start`.
+fn scalar_end(text: &str, start: usize, style: TScalarStyle, flow: bool, value: &str) -> usize {
+    let rest = &text[start..];
+    match style {
+        TScalarStyle::SingleQuoted => {
+            let mut chars = rest.char_indices().skip(1).peekable();
+            while let Some((index, c)) = chars.next() {
+                if c == '\'' {
+                    if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                        chars.next();
+                    } else {
+                        return start + index + 1;
+                    }
+                }
+            }
+            text.len()
+        }
+        TScalarStyle::DoubleQuoted => {
+            let mut chars = rest.char_indices().skip(1);
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => return start + index + 1,
+                    _ => {}
+                }
+            }
+            text.len()
+        }
+        TScalarStyle::Literal | TScalarStyle::Folded => {
+            // The marker is on the first content line; the block runs while
+            // lines are blank or indented at least as much.
+            let indent = column(text, start);
+            let mut position = line_end(text, start);
+            let mut end = start + rest.lines().next().unwrap_or_default().len();
+            for line in text[position..].split_inclusive('\n') {
+                position += line.len();
+                let content = line.trim_end_matches(['\n', '\r']);
+                if content.trim().is_empty() {
+                    continue;
+                }
+                if content.len() - content.trim_start_matches(' ').len() < indent {
+                    break;
+                }
+                end = position - (line.len() - content.len());
+            }
+            end
+        }
+        _ if flow => {
+            let end = rest.find([',', ']', '}', '\n']).unwrap_or(rest.len());
+            start + plain_line(&rest[..end]).len()
+        }
+        _ => {
+            // Plain scalars end before a comment, or continue on more lines
+            // until the folded text is complete.
+            let mut end = start + plain_line(rest).len();
+            let mut folded = plain_line(rest).to_string();
+            let mut position = line_end(text, start);
+            while folded.len() < value.len() && position < text.len() {
+                let line = text[position..].split_inclusive('\n').next().unwrap_or_default();
+                let content = line.trim_end_matches(['\n', '\r']).trim();
+                if !content.is_empty() {
+                    folded.push(' ');
+                    folded.push_str(content);
+                    end = position + line.trim_end().len();
+                }
+                position += line.len();
+            }
+            end
+        }
+    }
+}
+
+/// The plain scalar text on the first line of `rest`.
+fn plain_line(rest: &str) -> &str {
+    let line = rest.lines().next().unwrap_or_default();
+    let line = match line.find(" #") {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+    line.trim_end()
+}
+
+/// The `#` lines directly above the line holding `position`.
+fn comment_above(text: &str, position: usize) -> Option<String> {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text[..line_start(text, position)].lines().rev() {
+        match line.trim_start().strip_prefix('#') {
+            Some(comment) => lines.push(comment.trim()),
+            None => break,
+        }
+    }
+    lines.reverse();
+    let comment = lines.join("\n");
+    Some(comment).filter(|c| !c.trim().is_empty())
+}
+
+fn line_start(text: &str, position: usize) -> usize {
+    text[..position].rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+/// The end of the line holding `position`, after its line break.
+fn line_end(text: &str, position: usize) -> usize {
+    text[position..].find('\n').map_or(text.len(), |newline| position + newline + 1)
+}
+
+fn column(text: &str, position: usize) -> usize {
+    text[line_start(text, position)..position].chars().count()
+}
+
+/// `value` written as a scalar in the style of the one it replaces, quoted
+/// when a plain scalar would change its meaning.
+fn scalar(value: &str, style: TScalarStyle, flow: bool, indent: usize) -> String {
+    match style {
+        TScalarStyle::Literal | TScalarStyle::Folded => {
+            // Folded blocks join single line breaks, so each line of the
+            // translation becomes a paragraph of its own.
+            let separator = if style == TScalarStyle::Folded { "\n\n" } else { "\n" };
+            let padding = " ".repeat(indent);
+            value
+                .lines()
+                .map(|line| if line.is_empty() { String::new() } else { format!("{}{}", padding, line) })
+                .collect::<Vec<_>>()
+                .join(separator)
+                .trim_start()
+                .to_string()
+        }
+        TScalarStyle::Plain if is_plain_safe(value) && !(flow && value.contains([',', '[', ']', '{', '}'])) => {
+            value.to_string()
+        }
+        TScalarStyle::SingleQuoted if !value.contains(['\n', '\t']) => format!("'{}'", value.replace('\'', "''")),
+        _ => double_quoted(value),
+    }
+}
+
+fn is_plain_safe(value: &str) -> bool {
+    is_plain_string(value)
+        && value.trim() == value
+        && !value.contains(['\n', '\t', '\r'])
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.ends_with(':')
+        && !value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+}
+
+fn double_quoted(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn decode(content: &[u8]) -> Result<&str, FormatError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    std::str::from_utf8(content).map_err(|e| FormatError::Parse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{parse, round_trip, write};
+    use super::super::ResourceFormat::Yaml;
+
+    const TEMPLATE: &[u8] = b"en:\n  # Window title\n  title: Hello\n  menu:\n    open: \"Open %{name}\"\n    close: Close\n  days: [Mon, Tue]\n  base: &base\n    ok: OK\n  copy: *base\n";
+
+    #[test]
+    fn rails_keys_comments_and_aliases() {
+        let units = parse(Yaml, TEMPLATE, "de");
+        let keys: Vec<&str> = units.iter().map(|unit| unit.key.as_str()).collect();
+        assert_eq!(keys, ["title", "menu.open", "menu.close", "days.0", "days.1", "base.ok"]);
+        assert_eq!(units[0].developer_comment.as_deref(), Some("Window title"));
+        round_trip(Yaml, TEMPLATE);
+
+        let targets = [("title", "Hallo: Welt"), ("menu.close", "Schließen"), ("days.1", "Di")];
+        let out = write(Yaml, TEMPLATE, &targets, "de", true);
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "de:\n  # Window title\n  title: \"Hallo: Welt\"\n  menu:\n    open: \"Open %{name}\"\n    close: Schließen\n  days: [Mon, Di]\n  base: &base\n    ok: OK\n  copy: *base\n"
+        );
+    }
+}
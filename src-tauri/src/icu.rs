@@ -0,0 +1,468 @@
+//! ICU MessageFormat (`{count, plural, one {# file} other {# files}}`).
+//! Messages are parsed into elements the editor can show, and translations
+//! are checked against their source: the target has to parse, keep the same
+//! arguments and cover every plural category the target language needs.
+//! Apostrophes quote syntax characters the way ICU does by default: `''` is
+//! an apostrophe, and `'{'` a literal brace.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Argument types that format a value without options.
+const FORMATS: &[&str] = &["number", "date", "time", "spellout", "ordinal", "duration"];
+
+/// Languages whose cardinal rules only distinguish `one` from `other`.
+const ONE_OTHER: &[&str] = &[
+    "af", "am", "an", "as", "asa", "ast", "az", "bal", "bem", "bez", "bg", "bho", "bn", "brx", "ce", "cgg", "chr",
+    "ckb", "da", "de", "doi", "dv", "ee", "el", "en", "eo", "et", "eu", "fa", "ff", "fi", "fil", "fo", "fur", "fy",
+    "gl", "gsw", "gu", "guw", "ha", "haw", "hi", "hu", "hy", "ia", "io", "is", "ka", "kab", "kaj", "kcg", "kk", "kkj",
+    "kl", "kn", "ks", "ksb", "ku", "ky", "lb", "lg", "lij", "ln", "mas", "mg", "mgo", "mk", "ml", "mn", "mr", "nah",
+    "nb", "nd", "ne", "nl", "nn", "nnh", "no", "nr", "nso", "ny", "nyn", "om", "or", "os", "pa", "pap", "ps", "rm",
+    "rof", "rwk", "saq", "sc", "sd", "sdh", "seh", "si", "sn", "so", "sq", "ss", "ssy", "st", "sv", "sw", "syr", "ta",
+    "te", "teo", "ti", "tig", "tk", "tl", "tn", "tr", "ts", "ug", "ur", "uz", "ve", "vo", "vun", "wa", "wae", "xh",
+    "xog", "yi", "zu",
+];
+
+/// Languages without any plural distinction.
+const OTHER_ONLY: &[&str] = &[
+    "bm", "bo", "dz", "hnj", "id", "ig", "ii", "in", "ja", "jbo", "jv", "jw", "kde", "kea", "km", "ko", "lkt", "lo",
+    "ms", "my", "nqo", "osa", "root", "sah", "ses", "sg", "su", "th", "to", "tpi", "vi", "wo", "yo", "yue", "zh",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MessageElement {
+    Literal(String),
+    /// `{name}`
+    Argument(String),
+    /// `{name, number}`, `{name, date, short}` and the other simple types.
+    Formatted { name: String, format: String, style: Option<String> },
+    /// `{name, plural, ...}`, or `{name, selectordinal, ...}` when `ordinal`.
+    Plural { name: String, ordinal: bool, offset: i64, options: Vec<MessageOption> },
+    Select { name: String, options: Vec<MessageOption> },
+    /// `#` in a plural option, standing for the number.
+    Pound,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageOption {
+    /// A keyword such as `one` or `male`, or an exact value such as `=0`.
+    pub selector: String,
+    pub message: Vec<MessageElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageError {
+    /// The character offset the error was found at.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+/// The plural categories a language uses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluralCategories {
+    pub cardinal: Vec<String>,
+    pub ordinal: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<MessageElement>, MessageError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    parser.message(0, false)
+}
+
+/// The names of every argument in the message, including the ones plurals
+/// and selects switch on.
+pub fn arguments(elements: &[MessageElement]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    collect_arguments(elements, &mut names);
+    names
+}
+
+fn collect_arguments(elements: &[MessageElement], names: &mut BTreeSet<String>) {
+    for element in elements {
+        match element {
+            MessageElement::Argument(name) | MessageElement::Formatted { name, .. } => {
+                names.insert(name.clone());
+            }
+            MessageElement::Plural { name, options, .. } | MessageElement::Select { name, options } => {
+                names.insert(name.clone());
+                for option in options {
+                    collect_arguments(&option.message, names);
+                }
+            }
+            MessageElement::Literal(_) | MessageElement::Pound => {}
+        }
+    }
+}
+
+/// The CLDR plural categories of `locale`, or `None` for languages without
+/// known rules.
+pub fn plural_categories(locale: &str) -> Option<PluralCategories> {
+    let language = locale.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+    let to_strings = |categories: &[&str]| categories.iter().map(|c| c.to_string()).collect();
+    Some(PluralCategories {
+        cardinal: to_strings(cardinal_categories(&language)?),
+        ordinal: to_strings(ordinal_categories(&language)),
+    })
+}
+
+fn cardinal_categories(language: &str) -> Option<&'static [&'static str]> {
+    Some(match language {
+        "ar" | "ars" | "cy" | "kw" => &["zero", "one", "two", "few", "many", "other"],
+        "br" | "ga" | "gv" | "mt" => &["one", "two", "few", "many", "other"],
+        "be" | "cs" | "lt" | "pl" | "ru" | "sk" | "uk" => &["one", "few", "many", "other"],
+        "dsb" | "gd" | "hsb" | "sl" => &["one", "two", "few", "other"],
+        "he" | "iw" | "iu" | "naq" | "sat" | "se" | "sma" | "smi" | "smj" | "smn" | "sms" => &["one", "two", "other"],
+        "bs" | "hr" | "mo" | "ro" | "sh" | "shi" | "sr" => &["one", "few", "other"],
+        "ksh" | "lag" | "lv" | "prg" => &["zero", "one", "other"],
+        "ca" | "es" | "fr" | "it" | "pt" | "vec" => &["one", "many", "other"],
+        _ if OTHER_ONLY.contains(&language) => &["other"],
+        _ if ONE_OTHER.contains(&language) => &["one", "other"],
+        _ => return None,
+    })
+}
+
+fn ordinal_categories(language: &str) -> &'static [&'static str] {
+    match language {
+        "cy" => &["zero", "one", "two", "few", "many", "other"],
+        "as" | "bn" | "gu" | "hi" | "or" => &["one", "two", "few", "many", "other"],
+        "ca" | "en" | "gd" | "mr" => &["one", "two", "few", "other"],
+        "mk" => &["one", "two", "many", "other"],
+        "az" => &["one", "few", "many", "other"],
+        "ka" | "kw" | "sq" => &["one", "many", "other"],
+        "be" | "tk" | "uk" => &["few", "other"],
+        "it" | "kk" | "lij" | "sc" | "vec" => &["many", "other"],
+        "bal" | "fil" | "fr" | "ga" | "hu" | "hy" | "lo" | "mo" | "ms" | "ne" | "ro" | "sv" | "tl" | "vi" => {
+            &["one", "other"]
+        }
+        _ => &["other"],
+    }
+}
+
+/// The categories in `required` that a plural's options don't cover. Exact
+/// values such as `=1` don't stand in for a category.
+pub fn missing_categories<'a>(options: &[MessageOption], required: &'a [String]) -> Vec<&'a str> {
+    required
+        .iter()
+        .filter(|category| !options.iter().any(|option| option.selector == **category))
+        .map(String::as_str)
+        .collect()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, message: impl Into<String>) -> MessageError {
+        MessageError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), MessageError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}'", expected)))
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /// Text and arguments up to the `}` closing an option, which is left for
+    /// the caller, or to the end of a top-level message.
+    fn message(&mut self, depth: usize, in_plural: bool) -> Result<Vec<MessageElement>, MessageError> {
+        let mut elements = Vec::new();
+        let mut literal = String::new();
+
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    self.position += 1;
+                    self.quoted(&mut literal, in_plural);
+                    continue;
+                }
+                '{' => {
+                    flush(&mut literal, &mut elements);
+                    elements.push(self.argument(depth)?);
+                    continue;
+                }
+                '}' if depth == 0 => return Err(self.error("Unmatched '}'")),
+                '}' => break,
+                '#' if in_plural => {
+                    flush(&mut literal, &mut elements);
+                    elements.push(MessageElement::Pound);
+                }
+                _ => literal.push(c),
+            }
+            self.position += 1;
+        }
+        flush(&mut literal, &mut elements);
+
+        Ok(elements)
+    }
+
+    /// The text after an apostrophe. `''` is an apostrophe; before a syntax
+    /// character one starts a quoted run up to the next single apostrophe;
+    /// anywhere else it is just an apostrophe.
+    fn quoted(&mut self, literal: &mut String, in_plural: bool) {
+        match self.peek() {
+            Some('\'') => {
+                literal.push('\'');
+                self.position += 1;
+                return;
+            }
+            Some('{' | '}' | '|') => {}
+            Some('#') if in_plural => {}
+            _ => {
+                literal.push('\'');
+                return;
+            }
+        }
+
+        while let Some(c) = self.peek() {
+            self.position += 1;
+            if c != '\'' {
+                literal.push(c);
+            } else if self.peek() == Some('\'') {
+                literal.push('\'');
+                self.position += 1;
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn argument(&mut self, depth: usize) -> Result<MessageElement, MessageError> {
+        self.expect('{')?;
+        self.skip_whitespace();
+        let name = self.identifier();
+        if name.is_empty() {
+            return Err(self.error("Expected an argument name"));
+        }
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('}') => {
+                self.position += 1;
+                return Ok(MessageElement::Argument(name));
+            }
+            Some(',') => self.position += 1,
+            _ => return Err(self.error("Expected ',' or '}'")),
+        }
+        self.skip_whitespace();
+        let type_start = self.position;
+        let kind = self.identifier();
+        self.skip_whitespace();
+
+        match kind.as_str() {
+            "plural" | "selectordinal" => {
+                self.expect(',')?;
+                let (offset, options) = self.options(depth, true)?;
+                Ok(MessageElement::Plural {
+                    name,
+                    ordinal: kind == "selectordinal",
+                    offset,
+                    options,
+                })
+            }
+            "select" => {
+                self.expect(',')?;
+                let (_, options) = self.options(depth, false)?;
+                Ok(MessageElement::Select { name, options })
+            }
+            format if FORMATS.contains(&format) => {
+                let style = match self.peek() {
+                    Some(',') => {
+                        self.position += 1;
+                        Some(self.style()?)
+                    }
+                    _ => None,
+                };
+                self.expect('}')?;
+                Ok(MessageElement::Formatted {
+                    name,
+                    format: kind,
+                    style,
+                })
+            }
+            "" => Err(self.error("Expected an argument type")),
+            _ => {
+                self.position = type_start;
+                Err(self.error(format!("Unknown argument type '{}'", kind)))
+            }
+        }
+    }
+
+    /// The raw style of a formatted argument, up to its closing `}`. Nested
+    /// braces and quoted text are kept as written.
+    fn style(&mut self) -> Result<String, MessageError> {
+        let start = self.position;
+        let mut nesting = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    self.position += 1;
+                    while self.peek().is_some_and(|c| c != '\'') {
+                        self.position += 1;
+                    }
+                }
+                '{' => nesting += 1,
+                '}' if nesting == 0 => break,
+                '}' => nesting -= 1,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        let style: String = self.chars[start..self.position.min(self.chars.len())].iter().collect();
+        Ok(style.trim().to_string())
+    }
+
+    /// The options of a plural or select, and the plural's offset, up to and
+    /// including the closing `}`.
+    fn options(&mut self, depth: usize, plural: bool) -> Result<(i64, Vec<MessageOption>), MessageError> {
+        self.skip_whitespace();
+        let mut offset = 0;
+        if plural && self.chars[self.position..].starts_with(&['o', 'f', 'f', 's', 'e', 't', ':']) {
+            self.position += "offset:".len();
+            self.skip_whitespace();
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.position += 1;
+            }
+            let digits: String = self.chars[start..self.position].iter().collect();
+            offset = digits.parse().map_err(|_| self.error("Expected a number after 'offset:'"))?;
+        }
+
+        let mut options: Vec<MessageOption> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let selector_start = self.position;
+            let selector = match self.peek() {
+                Some('}') => {
+                    self.position += 1;
+                    break;
+                }
+                None => return Err(self.error("Expected '}'")),
+                Some('=') if plural => {
+                    self.position += 1;
+                    let start = self.position;
+                    while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.' || c == '-') {
+                        self.position += 1;
+                    }
+                    let value: String = self.chars[start..self.position].iter().collect();
+                    if value.parse::<f64>().is_err() {
+                        return Err(self.error("Expected a number after '='"));
+                    }
+                    format!("={}", value)
+                }
+                _ => self.identifier(),
+            };
+            if selector.is_empty() {
+                return Err(self.error("Expected a selector"));
+            }
+            if options.iter().any(|option| option.selector == selector) {
+                self.position = selector_start;
+                return Err(self.error(format!("Duplicate selector '{}'", selector)));
+            }
+
+            self.skip_whitespace();
+            if self.peek() != Some('{') {
+                return Err(self.error(format!("Expected '{{' after '{}'", selector)));
+            }
+            self.position += 1;
+            let message = self.message(depth + 1, plural)?;
+            self.expect('}')?;
+            options.push(MessageOption { selector, message });
+        }
+
+        if !options.iter().any(|option| option.selector == "other") {
+            return Err(self.error("Missing 'other' option"));
+        }
+        Ok((offset, options))
+    }
+}
+
+fn flush(literal: &mut String, elements: &mut Vec<MessageElement>) {
+    if !literal.is_empty() {
+        elements.push(MessageElement::Literal(std::mem::take(literal)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(text: &str) -> MessageElement {
+        MessageElement::Literal(text.to_string())
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(parse("it''s '{literal}' {name}").unwrap(), vec![
+            literal("it's {literal} "),
+            MessageElement::Argument("name".to_string()),
+        ]);
+        // `#` only needs quoting inside a plural.
+        assert_eq!(parse("# '#'").unwrap(), vec![literal("# '#'")]);
+        let Some(MessageElement::Plural { options, .. }) = parse("{n, plural, other {'#' #}}").unwrap().pop() else {
+            panic!("expected a plural");
+        };
+        assert_eq!(options[0].message, vec![literal("# "), MessageElement::Pound]);
+    }
+
+    #[test]
+    fn nested_select_in_plural_with_offset() {
+        let elements = parse("{count, plural, offset:1 =0 {nobody} other {{gender, select, female {her and #} other {them and #}}}}").unwrap();
+        let [MessageElement::Plural { name, ordinal, offset, options }] = elements.as_slice() else {
+            panic!("expected a single plural, got {:?}", elements);
+        };
+        assert_eq!((name.as_str(), *ordinal, *offset), ("count", false, 1));
+        assert_eq!(options.iter().map(|o| o.selector.as_str()).collect::<Vec<_>>(), ["=0", "other"]);
+        let [MessageElement::Select { name, options }] = options[1].message.as_slice() else {
+            panic!("expected a nested select, got {:?}", options[1].message);
+        };
+        assert_eq!(name, "gender");
+        // As in ICU, `#` only stands for the number directly inside a plural.
+        assert_eq!(options[0].message, vec![literal("her and #")]);
+        assert_eq!(arguments(&elements), BTreeSet::from(["count".to_string(), "gender".to_string()]));
+    }
+
+    #[test]
+    fn unbalanced_braces() {
+        assert!(parse("Hello {name").is_err());
+        assert!(parse("{n, plural, one {x}").is_err());
+    }
+}
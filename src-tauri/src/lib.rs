@@ -1,85 +1,581 @@
+mod analysis;
 mod database;
+mod formats;
+mod icu;
 mod llm_bridge;
+mod qa;
+mod segmentation;
+mod spreadsheet;
+mod workflow;
 
-use database::{Database, Project, Translation, TranslationStatus, ChatMessage, ChatRole};
+use database::{Database, Project, Translation, TranslationStatus, ChatMessage, ChatRole, RepairReport, ProjectStatistics, TranslationSelector, TranslationQuery, TranslationPage, LockResult, RepetitionGroup, PropagationOptions, PropagationResult, SourceFile, ImportSummary, ReimportSummary, FileProgress, NewTranslation, TranslationUpdate, BulkResult};
+use formats::{ExportOptions, ImportOptions, ResourceFormat};
+use formats::inline::{self, InlineElement};
+use analysis::{AnalysisExportFormat, AnalysisReport};
+use icu::{MessageElement, PluralCategories};
+use qa::{MessageIssue, SubtitleIssue, SubtitleLimits, TagIssue};
+use spreadsheet::{
+    SkippedChange, SpreadsheetApplyResult, SpreadsheetChange, SpreadsheetFormat, SpreadsheetOptions, SpreadsheetPreview,
+};
+use workflow::{Workflow, WorkflowRole};
 use llm_bridge::{LocalLLMBridge, ChatMessage as LLMChatMessage};
+use serde::Serialize;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tauri::{State, Manager};
-use tokio::sync::Mutex;
 
-type DbState = Arc<Mutex<Database>>;
-type LLMState = Arc<Mutex<LocalLLMBridge>>;
+// Both are used from many commands at once: the database queues its own
+// writes and the bridge holds no state between requests.
+type DbState = Arc<Database>;
+type LLMState = Arc<LocalLLMBridge>;
+
+/// How startup went. Kept as state so the window can ask once it has loaded
+/// and show why the backend is unavailable.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum BackendHealth {
+    Ready,
+    Failed { reason: String },
+}
 
 // Project commands
 #[tauri::command]
-async fn create_project(db: State<'_, DbState>, name: String, description: Option<String>) -> Result<Project, String> {
-    let db = db.lock().await;
-    db.create_project(name, description).await.map_err(|e| e.to_string())
+async fn create_project(
+    db: State<'_, DbState>,
+    name: String,
+    description: Option<String>,
+    source_locale: Option<String>,
+    target_locale: Option<String>
+) -> Result<Project, String> {
+    db.create_project(name, description, source_locale, target_locale).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn get_projects(db: State<'_, DbState>) -> Result<Vec<Project>, String> {
-    let db = db.lock().await;
     db.get_projects().await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn get_project(db: State<'_, DbState>, id: String) -> Result<Option<Project>, String> {
-    let db = db.lock().await;
     db.get_project(&id).await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn set_project_locales(
+    db: State<'_, DbState>,
+    id: String,
+    source_locale: Option<String>,
+    target_locale: Option<String>
+) -> Result<Option<Project>, String> {
+    db.set_project_locales(&id, source_locale, target_locale).await.map_err(|e| e.to_string())
+}
+
+// Analysis commands
+#[tauri::command]
+async fn analyze_projects(db: State<'_, DbState>, project_ids: Vec<String>) -> Result<AnalysisReport, String> {
+    db.analyze_projects(&project_ids).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_analysis(
+    db: State<'_, DbState>,
+    project_ids: Vec<String>,
+    format: AnalysisExportFormat,
+    path: String
+) -> Result<(), String> {
+    let report = db.analyze_projects(&project_ids).await.map_err(|e| e.to_string())?;
+    let content = analysis::export_report(&report, format)?;
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+// QA commands
+#[tauri::command]
+async fn check_subtitles(
+    db: State<'_, DbState>,
+    project_id: String,
+    limits: Option<SubtitleLimits>
+) -> Result<Vec<SubtitleIssue>, String> {
+    let translations = db.get_translations(&project_id).await.map_err(|e| e.to_string())?;
+    Ok(qa::check_subtitles(&translations, &limits.unwrap_or_default()))
+}
+
+/// Checks the project's ICU messages: strings from files in an ICU format and
+/// other strings whose source has a plural or select, against the plural
+/// rules of the project's target locale.
+#[tauri::command]
+async fn check_icu_messages(db: State<'_, DbState>, project_id: String) -> Result<Vec<MessageIssue>, String> {
+    let project = db
+        .get_project(&project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Project {} not found", project_id))?;
+    let translations = db.get_translations(&project_id).await.map_err(|e| e.to_string())?;
+    let icu_files = icu_files(&db, &project_id).await?;
+    Ok(qa::check_messages(&translations, &icu_files, project.target_locale.as_deref()))
+}
+
+/// The paths of the project's files whose strings are ICU messages.
+async fn icu_files(db: &Database, project_id: &str) -> Result<HashSet<String>, String> {
+    let files = db.get_source_files(project_id).await.map_err(|e| e.to_string())?;
+    Ok(files.into_iter().filter(|file| file.format.is_icu()).map(|file| file.path).collect())
+}
+
+/// Checks that translations keep the tags and variables of their source.
+#[tauri::command]
+async fn check_tags(db: State<'_, DbState>, project_id: String) -> Result<Vec<TagIssue>, String> {
+    let translations = db.get_translations(&project_id).await.map_err(|e| e.to_string())?;
+    Ok(qa::check_tags(&translations))
+}
+
+// Message format commands
+#[tauri::command]
+async fn parse_icu_message(text: String) -> Result<Vec<MessageElement>, String> {
+    icu::parse(&text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_plural_categories(locale: String) -> Result<PluralCategories, String> {
+    icu::plural_categories(&locale).ok_or_else(|| format!("No plural rules for locale {}", locale))
+}
+
 // Translation commands
 #[tauri::command]
 async fn create_translation(db: State<'_, DbState>, project_id: String, source_text: String) -> Result<Translation, String> {
-    let db = db.lock().await;
     db.create_translation(project_id, source_text).await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn create_translations_bulk(
+    db: State<'_, DbState>,
+    project_id: String,
+    items: Vec<NewTranslation>
+) -> Result<BulkResult, String> {
+    db.create_translations_bulk(&project_id, items).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_translations_bulk(
+    db: State<'_, DbState>,
+    items: Vec<TranslationUpdate>,
+    role: Option<WorkflowRole>
+) -> Result<BulkResult, String> {
+    db.update_translations_bulk(items, role).await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn get_translations(db: State<'_, DbState>, project_id: String) -> Result<Vec<Translation>, String> {
-    let db = db.lock().await;
     db.get_translations(&project_id).await.map_err(|e| e.to_string())
 }
 
+/// A page of the project's rows, for grids that load rows as they scroll.
+#[tauri::command]
+async fn query_translations(
+    db: State<'_, DbState>,
+    project_id: String,
+    mut query: TranslationQuery
+) -> Result<TranslationPage, String> {
+    if query.qa_issues.is_some() {
+        let project = db
+            .get_project(&project_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Project {} not found", project_id))?;
+        // Only translated rows are flagged, and only the ones the other
+        // filters pick need checking.
+        let translations = match query.selector.empty_target {
+            Some(true) => Vec::new(),
+            _ => {
+                let selector = TranslationSelector { empty_target: Some(false), ..query.selector.clone() };
+                db.select_translations(&project_id, &selector).await.map_err(|e| e.to_string())?
+            }
+        };
+        let icu_files = icu_files(&db, &project_id).await?;
+        query.flagged = Some(qa::flagged(&translations, &icu_files, project.target_locale.as_deref()));
+    }
+    db.query_translations(&project_id, &query).await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn update_translation(
     db: State<'_, DbState>, 
     id: String, 
     target_text: Option<String>, 
     notes: Option<String>, 
-    status: Option<String>
+    status: Option<TranslationStatus>,
+    workflow_status: Option<String>,
+    role: Option<WorkflowRole>,
+    propagate: Option<PropagationOptions>,
+    target_content: Option<Vec<InlineElement>>
+) -> Result<Option<PropagationResult>, String> {
+    // An editor working with tags sends the structured form instead of text.
+    let target_text = match target_content {
+        Some(content) => {
+            let translation = db
+                .get_translation(&id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Translation {} not found", id))?;
+            Some(inline::to_text(&content, &translation.placeholders)?)
+        }
+        None => target_text,
+    };
+    let status_changed = status.is_some() || workflow_status.is_some();
+    db.update_translation(&id, target_text, notes, status, workflow_status, role).await.map_err(|e| e.to_string())?;
+
+    // Repetitions are only filled in when this update confirmed the row.
+    let options = match propagate {
+        Some(options) if status_changed => options,
+        _ => return Ok(None),
+    };
+    let translation = db.get_translation(&id).await.map_err(|e| e.to_string())?;
+    if translation.is_some_and(|t| t.status != TranslationStatus::Draft) {
+        db.propagate_translation(&id, &options, role).await.map(Some).map_err(|e| e.to_string())
+    } else {
+        Ok(None)
+    }
+}
+
+#[tauri::command]
+async fn set_translation_details(
+    db: State<'_, DbState>,
+    id: String,
+    developer_comment: Option<String>,
+    max_length: Option<i64>,
+    screenshot: Option<String>
 ) -> Result<(), String> {
-    let db = db.lock().await;
-    let status_enum = status.map(|s| match s.as_str() {
-        "Draft" => TranslationStatus::Draft,
-        "Validated" => TranslationStatus::Validated,
-        "Approved" => TranslationStatus::Approved,
-        _ => TranslationStatus::Draft,
-    });
-    
-    db.update_translation(&id, target_text, notes, status_enum).await.map_err(|e| e.to_string())
+    db.set_translation_details(&id, developer_comment, max_length, screenshot).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_repetition_groups(db: State<'_, DbState>, project_id: String) -> Result<Vec<RepetitionGroup>, String> {
+    db.get_repetition_groups(&project_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn propagate_translation(
+    db: State<'_, DbState>,
+    id: String,
+    options: PropagationOptions,
+    role: Option<WorkflowRole>
+) -> Result<PropagationResult, String> {
+    db.propagate_translation(&id, &options, role).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn lock_translations(
+    db: State<'_, DbState>,
+    project_id: String,
+    selector: TranslationSelector,
+    reason: Option<String>,
+    locked_by: String
+) -> Result<LockResult, String> {
+    db.lock_translations(&project_id, &selector, reason, locked_by).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn unlock_translations(
+    db: State<'_, DbState>,
+    project_id: String,
+    selector: TranslationSelector,
+    user: String,
+    role: Option<WorkflowRole>
+) -> Result<LockResult, String> {
+    db.unlock_translations(&project_id, &selector, &user, role).await.map_err(|e| e.to_string())
+}
+
+// Segment commands
+/// Splits a translation at character offsets of its source text.
+#[tauri::command]
+async fn split_translation(db: State<'_, DbState>, id: String, positions: Vec<usize>) -> Result<Vec<Translation>, String> {
+    db.split_translation(&id, &positions).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn merge_translations(db: State<'_, DbState>, ids: Vec<String>) -> Result<Translation, String> {
+    db.merge_translations(&ids).await.map_err(|e| e.to_string())
+}
+
+/// The project's SRX rules, or the shipped ones when it has none.
+#[tauri::command]
+async fn get_segmentation_rules(db: State<'_, DbState>, project_id: String) -> Result<String, String> {
+    let srx = db.get_project_segmentation(&project_id).await.map_err(|e| e.to_string())?;
+    Ok(srx.unwrap_or_else(|| segmentation::DEFAULT_SRX.to_string()))
+}
+
+#[tauri::command]
+async fn set_segmentation_rules(db: State<'_, DbState>, project_id: String, srx: Option<String>) -> Result<(), String> {
+    db.set_project_segmentation(&project_id, srx).await.map_err(|e| e.to_string())
+}
+
+/// Splits `text` with the project's rules, so rule changes can be tried out
+/// before importing anything.
+#[tauri::command]
+async fn preview_segmentation(db: State<'_, DbState>, project_id: String, text: String) -> Result<Vec<String>, String> {
+    let project = db
+        .get_project(&project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Project {} not found", project_id))?;
+    let rules = db.get_segmentation_rules(&project_id).await.map_err(|e| e.to_string())?;
+    let language = project.source_locale.unwrap_or_default();
+    Ok(rules
+        .split(&text, &language)
+        .into_iter()
+        .map(|range| text[range].to_string())
+        .collect())
+}
+
+#[tauri::command]
+async fn set_translation_section(db: State<'_, DbState>, ids: Vec<String>, section: Option<String>) -> Result<u64, String> {
+    db.set_translation_section(&ids, section).await.map_err(|e| e.to_string())
+}
+
+/// Moves segments to right after `after_id`, or to the start of the project.
+#[tauri::command]
+async fn move_translations(
+    db: State<'_, DbState>,
+    project_id: String,
+    ids: Vec<String>,
+    after_id: Option<String>
+) -> Result<(), String> {
+    db.move_translations(&project_id, &ids, after_id.as_deref()).await.map_err(|e| e.to_string())
 }
 
-// Chat commands
 #[tauri::command]
-async fn add_chat_message(db: State<'_, DbState>, project_id: String, role: String, content: String) -> Result<ChatMessage, String> {
-    let db = db.lock().await;
-    let role_enum = match role.as_str() {
-        "User" => ChatRole::User,
-        "Assistant" => ChatRole::Assistant,
-        _ => ChatRole::User,
+async fn reorder_translations(db: State<'_, DbState>, project_id: String, ids: Vec<String>) -> Result<(), String> {
+    db.reorder_translations(&project_id, &ids).await.map_err(|e| e.to_string())
+}
+
+// Workflow commands
+#[tauri::command]
+async fn get_project_workflow(db: State<'_, DbState>, project_id: String) -> Result<Workflow, String> {
+    db.get_project_workflow(&project_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_project_workflow(db: State<'_, DbState>, project_id: String, workflow: Workflow) -> Result<Workflow, String> {
+    db.set_project_workflow(&project_id, workflow).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_project_statistics(db: State<'_, DbState>, project_id: String) -> Result<ProjectStatistics, String> {
+    db.get_project_statistics(&project_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_group_progress(db: State<'_, DbState>, project_id: String) -> Result<Vec<FileProgress>, String> {
+    db.get_group_progress(&project_id).await.map_err(|e| e.to_string())
+}
+
+// Import/export commands
+#[tauri::command]
+async fn import_resource_file(
+    db: State<'_, DbState>,
+    project_id: String,
+    path: String,
+    format: ResourceFormat
+) -> Result<ImportSummary, String> {
+    let content = std::fs::read(&path).map_err(|e| e.to_string())?;
+
+    let project = db
+        .get_project(&project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Project {} not found", project_id))?;
+    let segmentation = db.get_segmentation_rules(&project.id).await.map_err(|e| e.to_string())?;
+    let options = ImportOptions {
+        source_locale: project.source_locale,
+        target_locale: project.target_locale,
+        segmentation: Some(segmentation),
     };
-    
-    db.add_chat_message(project_id, role_enum, content).await.map_err(|e| e.to_string())
+    let units = format.parse(&content, &options).map_err(|e| e.to_string())?;
+
+    db.import_resource(&project_id, &path, format, &content, units).await.map_err(|e| e.to_string())
+}
+
+/// Reads an imported file again, from its original path unless another one
+/// is given, and merges the changes into the project.
+#[tauri::command]
+async fn reimport_resource_file(
+    db: State<'_, DbState>,
+    file_id: String,
+    path: Option<String>
+) -> Result<ReimportSummary, String> {
+    let (file, _) = db
+        .get_source_file(&file_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Source file {} not found", file_id))?;
+    let content = std::fs::read(path.as_deref().unwrap_or(&file.path)).map_err(|e| e.to_string())?;
+
+    let project = db
+        .get_project(&file.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Project {} not found", file.project_id))?;
+    let segmentation = db.get_segmentation_rules(&project.id).await.map_err(|e| e.to_string())?;
+    let options = ImportOptions {
+        source_locale: project.source_locale,
+        target_locale: project.target_locale,
+        segmentation: Some(segmentation),
+    };
+    let units = file.format.parse(&content, &options).map_err(|e| e.to_string())?;
+
+    db.reimport_resource(&file_id, &content, units).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_source_files(db: State<'_, DbState>, project_id: String) -> Result<Vec<SourceFile>, String> {
+    db.get_source_files(&project_id).await.map_err(|e| e.to_string())
+}
+
+/// Writes the translated version of an imported file and returns the path it
+/// was written to.
+#[tauri::command]
+async fn export_resource_file(
+    db: State<'_, DbState>,
+    file_id: String,
+    path: Option<String>,
+    options: ExportOptions
+) -> Result<String, String> {
+    let (file, template) = db
+        .get_source_file(&file_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Source file {} not found", file_id))?;
+    let project = db
+        .get_project(&file.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Project {} not found", file.project_id))?;
+    // Rows created in the app rather than imported belong to no file.
+    let translations: Vec<Translation> = db
+        .get_translations(&file.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|t| t.file_path.as_deref() == Some(file.path.as_str()))
+        .collect();
+
+    let mut options = options;
+    if options.target_locale.is_none() {
+        options.target_locale = project.target_locale;
+    }
+
+    let output = file.format.write(&template, &translations, &options).map_err(|e| e.to_string())?;
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let locale = options.target_locale.as_deref().ok_or("Project has no target locale; choose an export path")?;
+            file.format.default_export_path(&file.path, project.source_locale.as_deref(), locale)
+        }
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, output).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// Spreadsheet commands
+#[tauri::command]
+async fn preview_spreadsheet(
+    path: String,
+    format: SpreadsheetFormat,
+    options: SpreadsheetOptions
+) -> Result<SpreadsheetPreview, String> {
+    let content = std::fs::read(&path).map_err(|e| e.to_string())?;
+    spreadsheet::preview(&content, format, &options)
+}
+
+#[tauri::command]
+async fn import_spreadsheet(
+    db: State<'_, DbState>,
+    project_id: String,
+    path: String,
+    format: SpreadsheetFormat,
+    options: SpreadsheetOptions
+) -> Result<SpreadsheetApplyResult, String> {
+    let content = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let rows = spreadsheet::parse(&content, format, &options)?;
+    let row_numbers: Vec<usize> = rows.iter().map(|row| row.row).collect();
+
+    let workflow = db.get_project_workflow(&project_id).await.map_err(|e| e.to_string())?;
+    let units = spreadsheet::to_units(rows, &workflow)?;
+    let skipped = db.import_translations(&project_id, units).await.map_err(|e| e.to_string())?;
+    Ok(SpreadsheetApplyResult {
+        added: (row_numbers.len() - skipped.len()) as u64,
+        updated: 0,
+        skipped: skipped
+            .into_iter()
+            .map(|index| SkippedChange {
+                row: row_numbers[index],
+                reason: "A row with the same key and context already exists".to_string(),
+            })
+            .collect(),
+    })
+}
+
+#[tauri::command]
+async fn export_spreadsheet(
+    db: State<'_, DbState>,
+    project_id: String,
+    path: String,
+    format: SpreadsheetFormat,
+    options: SpreadsheetOptions
+) -> Result<(), String> {
+    let translations = db.get_translations(&project_id).await.map_err(|e| e.to_string())?;
+    let content = spreadsheet::export(&translations, format, &options)?;
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+/// Compares a spreadsheet returned by a reviewer with the project, without
+/// changing anything.
+#[tauri::command]
+async fn preview_spreadsheet_changes(
+    db: State<'_, DbState>,
+    project_id: String,
+    path: String,
+    format: SpreadsheetFormat,
+    options: SpreadsheetOptions
+) -> Result<Vec<SpreadsheetChange>, String> {
+    let content = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let rows = spreadsheet::parse(&content, format, &options)?;
+
+    let workflow = db.get_project_workflow(&project_id).await.map_err(|e| e.to_string())?;
+    let translations = db.get_translations(&project_id).await.map_err(|e| e.to_string())?;
+    spreadsheet::diff(&rows, &translations, &workflow)
+}
+
+#[tauri::command]
+async fn apply_spreadsheet_changes(
+    db: State<'_, DbState>,
+    project_id: String,
+    changes: Vec<SpreadsheetChange>,
+    role: Option<WorkflowRole>
+) -> Result<SpreadsheetApplyResult, String> {
+    db.apply_spreadsheet_changes(&project_id, &changes, role).await.map_err(|e| e.to_string())
+}
+
+// Chat commands
+#[tauri::command]
+async fn add_chat_message(db: State<'_, DbState>, project_id: String, role: ChatRole, content: String) -> Result<ChatMessage, String> {
+    db.add_chat_message(project_id, role, content).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn get_chat_messages(db: State<'_, DbState>, project_id: String) -> Result<Vec<ChatMessage>, String> {
-    let db = db.lock().await;
     db.get_chat_messages(&project_id).await.map_err(|e| e.to_string())
 }
 
+// Maintenance commands
+#[tauri::command]
+async fn repair_database(db: State<'_, DbState>, dry_run: bool) -> Result<RepairReport, String> {
+    db.repair_invalid_enum_values(dry_run).await.map_err(|e| e.to_string())
+}
+
 // LLM commands
 #[tauri::command]
 async fn chat_with_llm(
@@ -88,9 +584,6 @@ async fn chat_with_llm(
     project_id: String, 
     message: String
 ) -> Result<String, String> {
-    let llm = llm.lock().await;
-    let db = db.lock().await;
-    
     // Convert chat history to LLM format
     let chat_messages = db.get_chat_messages(&project_id).await.map_err(|e| e.to_string())?;
     let llm_messages: Vec<LLMChatMessage> = chat_messages
@@ -127,7 +620,6 @@ async fn translate_with_llm(
     source_lang: String,
     target_lang: String
 ) -> Result<String, String> {
-    let llm = llm.lock().await;
     llm.translate_text(&source_text, &source_lang, &target_lang).await.map_err(|e| e.to_string())
 }
 
@@ -137,7 +629,6 @@ async fn explain_context_with_llm(
     text: String,
     language: String
 ) -> Result<String, String> {
-    let llm = llm.lock().await;
     llm.explain_context(&text, &language).await.map_err(|e| e.to_string())
 }
 
@@ -147,42 +638,86 @@ async fn suggest_improvements_with_llm(
     source_text: String,
     translation: String
 ) -> Result<String, String> {
-    let llm = llm.lock().await;
     llm.suggest_improvements(&source_text, &translation).await.map_err(|e| e.to_string())
 }
 
+// Startup commands
+#[tauri::command]
+async fn backend_health(health: State<'_, BackendHealth>) -> Result<BackendHealth, String> {
+    Ok(health.inner().clone())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                // Initialize database with a simple path
-                let database_url = "sqlite::memory:"; // Use in-memory database for testing
-                
-                let db = Database::new(database_url).await.expect("Failed to initialize database");
-                let db_state: DbState = Arc::new(Mutex::new(db));
-                
-                // Initialize LLM bridge
-                let llm_bridge = LocalLLMBridge::new();
-                let llm_state: LLMState = Arc::new(Mutex::new(llm_bridge));
-                
-                // Store the states in the app state
-                app_handle.manage(db_state);
-                app_handle.manage(llm_state);
-            });
+            // Commands can arrive as soon as the window loads, so the state
+            // they use is in place before setup returns. A failure is kept
+            // for `backend_health` rather than stopping the app, so the
+            // window can say what went wrong.
+            let database_url = "sqlite::memory:"; // Use in-memory database for testing
+            let health = match tauri::async_runtime::block_on(Database::new(database_url)) {
+                Ok(db) => {
+                    app.manage::<DbState>(Arc::new(db));
+                    BackendHealth::Ready
+                }
+                Err(e) => BackendHealth::Failed {
+                    reason: format!("Failed to open the database: {}", e),
+                },
+            };
+            app.manage::<LLMState>(Arc::new(LocalLLMBridge::new()));
+            app.manage(health);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            backend_health,
             create_project,
             get_projects,
             get_project,
+            set_project_locales,
+            analyze_projects,
+            export_analysis,
+            check_subtitles,
+            check_icu_messages,
+            check_tags,
+            parse_icu_message,
+            get_plural_categories,
             create_translation,
+            create_translations_bulk,
+            update_translations_bulk,
             get_translations,
+            query_translations,
             update_translation,
+            set_translation_details,
+            get_repetition_groups,
+            propagate_translation,
+            lock_translations,
+            unlock_translations,
+            split_translation,
+            merge_translations,
+            get_segmentation_rules,
+            set_segmentation_rules,
+            preview_segmentation,
+            set_translation_section,
+            move_translations,
+            reorder_translations,
+            get_project_workflow,
+            set_project_workflow,
+            get_project_statistics,
+            get_group_progress,
+            import_resource_file,
+            reimport_resource_file,
+            get_source_files,
+            export_resource_file,
+            preview_spreadsheet,
+            import_spreadsheet,
+            export_spreadsheet,
+            preview_spreadsheet_changes,
+            apply_spreadsheet_changes,
             add_chat_message,
             get_chat_messages,
+            repair_database,
             chat_with_llm,
             translate_with_llm,
             explain_context_with_llm,
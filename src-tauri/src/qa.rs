@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::database::Translation;
+use crate::formats::inline::InlineElement;
+use crate::icu::{self, MessageElement, PluralCategories};
+
+/// Limits for subtitle cues. The defaults follow common broadcast guidelines
+/// for adult programmes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleLimits {
+    pub max_chars_per_second: f64,
+    pub max_line_length: usize,
+    pub max_lines: usize,
+}
+
+impl Default for SubtitleLimits {
+    fn default() -> Self {
+        SubtitleLimits {
+            max_chars_per_second: 17.0,
+            max_line_length: 42,
+            max_lines: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubtitleCheck {
+    ReadingSpeed,
+    LineLength,
+    LineCount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleIssue {
+    pub translation_id: String,
+    pub check: SubtitleCheck,
+    pub message: String,
+}
+
+/// Checks the translated text of every timed cue against `limits`. Cues
+/// without a translation or without timing are skipped.
+pub fn check_subtitles(translations: &[Translation], limits: &SubtitleLimits) -> Vec<SubtitleIssue> {
+    let mut issues = Vec::new();
+
+    for translation in translations {
+        let (Some(start_ms), Some(end_ms)) = (translation.start_ms, translation.end_ms) else {
+            continue;
+        };
+        let Some(text) = translation.target_text.as_deref().filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        let mut issue = |check, message| {
+            issues.push(SubtitleIssue {
+                translation_id: translation.id.clone(),
+                check,
+                message,
+            })
+        };
+
+        let lines: Vec<String> = text.lines().map(visible_text).collect();
+        let characters: usize = lines.iter().map(|line| line.chars().count()).sum();
+        let duration_ms = end_ms - start_ms;
+        if duration_ms <= 0 {
+            issue(SubtitleCheck::ReadingSpeed, "Cue ends before it starts".to_string());
+        } else {
+            let speed = characters as f64 * 1000.0 / duration_ms as f64;
+            if speed > limits.max_chars_per_second {
+                issue(
+                    SubtitleCheck::ReadingSpeed,
+                    format!(
+                        "Reading speed is {:.1} characters per second (maximum {})",
+                        speed, limits.max_chars_per_second
+                    ),
+                );
+            }
+        }
+
+        for (number, line) in lines.iter().enumerate() {
+            let length = line.chars().count();
+            if length > limits.max_line_length {
+                issue(
+                    SubtitleCheck::LineLength,
+                    format!(
+                        "Line {} has {} characters (maximum {})",
+                        number + 1,
+                        length,
+                        limits.max_line_length
+                    ),
+                );
+            }
+        }
+
+        if lines.len() > limits.max_lines {
+            issue(
+                SubtitleCheck::LineCount,
+                format!("Cue has {} lines (maximum {})", lines.len(), limits.max_lines),
+            );
+        }
+    }
+
+    issues
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageCheck {
+    SourceSyntax,
+    TargetSyntax,
+    MissingArgument,
+    UnknownArgument,
+    MissingPluralCategory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageIssue {
+    pub translation_id: String,
+    pub check: MessageCheck,
+    pub message: String,
+}
+
+/// Checks ICU MessageFormat strings: rows from `icu_files`, and other rows
+/// whose source has a plural or select. Sources from ICU files have to
+/// parse, and other sources that don't are skipped, since their braces may
+/// mean something else. Translations have to parse, use the source's
+/// arguments and, when the target locale's plural rules are known, cover
+/// each of its plural categories.
+pub fn check_messages(
+    translations: &[Translation],
+    icu_files: &HashSet<String>,
+    target_locale: Option<&str>,
+) -> Vec<MessageIssue> {
+    let categories = target_locale.and_then(icu::plural_categories);
+    let icu_syntax = Regex::new(r"\{\s*[\w-]+\s*,\s*(?:plural|select|selectordinal)\s*,").expect("the ICU pattern is valid");
+    let mut issues = Vec::new();
+
+    for translation in translations {
+        let from_icu_file = translation.file_path.as_ref().is_some_and(|path| icu_files.contains(path));
+        if !from_icu_file && !icu_syntax.is_match(&translation.source_text) {
+            continue;
+        }
+        let mut issue = |check, message| {
+            issues.push(MessageIssue {
+                translation_id: translation.id.clone(),
+                check,
+                message,
+            })
+        };
+
+        let source = match icu::parse(&translation.source_text) {
+            Ok(source) => Some(source),
+            Err(e) if from_icu_file => {
+                issue(MessageCheck::SourceSyntax, format!("Source: {}", e));
+                None
+            }
+            Err(_) => continue,
+        };
+        let Some(text) = translation.target_text.as_deref().filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        let target = match icu::parse(text) {
+            Ok(target) => target,
+            Err(e) => {
+                issue(MessageCheck::TargetSyntax, format!("Translation: {}", e));
+                continue;
+            }
+        };
+
+        if let Some(source) = &source {
+            let expected = icu::arguments(source);
+            let found = icu::arguments(&target);
+            for name in expected.difference(&found) {
+                issue(MessageCheck::MissingArgument, format!("Argument '{}' is missing", name));
+            }
+            for name in found.difference(&expected) {
+                issue(MessageCheck::UnknownArgument, format!("Argument '{}' is not in the source", name));
+            }
+        }
+
+        if let Some(categories) = &categories {
+            for (name, missing) in missing_plural_categories(&target, categories) {
+                issue(
+                    MessageCheck::MissingPluralCategory,
+                    format!("Plural '{}' is missing the categories {}", name, missing.join(", ")),
+                );
+            }
+        }
+    }
+
+    issues
+}
+
+/// Every plural in the message, however deeply nested, with the
+/// categories it doesn't cover.
+fn missing_plural_categories<'a>(
+    elements: &'a [MessageElement],
+    categories: &'a PluralCategories,
+) -> Vec<(&'a str, Vec<&'a str>)> {
+    let mut found = Vec::new();
+    for element in elements {
+        let options = match element {
+            MessageElement::Plural { name, ordinal, options, .. } => {
+                let required = if *ordinal { &categories.ordinal } else { &categories.cardinal };
+                let missing = icu::missing_categories(options, required);
+                if !missing.is_empty() {
+                    found.push((name.as_str(), missing));
+                }
+                options
+            }
+            MessageElement::Select { options, .. } => options,
+            _ => continue,
+        };
+        for option in options {
+            found.extend(missing_plural_categories(&option.message, categories));
+        }
+    }
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagCheck {
+    /// The translation has fewer of a tag or variable than the source.
+    Missing,
+    /// The translation has more of a tag or variable than the source, or a
+    /// tag the source doesn't have.
+    Extra,
+    /// Paired tags are closed out of order.
+    Order,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagIssue {
+    pub translation_id: String,
+    pub check: TagCheck,
+    pub message: String,
+}
+
+/// Checks that translations have the tags and variables of their source, as
+/// many times as the source has them, with paired tags properly nested.
+pub fn check_tags(translations: &[Translation]) -> Vec<TagIssue> {
+    let numbered_tag = Regex::new(r"</?\d+/?>").expect("the tag pattern is valid");
+    let mut issues = Vec::new();
+
+    for translation in translations {
+        if translation.target_text.as_deref().is_none_or(|t| t.trim().is_empty()) {
+            continue;
+        }
+        let Some(target) = &translation.target_content else {
+            continue;
+        };
+        let mut issue = |check, message| {
+            issues.push(TagIssue {
+                translation_id: translation.id.clone(),
+                check,
+                message,
+            })
+        };
+
+        let expected = tag_counts(&translation.source_content);
+        let found = tag_counts(target);
+        let mut balanced = true;
+        for (tag, &count) in &expected {
+            match found.get(tag).copied().unwrap_or(0) {
+                0 => issue(TagCheck::Missing, format!("{} is missing", tag)),
+                n if n < count => issue(TagCheck::Missing, format!("{} appears {} times, {} in the source", tag, n, count)),
+                n if n > count => issue(TagCheck::Extra, format!("{} appears {} times, {} in the source", tag, n, count)),
+                _ => continue,
+            }
+            balanced = false;
+        }
+        for tag in found.keys().filter(|tag| !expected.contains_key(*tag)) {
+            issue(TagCheck::Extra, format!("{} is not in the source", tag));
+            balanced = false;
+        }
+        for element in target {
+            if let InlineElement::Text(text) = element {
+                for tag in numbered_tag.find_iter(text) {
+                    issue(TagCheck::Extra, format!("Tag {} is not in the source", tag.as_str()));
+                }
+            }
+        }
+
+        // With the counts off, the order would only repeat the same problem.
+        if !balanced {
+            continue;
+        }
+        let mut open = Vec::new();
+        for element in target {
+            match element {
+                InlineElement::Open { id, .. } => open.push(*id),
+                InlineElement::Close { id, display } => {
+                    if open.last() != Some(id) {
+                        issue(TagCheck::Order, format!("Closing tag {} ({}) doesn't close the last opened tag", id, display));
+                    }
+                    open.retain(|open| open != id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    issues
+}
+
+/// How many times each tag and variable appears, by a description fit for
+/// messages.
+fn tag_counts(elements: &[InlineElement]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for element in elements {
+        let tag = match element {
+            InlineElement::Text(_) => continue,
+            InlineElement::Open { id, display } => format!("Opening tag {} ({})", id, display),
+            InlineElement::Close { id, display } => format!("Closing tag {} ({})", id, display),
+            InlineElement::Standalone { id, display } => format!("Tag {} ({})", id, display),
+            InlineElement::Variable { display, .. } => format!("Variable {}", display),
+        };
+        *counts.entry(tag).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The text a viewer actually reads: formatting tags such as `<i>` and
+/// SubRip position codes like `{\an8}` don't count towards any limit.
+fn visible_text(line: &str) -> String {
+    let mut visible = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let closing = match (c, chars.peek()) {
+            ('<', Some(next)) if next.is_alphabetic() || *next == '/' => '>',
+            ('{', Some('\\')) => '}',
+            _ => {
+                visible.push(c);
+                continue;
+            }
+        };
+        for c in chars.by_ref() {
+            if c == closing {
+                break;
+            }
+        }
+    }
+    visible.trim().to_string()
+}
+
+/// The ids of the translated rows any check flags: tags, ICU messages
+/// against `target_locale`, and subtitle cues against the default limits.
+pub fn flagged(translations: &[Translation], icu_files: &HashSet<String>, target_locale: Option<&str>) -> Vec<String> {
+    let translated: HashSet<&str> = translations
+        .iter()
+        .filter(|t| t.target_text.as_deref().is_some_and(|text| !text.trim().is_empty()))
+        .map(|t| t.id.as_str())
+        .collect();
+    let mut ids = BTreeSet::new();
+    ids.extend(check_tags(translations).into_iter().map(|issue| issue.translation_id));
+    ids.extend(check_messages(translations, icu_files, target_locale).into_iter().map(|issue| issue.translation_id));
+    ids.extend(check_subtitles(translations, &SubtitleLimits::default()).into_iter().map(|issue| issue.translation_id));
+    ids.into_iter().filter(|id| translated.contains(id.as_str())).collect()
+}
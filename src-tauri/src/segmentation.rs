@@ -0,0 +1,267 @@
+//! Sentence segmentation with SRX 2.0 rules. A rule says whether text may
+//! break between what its `beforebreak` pattern matches and what its
+//! `afterbreak` pattern matches; at every position the first rule that
+//! matches decides. Language maps pick the rules for a language, and with
+//! `cascade` every matching map contributes its rules in order. Patterns are
+//! ICU regular expressions; `fancy-regex` reads them including look-around,
+//! which many published rule sets use.
+
+use std::ops::Range;
+
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use fancy_regex::Regex;
+
+/// The rules used when a project has none of its own.
+pub const DEFAULT_SRX: &str = include_str!("segmentation/default.srx");
+
+/// How many characters before a position `beforebreak` patterns can see.
+const LOOKBEHIND: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Rule {
+    breaks: bool,
+    before: Regex,
+    after: Regex,
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentationRules {
+    cascade: bool,
+    languages: Vec<(String, Vec<Rule>)>,
+    maps: Vec<(Regex, String)>,
+}
+
+impl Default for SegmentationRules {
+    fn default() -> Self {
+        SegmentationRules::from_srx(DEFAULT_SRX).expect("the shipped segmentation rules are valid")
+    }
+}
+
+impl SegmentationRules {
+    pub fn from_srx(srx: &str) -> Result<Self, String> {
+        let mut reader = Reader::from_str(srx);
+        let mut rules = SegmentationRules {
+            cascade: false,
+            languages: Vec::new(),
+            maps: Vec::new(),
+        };
+        let mut current: Option<(String, Vec<Rule>)> = None;
+        let mut rule: Option<(bool, String, String)> = None;
+
+        loop {
+            let event = reader.read_event().map_err(|e| format!("Invalid SRX: {}", e))?;
+            let empty = matches!(event, Event::Empty(_));
+            match event {
+                Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                    b"header" => rules.cascade = attribute(&e, "cascade")?.as_deref() == Some("yes"),
+                    b"languagerule" => {
+                        let name = attribute(&e, "languagerulename")?
+                            .ok_or("Invalid SRX: languagerule without languagerulename")?;
+                        current = Some((name, Vec::new()));
+                    }
+                    b"rule" => {
+                        let breaks = attribute(&e, "break")?.as_deref() != Some("no");
+                        rule = Some((breaks, String::new(), String::new()));
+                    }
+                    name @ (b"beforebreak" | b"afterbreak") => {
+                        let pattern = if empty {
+                            String::new()
+                        } else {
+                            let raw = reader
+                                .read_text(e.to_end().name())
+                                .map_err(|e| format!("Invalid SRX: {}", e))?;
+                            unescape(&raw).map_err(|e| format!("Invalid SRX: {}", e))?.into_owned()
+                        };
+                        if let Some((_, before, after)) = rule.as_mut() {
+                            match name {
+                                b"beforebreak" => *before = pattern,
+                                _ => *after = pattern,
+                            }
+                        }
+                    }
+                    b"languagemap" => {
+                        let pattern = attribute(&e, "languagepattern")?
+                            .ok_or("Invalid SRX: languagemap without languagepattern")?;
+                        let name = attribute(&e, "languagerulename")?
+                            .ok_or("Invalid SRX: languagemap without languagerulename")?;
+                        rules.maps.push((compile(&format!("(?i)^(?:{})$", pattern))?, name));
+                    }
+                    _ => {}
+                },
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"rule" => {
+                        if let (Some((breaks, before, after)), Some((_, list))) = (rule.take(), current.as_mut()) {
+                            list.push(Rule {
+                                breaks,
+                                before: compile(&format!("(?:{})$", before))?,
+                                after: compile(&format!("^(?:{})", after))?,
+                            });
+                        }
+                    }
+                    b"languagerule" => rules.languages.extend(current.take()),
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        for (_, name) in &rules.maps {
+            if !rules.languages.iter().any(|(language, _)| language == name) {
+                return Err(format!("Invalid SRX: no languagerule named '{}'", name));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// The rules that apply to `language`, in the order they are tried.
+    fn rules_for(&self, language: &str) -> Vec<&Rule> {
+        let mut rules = Vec::new();
+        for (pattern, name) in &self.maps {
+            if !pattern.is_match(language).unwrap_or(false) {
+                continue;
+            }
+            if let Some((_, list)) = self.languages.iter().find(|(language, _)| language == name) {
+                rules.extend(list);
+            }
+            if !self.cascade {
+                break;
+            }
+        }
+        rules
+    }
+
+    /// The byte offsets in `text` where a new sentence starts.
+    pub fn breaks(&self, text: &str, language: &str) -> Vec<usize> {
+        let rules = self.rules_for(language);
+        let mut breaks = Vec::new();
+
+        for (position, _) in text.char_indices().skip(1) {
+            let window = text[..position]
+                .char_indices()
+                .rev()
+                .nth(LOOKBEHIND - 1)
+                .map_or(0, |(start, _)| start);
+            // A match that gives up (too much backtracking) counts as no match.
+            let rule = rules.iter().find(|rule| {
+                rule.before.is_match(&text[window..position]).unwrap_or(false)
+                    && rule.after.is_match(&text[position..]).unwrap_or(false)
+            });
+            if rule.is_some_and(|rule| rule.breaks) {
+                breaks.push(position);
+            }
+        }
+
+        breaks
+    }
+
+    /// The sentences of `text`, without the whitespace between them.
+    pub fn split(&self, text: &str, language: &str) -> Vec<Range<usize>> {
+        let mut bounds = vec![0];
+        bounds.extend(self.breaks(text, language));
+        bounds.push(text.len());
+
+        bounds
+            .windows(2)
+            .filter_map(|pair| trimmed(text, pair[0]..pair[1]))
+            .collect()
+    }
+}
+
+/// `range` without leading and trailing whitespace, or `None` when nothing
+/// else is left.
+pub fn trimmed(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let part = &text[range.clone()];
+    let start = range.start + (part.len() - part.trim_start().len());
+    let end = range.start + part.trim_end().len();
+    (start < end).then_some(start..end)
+}
+
+/// What goes between two sentences joined without their original spacing:
+/// nothing between Chinese or Japanese text, a space otherwise.
+pub fn joiner(before: &str, after: &str) -> &'static str {
+    let wide = |c: Option<char>| {
+        c.is_some_and(|c| {
+            matches!(c, '\u{3000}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF00}'..='\u{FFEF}')
+        })
+    };
+    if wide(before.chars().next_back()) || wide(after.chars().next()) {
+        ""
+    } else {
+        " "
+    }
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    match e.try_get_attribute(name).map_err(|e| format!("Invalid SRX: {}", e))? {
+        Some(attr) => Ok(Some(
+            attr.unescape_value().map_err(|e| format!("Invalid SRX: {}", e))?.into_owned(),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid SRX pattern '{}': {}", pattern, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sentences<'t>(text: &'t str, language: &str) -> Vec<&'t str> {
+        SegmentationRules::default()
+            .split(text, language)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn english_abbreviations() {
+        let text = "Dr. Smith arrived, e.g. at noon. He sat down.";
+        assert_eq!(SegmentationRules::default().breaks(text, "en"), [32]);
+        assert_eq!(sentences(text, "en-US"), ["Dr. Smith arrived, e.g. at noon.", "He sat down."]);
+    }
+
+    #[test]
+    fn german_abbreviations_and_ordinals() {
+        assert_eq!(sentences("Das ist z. B. gut. Am 3. Mai kam er.", "de"), ["Das ist z. B. gut.", "Am 3. Mai kam er."]);
+    }
+
+    #[test]
+    fn initials() {
+        assert_eq!(sentences("J. R. R. Tolkien wrote it. Read it!", "fr"), ["J. R. R. Tolkien wrote it.", "Read it!"]);
+    }
+
+    #[test]
+    fn look_around_rules() {
+        let srx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<srx xmlns="http://www.lisa.org/srx20" version="2.0">
+  <header segmentsubflows="yes" cascade="no"/>
+  <body>
+    <languagerules>
+      <languagerule languagerulename="Default">
+        <rule break="no">
+          <beforebreak>(?&lt;![A-Za-z])No\.</beforebreak>
+          <afterbreak>\s(?=\d)</afterbreak>
+        </rule>
+        <rule break="yes">
+          <beforebreak>(?&lt;=[a-z0-9])[.!?]</beforebreak>
+          <afterbreak>\s</afterbreak>
+        </rule>
+      </languagerule>
+    </languagerules>
+    <maprules>
+      <languagemap languagepattern=".*" languagerulename="Default"/>
+    </maprules>
+  </body>
+</srx>"#;
+        let rules = SegmentationRules::from_srx(srx).unwrap();
+        let text = "See No. 5 here. Then No. Go on.";
+        let sentences: Vec<&str> = rules.split(text, "en").into_iter().map(|range| &text[range]).collect();
+        assert_eq!(sentences, ["See No. 5 here.", "Then No.", "Go on."]);
+    }
+}
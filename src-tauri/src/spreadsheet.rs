@@ -0,0 +1,579 @@
+//! Bilingual spreadsheets (CSV and XLSX) for vendors and reviewers who work
+//! outside GAIA. Columns are mapped by position, so any layout a vendor sends
+//! can be read, and projects are exported to the same layout for re-import.
+
+use calamine::{open_workbook_from_rs, Reader, Xlsx};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use crate::database::{Translation, TranslationStatus};
+use crate::formats::ResourceUnit;
+use crate::workflow::{Workflow, WorkflowStatus};
+
+/// Rows shown when previewing a file to choose its columns.
+const PREVIEW_ROWS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpreadsheetFormat {
+    Csv,
+    Xlsx,
+}
+
+/// Zero-based column index of each field. Only `source` is required.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub id: Option<usize>,
+    pub key: Option<usize>,
+    pub source: usize,
+    pub target: Option<usize>,
+    pub notes: Option<usize>,
+    pub status: Option<usize>,
+    pub context: Option<usize>,
+}
+
+impl Default for ColumnMapping {
+    /// The layout GAIA exports when the caller doesn't choose one.
+    fn default() -> Self {
+        ColumnMapping {
+            id: Some(0),
+            key: Some(1),
+            source: 2,
+            target: Some(3),
+            notes: Some(4),
+            status: Some(5),
+            context: None,
+        }
+    }
+}
+
+impl ColumnMapping {
+    fn headers(&self) -> Vec<(usize, &'static str)> {
+        let mut headers: Vec<(usize, &'static str)> = [
+            (self.id, "ID"),
+            (self.key, "Key"),
+            (Some(self.source), "Source"),
+            (self.target, "Target"),
+            (self.notes, "Notes"),
+            (self.status, "Status"),
+            (self.context, "Context"),
+        ]
+        .into_iter()
+        .filter_map(|(column, name)| Some((column?, name)))
+        .collect();
+        headers.sort_by_key(|(column, _)| *column);
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpreadsheetOptions {
+    pub columns: ColumnMapping,
+    /// Whether the first row holds column names rather than a translation.
+    pub has_header: bool,
+    /// CSV only; detected from the first line when not set.
+    pub delimiter: Option<char>,
+    /// CSV only, as an encoding label such as `windows-1252`. Detected from
+    /// the byte order mark when not set, falling back to Windows-1252 for
+    /// files that aren't valid UTF-8.
+    pub encoding: Option<String>,
+    /// XLSX only; the first sheet when not set.
+    pub sheet: Option<String>,
+}
+
+impl Default for SpreadsheetOptions {
+    fn default() -> Self {
+        SpreadsheetOptions {
+            columns: ColumnMapping::default(),
+            has_header: true,
+            delimiter: None,
+            encoding: None,
+            sheet: None,
+        }
+    }
+}
+
+/// The first rows of a file together with what was detected, so the user
+/// can pick the column mapping.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpreadsheetPreview {
+    pub rows: Vec<Vec<String>>,
+    pub delimiter: Option<char>,
+    pub encoding: Option<String>,
+    pub sheets: Vec<String>,
+}
+
+/// One translation read from a spreadsheet. `row` is the 1-based row number
+/// as shown in Excel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpreadsheetRow {
+    pub row: usize,
+    pub id: Option<String>,
+    pub key: Option<String>,
+    pub source_text: String,
+    pub target_text: Option<String>,
+    pub notes: Option<String>,
+    pub status: Option<String>,
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+}
+
+/// A difference between a re-imported row and the project, shown to the
+/// user before anything is written. Fields are only set when they change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpreadsheetChange {
+    pub row: usize,
+    pub kind: ChangeKind,
+    pub translation_id: Option<String>,
+    pub key: Option<String>,
+    pub context: Option<String>,
+    pub source_text: String,
+    /// The source in the file differs from the project's, which usually
+    /// means the row was edited by mistake or the project changed since the
+    /// export.
+    pub source_changed: bool,
+    pub old_target: Option<String>,
+    pub new_target: Option<String>,
+    pub old_notes: Option<String>,
+    pub new_notes: Option<String>,
+    pub old_status: Option<String>,
+    pub new_status: Option<String>,
+    pub locked: bool,
+}
+
+/// A change that could not be applied, with the reason.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedChange {
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpreadsheetApplyResult {
+    pub added: u64,
+    pub updated: u64,
+    pub skipped: Vec<SkippedChange>,
+}
+
+struct Table {
+    rows: Vec<Vec<String>>,
+    delimiter: Option<char>,
+    encoding: Option<String>,
+    sheets: Vec<String>,
+}
+
+pub fn preview(content: &[u8], format: SpreadsheetFormat, options: &SpreadsheetOptions) -> Result<SpreadsheetPreview, String> {
+    let table = read_table(content, format, options)?;
+    Ok(SpreadsheetPreview {
+        rows: table.rows.into_iter().take(PREVIEW_ROWS).collect(),
+        delimiter: table.delimiter,
+        encoding: table.encoding,
+        sheets: table.sheets,
+    })
+}
+
+/// Reads every row that has source text.
+pub fn parse(content: &[u8], format: SpreadsheetFormat, options: &SpreadsheetOptions) -> Result<Vec<SpreadsheetRow>, String> {
+    let table = read_table(content, format, options)?;
+    let columns = &options.columns;
+    let skip = usize::from(options.has_header);
+
+    Ok(table
+        .rows
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .filter_map(|(index, cells)| {
+            let cell = |column: Option<usize>| {
+                column
+                    .and_then(|column| cells.get(column))
+                    .map(|value| value.trim_end_matches(['\r', '\n']))
+                    .filter(|value| !value.trim().is_empty())
+                    .map(str::to_string)
+            };
+            Some(SpreadsheetRow {
+                row: index + 1,
+                source_text: cell(Some(columns.source))?,
+                id: cell(columns.id),
+                key: cell(columns.key),
+                target_text: cell(columns.target),
+                notes: cell(columns.notes),
+                status: cell(columns.status),
+                context: cell(columns.context),
+            })
+        })
+        .collect())
+}
+
+/// Converts rows into units for a first import.
+pub fn to_units(rows: Vec<SpreadsheetRow>, workflow: &Workflow) -> Result<Vec<ResourceUnit>, String> {
+    rows.into_iter()
+        .map(|row| {
+            let status = row
+                .status
+                .as_deref()
+                .map(|status| resolve_status(workflow, status, row.row))
+                .transpose()?;
+            Ok(ResourceUnit {
+                key: row.key.unwrap_or_default(),
+                context: row.context,
+                notes: row.notes,
+                source_text: row.source_text,
+                target_text: row.target_text,
+                status: status.map(|s| s.category),
+                workflow_status: status.map(|s| s.id.clone()),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Compares re-imported rows with the project. Rows are matched by the ID
+/// column, then by key and context, then by source text when it is unique in the
+/// project; rows that match nothing are new. Unchanged rows are left out.
+pub fn diff(rows: &[SpreadsheetRow], existing: &[Translation], workflow: &Workflow) -> Result<Vec<SpreadsheetChange>, String> {
+    let by_id: HashMap<&str, &Translation> = existing.iter().map(|t| (t.id.as_str(), t)).collect();
+    let by_key: HashMap<(&str, Option<&str>), &Translation> = existing
+        .iter()
+        .filter_map(|t| Some(((t.resource_key.as_deref()?, t.context.as_deref()), t)))
+        .collect();
+    let mut by_source: HashMap<&str, Vec<&Translation>> = HashMap::new();
+    for translation in existing {
+        by_source.entry(translation.source_text.as_str()).or_default().push(translation);
+    }
+
+    let mut changes = Vec::new();
+    for row in rows {
+        let new_status = row
+            .status
+            .as_deref()
+            .map(|status| resolve_status(workflow, status, row.row).map(|s| s.id.clone()))
+            .transpose()?;
+        let new_notes = row.notes.clone();
+
+        let matched = row
+            .id
+            .as_deref()
+            .and_then(|id| by_id.get(id))
+            .or_else(|| row.key.as_deref().and_then(|key| by_key.get(&(key, row.context.as_deref()))))
+            .or_else(|| match by_source.get(row.source_text.as_str()).map(Vec::as_slice) {
+                Some([only]) => Some(only),
+                _ => None,
+            });
+
+        let Some(current) = matched else {
+            changes.push(SpreadsheetChange {
+                row: row.row,
+                kind: ChangeKind::Added,
+                translation_id: None,
+                key: row.key.clone(),
+                context: row.context.clone(),
+                source_text: row.source_text.clone(),
+                source_changed: false,
+                old_target: None,
+                new_target: row.target_text.clone(),
+                old_notes: None,
+                new_notes,
+                old_status: None,
+                new_status,
+                locked: false,
+            });
+            continue;
+        };
+
+        let target_changed = row.target_text.is_some() && row.target_text != current.target_text;
+        let notes_changed = new_notes.is_some() && new_notes != current.notes;
+        let status_changed = new_status.as_ref().is_some_and(|status| *status != current.workflow_status);
+        if !target_changed && !notes_changed && !status_changed {
+            continue;
+        }
+
+        changes.push(SpreadsheetChange {
+            row: row.row,
+            kind: ChangeKind::Modified,
+            translation_id: Some(current.id.clone()),
+            key: current.resource_key.clone(),
+            context: current.context.clone(),
+            source_text: current.source_text.clone(),
+            source_changed: row.source_text != current.source_text,
+            old_target: current.target_text.clone().filter(|_| target_changed),
+            new_target: row.target_text.clone().filter(|_| target_changed),
+            old_notes: current.notes.clone().filter(|_| notes_changed),
+            new_notes: new_notes.filter(|_| notes_changed),
+            old_status: Some(current.workflow_status.clone()).filter(|_| status_changed),
+            new_status: new_status.filter(|_| status_changed),
+            locked: current.locked,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Writes translations in the layout described by `options`.
+pub fn export(translations: &[Translation], format: SpreadsheetFormat, options: &SpreadsheetOptions) -> Result<Vec<u8>, String> {
+    let columns = &options.columns;
+    let headers = columns.headers();
+    let width = headers.last().map_or(0, |(column, _)| column + 1);
+
+    let mut rows = Vec::with_capacity(translations.len() + 1);
+    if options.has_header {
+        let mut row = vec![String::new(); width];
+        for (column, name) in &headers {
+            row[*column] = name.to_string();
+        }
+        rows.push(row);
+    }
+    for translation in translations {
+        let mut row = vec![String::new(); width];
+        let mut set = |column: Option<usize>, value: Option<&str>| {
+            if let (Some(column), Some(value)) = (column, value) {
+                row[column] = value.to_string();
+            }
+        };
+        set(columns.id, Some(&translation.id));
+        set(columns.key, translation.resource_key.as_deref());
+        set(Some(columns.source), Some(&translation.source_text));
+        set(columns.target, translation.target_text.as_deref());
+        set(columns.notes, translation.notes.as_deref());
+        set(columns.status, Some(&translation.workflow_status));
+        set(columns.context, translation.context.as_deref());
+        rows.push(row);
+    }
+
+    match format {
+        SpreadsheetFormat::Csv => write_csv(&rows, options),
+        SpreadsheetFormat::Xlsx => write_xlsx(&rows, options.has_header),
+    }
+}
+
+/// Finds the workflow status a cell refers to, by id, by label, or by one of
+/// the built-in status names.
+fn resolve_status<'w>(workflow: &'w Workflow, value: &str, row: usize) -> Result<&'w WorkflowStatus, String> {
+    let value = value.trim();
+    workflow
+        .status(value)
+        .or_else(|| workflow.statuses.iter().find(|s| s.label.eq_ignore_ascii_case(value)))
+        .or_else(|| {
+            let category = TranslationStatus::ALL
+                .iter()
+                .find(|status| status.as_str().eq_ignore_ascii_case(value))?;
+            workflow.status_for_category(*category)
+        })
+        .ok_or_else(|| format!("Row {}: unknown status '{}'", row, value))
+}
+
+fn read_table(content: &[u8], format: SpreadsheetFormat, options: &SpreadsheetOptions) -> Result<Table, String> {
+    match format {
+        SpreadsheetFormat::Csv => read_csv(content, options),
+        SpreadsheetFormat::Xlsx => read_xlsx(content, options),
+    }
+}
+
+fn read_csv(content: &[u8], options: &SpreadsheetOptions) -> Result<Table, String> {
+    let (text, encoding) = decode(content, options.encoding.as_deref())?;
+    let delimiter = options.delimiter.unwrap_or_else(|| detect_delimiter(&text));
+    if !delimiter.is_ascii() {
+        return Err(format!("Unsupported delimiter '{}'", delimiter));
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(text.as_bytes());
+    let rows = reader
+        .records()
+        .map(|record| record.map(|r| r.iter().map(str::to_string).collect()))
+        .collect::<Result<Vec<Vec<String>>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(Table {
+        rows,
+        delimiter: Some(delimiter),
+        encoding: Some(encoding.name().to_string()),
+        sheets: Vec::new(),
+    })
+}
+
+fn decode(content: &[u8], label: Option<&str>) -> Result<(String, &'static Encoding), String> {
+    let (encoding, body) = match Encoding::for_bom(content) {
+        Some((encoding, bom_length)) => (encoding, &content[bom_length..]),
+        None => match label {
+            Some(label) => {
+                let encoding = Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Unknown encoding '{}'", label))?;
+                (encoding, content)
+            }
+            None if std::str::from_utf8(content).is_ok() => (UTF_8, content),
+            None => (WINDOWS_1252, content),
+        },
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    if had_errors {
+        return Err(format!("File is not valid {}", encoding.name()));
+    }
+    Ok((text.into_owned(), encoding))
+}
+
+/// Picks whichever of comma, semicolon, tab or pipe appears most often in
+/// the first line; Excel uses semicolons in locales with decimal commas.
+fn detect_delimiter(text: &str) -> char {
+    let first_line = text.lines().next().unwrap_or("");
+    [',', ';', '\t', '|']
+        .into_iter()
+        .max_by_key(|candidate| first_line.matches(*candidate).count())
+        .filter(|candidate| first_line.contains(*candidate))
+        .unwrap_or(',')
+}
+
+fn read_xlsx(content: &[u8], options: &SpreadsheetOptions) -> Result<Table, String> {
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(content)).map_err(|e: calamine::XlsxError| e.to_string())?;
+    let sheets = workbook.sheet_names();
+    let sheet = match &options.sheet {
+        Some(sheet) => sheet.clone(),
+        None => sheets.first().cloned().ok_or("Workbook has no sheets")?,
+    };
+    let range = workbook.worksheet_range(&sheet).map_err(|e| e.to_string())?;
+
+    // Rows are relative to the first used cell, which isn't always A1.
+    let (first_row, first_column) = range.start().unwrap_or((0, 0));
+    let mut rows = vec![Vec::new(); first_row as usize];
+    for cells in range.rows() {
+        let mut row = vec![String::new(); first_column as usize];
+        row.extend(cells.iter().map(|cell| cell.to_string()));
+        rows.push(row);
+    }
+
+    Ok(Table {
+        rows,
+        delimiter: None,
+        encoding: None,
+        sheets,
+    })
+}
+
+fn write_csv(rows: &[Vec<String>], options: &SpreadsheetOptions) -> Result<Vec<u8>, String> {
+    let delimiter = options.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        return Err(format!("Unsupported delimiter '{}'", delimiter));
+    }
+
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter as u8).from_writer(Vec::new());
+    for row in rows {
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    let text = writer.into_inner().map_err(|e| e.to_string())?;
+    let text = String::from_utf8(text).map_err(|e| e.to_string())?;
+
+    let encoding = match options.encoding.as_deref() {
+        Some(label) => Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Unknown encoding '{}'", label))?,
+        None => UTF_8,
+    };
+    // Excel only recognises UTF-8 and UTF-16 files by their byte order mark.
+    let out: Vec<u8> = if encoding == UTF_16LE {
+        [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+    } else if encoding == UTF_16BE {
+        [0xFE, 0xFF].into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect()
+    } else if encoding == UTF_8 {
+        [0xEF, 0xBB, 0xBF].into_iter().chain(text.into_bytes()).collect()
+    } else {
+        let (bytes, _, had_errors) = encoding.encode(&text);
+        if had_errors {
+            return Err(format!("Some text cannot be written as {}", encoding.name()));
+        }
+        bytes.into_owned()
+    };
+    Ok(out)
+}
+
+fn write_xlsx(rows: &[Vec<String>], has_header: bool) -> Result<Vec<u8>, String> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    let wrap = Format::new().set_text_wrap();
+
+    for (index, row) in rows.iter().enumerate() {
+        let format = if has_header && index == 0 { &bold } else { &wrap };
+        for (column, value) in row.iter().enumerate() {
+            sheet
+                .write_string_with_format(index as u32, column as u16, value, format)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    if has_header {
+        sheet.set_freeze_panes(1, 0).map_err(|e| e.to_string())?;
+    }
+
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(id: &str, key: &str, source: &str, target: &str, workflow_status: &str) -> Translation {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "project_id": "p", "resource_key": key, "source_text": source, "target_text": target,
+            "notes": null, "status": "Draft", "workflow_status": workflow_status, "locked": false, "lock_reason": null,
+            "locked_by": null, "locked_at": null, "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn workflow() -> Workflow {
+        let status = |id: &str, category| WorkflowStatus { id: id.to_string(), label: id.to_string(), category };
+        Workflow {
+            initial_status: "New".to_string(),
+            statuses: vec![
+                status("New", TranslationStatus::Draft),
+                status("In Review", TranslationStatus::Draft),
+                status("Done", TranslationStatus::Approved),
+            ],
+            transitions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn export_and_reimport() {
+        let existing = [
+            translation("a", "greeting", "Hello, \"world\"", "Hallo, „Welt“", "New"),
+            translation("b", "farewell", "Bye\nfor now", "", "New"),
+        ];
+        let options = SpreadsheetOptions::default();
+        for format in [SpreadsheetFormat::Csv, SpreadsheetFormat::Xlsx] {
+            let file = export(&existing, format, &options).unwrap();
+            let mut rows = parse(&file, format, &options).unwrap();
+            let cells: Vec<_> = rows
+                .iter()
+                .map(|r| (r.id.as_deref(), r.key.as_deref(), r.source_text.as_str(), r.target_text.as_deref()))
+                .collect();
+            assert_eq!(
+                cells,
+                [
+                    (Some("a"), Some("greeting"), "Hello, \"world\"", Some("Hallo, „Welt“")),
+                    (Some("b"), Some("farewell"), "Bye\nfor now", None),
+                ],
+                "{:?}",
+                format
+            );
+            assert!(diff(&rows, &existing, &workflow()).unwrap().is_empty());
+
+            // A status that only differs from the current one by id is kept.
+            rows[1].target_text = Some("Tschüss".to_string());
+            rows[1].status = Some("In Review".to_string());
+            let changes = diff(&rows, &existing, &workflow()).unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].translation_id.as_deref(), Some("b"));
+            assert_eq!(changes[0].new_target.as_deref(), Some("Tschüss"));
+            assert_eq!(changes[0].new_status.as_deref(), Some("In Review"));
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::database::TranslationStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkflowRole {
+    Translator,
+    Reviewer,
+    ProjectManager,
+}
+
+/// A project-defined status. `category` ties it back to the built-in
+/// `TranslationStatus` so code that only cares about draft/validated/approved
+/// keeps working regardless of how many custom statuses a project has.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStatus {
+    pub id: String,
+    pub label: String,
+    pub category: TranslationStatus,
+}
+
+/// An allowed move between two statuses. An empty `roles` list means any
+/// role (or no role at all) may perform it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowTransition {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub roles: Vec<WorkflowRole>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub initial_status: String,
+    pub statuses: Vec<WorkflowStatus>,
+    pub transitions: Vec<WorkflowTransition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkflowError {
+    Invalid(String),
+    UnknownStatus(String),
+    NoStatusForCategory(TranslationStatus),
+    StatusMismatch { status: TranslationStatus, workflow_status: String },
+    TransitionNotAllowed { from: String, to: String },
+    RoleNotAllowed { from: String, to: String, role: Option<WorkflowRole> },
+}
+
+impl fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkflowError::Invalid(reason) => write!(f, "Invalid workflow: {}", reason),
+            WorkflowError::UnknownStatus(id) => write!(f, "Unknown workflow status '{}'", id),
+            WorkflowError::NoStatusForCategory(status) => {
+                write!(f, "Workflow has no status in category {}", status.as_str())
+            }
+            WorkflowError::StatusMismatch { status, workflow_status } => write!(
+                f,
+                "Workflow status '{}' does not belong to category {}",
+                workflow_status,
+                status.as_str()
+            ),
+            WorkflowError::TransitionNotAllowed { from, to } => {
+                write!(f, "Transition from '{}' to '{}' is not allowed", from, to)
+            }
+            WorkflowError::RoleNotAllowed { from, to, role: Some(role) } => write!(
+                f,
+                "Role {:?} may not move a translation from '{}' to '{}'",
+                role, from, to
+            ),
+            WorkflowError::RoleNotAllowed { from, to, role: None } => write!(
+                f,
+                "Moving a translation from '{}' to '{}' requires a role",
+                from, to
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WorkflowError {}
+
+impl Default for Workflow {
+    /// The original Draft/Validated/Approved flow, with every transition open
+    /// to everyone.
+    fn default() -> Self {
+        let statuses: Vec<WorkflowStatus> = TranslationStatus::ALL
+            .iter()
+            .map(|status| WorkflowStatus {
+                id: status.as_str().to_string(),
+                label: status.as_str().to_string(),
+                category: *status,
+            })
+            .collect();
+
+        let mut transitions = Vec::new();
+        for from in &statuses {
+            for to in &statuses {
+                if from.id != to.id {
+                    transitions.push(WorkflowTransition {
+                        from: from.id.clone(),
+                        to: to.id.clone(),
+                        roles: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        Workflow {
+            initial_status: TranslationStatus::Draft.as_str().to_string(),
+            statuses,
+            transitions,
+        }
+    }
+}
+
+impl Workflow {
+    pub fn validate(&self) -> Result<(), WorkflowError> {
+        if self.statuses.is_empty() {
+            return Err(WorkflowError::Invalid("at least one status is required".to_string()));
+        }
+
+        let mut ids = HashSet::new();
+        for status in &self.statuses {
+            if status.id.trim().is_empty() {
+                return Err(WorkflowError::Invalid("status ids cannot be empty".to_string()));
+            }
+            if !ids.insert(status.id.as_str()) {
+                return Err(WorkflowError::Invalid(format!("duplicate status '{}'", status.id)));
+            }
+        }
+
+        if !ids.contains(self.initial_status.as_str()) {
+            return Err(WorkflowError::UnknownStatus(self.initial_status.clone()));
+        }
+
+        for transition in &self.transitions {
+            for id in [&transition.from, &transition.to] {
+                if !ids.contains(id.as_str()) {
+                    return Err(WorkflowError::UnknownStatus(id.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn status(&self, id: &str) -> Option<&WorkflowStatus> {
+        self.statuses.iter().find(|status| status.id == id)
+    }
+
+    /// The first status (in definition order) that belongs to `category`.
+    pub fn status_for_category(&self, category: TranslationStatus) -> Option<&WorkflowStatus> {
+        self.statuses.iter().find(|status| status.category == category)
+    }
+
+    /// Resolves the status a caller asked for. Either argument may be given;
+    /// when both are, they have to agree.
+    pub fn resolve(
+        &self,
+        status: Option<TranslationStatus>,
+        workflow_status: Option<&str>,
+    ) -> Result<&WorkflowStatus, WorkflowError> {
+        match (status, workflow_status) {
+            (_, Some(id)) => {
+                let target = self
+                    .status(id)
+                    .ok_or_else(|| WorkflowError::UnknownStatus(id.to_string()))?;
+                match status {
+                    Some(status) if status != target.category => Err(WorkflowError::StatusMismatch {
+                        status,
+                        workflow_status: id.to_string(),
+                    }),
+                    _ => Ok(target),
+                }
+            }
+            (Some(status), None) => self
+                .status_for_category(status)
+                .ok_or(WorkflowError::NoStatusForCategory(status)),
+            (None, None) => self
+                .status(&self.initial_status)
+                .ok_or_else(|| WorkflowError::UnknownStatus(self.initial_status.clone())),
+        }
+    }
+
+    pub fn check_transition(&self, from: &str, to: &str, role: Option<WorkflowRole>) -> Result<(), WorkflowError> {
+        if from == to {
+            return Ok(());
+        }
+
+        let transition = self
+            .transitions
+            .iter()
+            .find(|transition| transition.from == from && transition.to == to)
+            .ok_or_else(|| WorkflowError::TransitionNotAllowed {
+                from: from.to_string(),
+                to: to.to_string(),
+            })?;
+
+        if transition.roles.is_empty() || role.is_some_and(|role| transition.roles.contains(&role)) {
+            Ok(())
+        } else {
+            Err(WorkflowError::RoleNotAllowed {
+                from: from.to_string(),
+                to: to.to_string(),
+                role,
+            })
+        }
+    }
+}
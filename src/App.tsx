@@ -1,4 +1,5 @@
-import { useEffect } from 'react';
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
 import { useStore } from './store/useStore';
 import { useWindowTitle } from './hooks/useWindowTitle';
 import Sidebar from './components/Sidebar.tsx';
@@ -10,6 +11,8 @@ import SettingsModal from './components/SettingsModal.tsx';
 import TitleBarOverlay from './components/TitleBarOverlay.tsx';
 import './App.css';
 
+type BackendHealth = { status: 'ready' } | { status: 'failed'; reason: string };
+
 function App() {
   const { 
     currentProject, 
@@ -23,8 +26,19 @@ function App() {
     setChatPanelWidth
   } = useStore();
 
+  const [backendError, setBackendError] = useState<string | null>(null);
+
+  // Only load data once the backend reports it started
   useEffect(() => {
-    loadProjects();
+    invoke<BackendHealth>('backend_health')
+      .then((health) => {
+        if (health.status === 'failed') {
+          setBackendError(health.reason);
+        } else {
+          loadProjects();
+        }
+      })
+      .catch((error) => setBackendError(String(error)));
   }, [loadProjects]);
 
   // Update window title when project changes
@@ -33,6 +47,22 @@ function App() {
   });
 
 
+  if (backendError) {
+    return (
+      <div className="h-screen flex flex-col bg-background-primary dark">
+        <TitleBarOverlay />
+        <div className="flex-1 flex items-center justify-center">
+          <div className="text-center animate-fade-in">
+            <h2 className="text-lg font-medium text-text-primary mb-2">
+              The backend failed to start
+            </h2>
+            <p className="text-sm text-text-secondary">{backendError}</p>
+          </div>
+        </div>
+      </div>
+    );
+  }
+
   return (
     <div className="h-screen flex flex-col bg-background-primary dark">
       {/* Title Bar Overlay */}